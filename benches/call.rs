@@ -0,0 +1,73 @@
+// mrusty. mruby safe bindings for Rust
+// Copyright (C) 2016  Dragoș Tiselice
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+#![feature(test)]
+
+extern crate mrusty;
+extern crate test;
+
+use mrusty::*;
+use test::Bencher;
+
+#[bench]
+fn call_plus(b: &mut Bencher) {
+    let mruby = Mruby::new();
+    let one = mruby.fixnum(1);
+
+    b.iter(|| {
+        for _ in 0..1000 {
+            one.call("+", vec![one.clone()]).unwrap();
+        }
+    });
+}
+
+#[bench]
+fn call_sym_plus(b: &mut Bencher) {
+    let mruby = Mruby::new();
+    let one = mruby.fixnum(1);
+    let plus = mruby.sym("+");
+
+    b.iter(|| {
+        for _ in 0..1000 {
+            one.call_sym(plus, vec![one.clone()]).unwrap();
+        }
+    });
+}
+
+#[bench]
+fn call_long_name(b: &mut Bencher) {
+    let mruby = Mruby::new();
+    let one = mruby.fixnum(1);
+
+    b.iter(|| {
+        for _ in 0..1000 {
+            one.call("respond_to?", vec![mruby.symbol("a_rather_long_method_name")]).unwrap();
+        }
+    });
+}
+
+#[bench]
+fn call_sym_long_name(b: &mut Bencher) {
+    let mruby = Mruby::new();
+    let one = mruby.fixnum(1);
+    let respond_to = mruby.sym("respond_to?");
+
+    b.iter(|| {
+        for _ in 0..1000 {
+            one.call_sym(respond_to, vec![mruby.symbol("a_rather_long_method_name")]).unwrap();
+        }
+    });
+}
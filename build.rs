@@ -26,9 +26,14 @@ fn main() {
         if is_c(&entry) { config.file(entry.path()); }
     }
 
-    config.include("target/mruby-out/include").compile("libmruby.a");
+    config.include("target/mruby-out/include")
+          .define("MRB_ENABLE_DEBUG_HOOK", None)
+          .compile("libmruby.a");
 
     let mut config = gcc::Config::new();
 
-    config.file("src/mrb_ext.c").include("target/mruby-out/include").compile("libmrbe.a");
+    config.file("src/mrb_ext.c")
+          .include("target/mruby-out/include")
+          .define("MRB_ENABLE_DEBUG_HOOK", None)
+          .compile("libmrbe.a");
 }
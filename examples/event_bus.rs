@@ -0,0 +1,49 @@
+// Rust firing blocks registered by scripts.
+#[macro_use]
+extern crate mrusty;
+
+use mrusty::*;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+fn main() {
+    let mruby = Mruby::new();
+
+    // Blocks registered by scripts, fired later on from Rust.
+    let handlers = Rc::new(RefCell::new(Vec::<Value>::new()));
+    let registry = handlers.clone();
+
+    // mrfn! closures are move, so they can share the registry with Rust.
+    mruby.def_method_on("Object", "on_event", mrfn!(|mruby, _slf: Value, &block| {
+        if !block.is_proc() {
+            return mruby.raise("ArgumentError", "on_event needs a block");
+        }
+
+        // After on_event returns, the block is only referenced from Rust. Protect it from the
+        // garbage collector until it's released.
+        block.gc_protect();
+        registry.borrow_mut().push(block);
+
+        mruby.nil()
+    })).unwrap();
+
+    mruby.run("
+        on_event { |event| puts \"first got #{event}\" }
+        on_event { |event| puts \"second got #{event}\" }
+    ").unwrap();
+
+    // Nothing in mruby references the blocks anymore, yet they survive collections.
+    mruby.full_gc();
+
+    for event in &["start", "stop"] {
+        for handler in handlers.borrow().iter() {
+            handler.call_proc(vec![mruby.symbol(event)]).unwrap();
+        }
+    }
+
+    // Release the blocks once they are not needed anymore.
+    for handler in handlers.borrow_mut().drain(..) {
+        handler.gc_release();
+    }
+}
@@ -0,0 +1,394 @@
+// mrusty. mruby safe bindings for Rust
+// Copyright (C) 2016  Dragoș Tiselice
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! # mrusty_derive. `#[derive(MrubyFile)]` for mrusty
+//!
+//! Deriving `MrubyFile` on a `struct` with named fields generates a `require` implementation
+//! which defines the mruby `Class` and a getter for every `pub` field with `getters!`. Field
+//! types need to implement `ToValue`; fields are cloned on every call. The `Class` name defaults
+//! to the Rust type name and can be set with `#[mruby_class(name = "Vector")]`.
+//!
+//! Methods are defined by marking them with `#[mruby_method]` inside an `impl` block annotated
+//! with `#[mruby_methods]`. Each of them is defined with `def_method` and `mrfn!`:
+//!
+//! * methods taking `&self` become instance methods
+//! * associated functions become `Class` methods, except for
+//!   `#[mruby_method(name = "initialize")]`, which initializes the object with the returned `Self`
+//! * `#[mruby_method(name = "to_s")]` sets the mruby method name (defaults to the Rust name)
+//!
+//! Arguments can be of any type supported by `mrfn!`, with `&str` for `str` and `&T` for objects
+//! of defined `Class`es. Results are converted with `ToValue`, `Self` is wrapped with `obj` and
+//! methods without a result return `nil`.
+//!
+//! The generated code uses `mrfn!`, so it requires `#[macro_use] extern crate mrusty;` and
+//! `use mrusty::*;`.
+//!
+//! # Examples
+//!
+//! ```
+//! #[macro_use]
+//! extern crate mrusty;
+//! #[macro_use]
+//! extern crate mrusty_derive;
+//!
+//! use mrusty::*;
+//!
+//! #[derive(MrubyFile)]
+//! #[mruby_class(name = "Point")]
+//! pub struct Point {
+//!     pub x: i32,
+//!     pub y: i32,
+//!     secret: i32
+//! }
+//!
+//! #[mruby_methods]
+//! impl Point {
+//!     #[mruby_method(name = "initialize")]
+//!     fn new(x: i32, y: i32) -> Point {
+//!         Point { x: x, y: y, secret: 0 }
+//!     }
+//!
+//!     #[mruby_method]
+//!     fn sum(&self) -> i32 {
+//!         self.x + self.y
+//!     }
+//! }
+//!
+//! # fn main() {
+//! let mruby = Mruby::new();
+//!
+//! Point::require(mruby.clone());
+//!
+//! let result = mruby.run("point = Point.new 1, 2; point.x + point.sum").unwrap();
+//!
+//! assert_eq!(result.to_i32().unwrap(), 4);
+//! assert!(mruby.run("Point.new(1, 2).secret").is_err());
+//! # }
+//! ```
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+#[macro_use]
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as Tokens;
+use syn::spanned::Spanned;
+use syn::{Attribute, Data, DeriveInput, Error, Fields, FnArg, ImplItem, ImplItemMethod, ItemImpl,
+          Lit, Meta, NestedMeta, Pat, ReturnType, Type, Visibility};
+
+/// Derives `MrubyFile` for a `struct` with named fields. See the crate documentation.
+#[proc_macro_derive(MrubyFile, attributes(mruby_class))]
+pub fn derive_mruby_file(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand_class(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err)   => err.to_compile_error().into()
+    }
+}
+
+/// Defines the `#[mruby_method]` methods of an `impl` block on the mruby `Class` derived with
+/// `#[derive(MrubyFile)]`. See the crate documentation.
+#[proc_macro_attribute]
+pub fn mruby_methods(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let attr = Tokens::from(attr);
+
+    if !attr.is_empty() {
+        return Error::new(attr.span(), "#[mruby_methods] takes no arguments")
+                     .to_compile_error().into();
+    }
+
+    let item = parse_macro_input!(item as ItemImpl);
+
+    match expand_methods(item) {
+        Ok(tokens) => tokens.into(),
+        Err(err)   => err.to_compile_error().into()
+    }
+}
+
+/// Marks a method of a `#[mruby_methods]` `impl` block to be defined in mruby. See the crate
+/// documentation.
+#[proc_macro_attribute]
+pub fn mruby_method(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let item = Tokens::from(item);
+
+    Error::new(item.span(), "#[mruby_method] needs to be inside a #[mruby_methods] impl block")
+         .to_compile_error().into()
+}
+
+// Returns the value of `name = "..."` in `#[attr_name(...)]` attributes, if any.
+fn name_attr(attrs: &[Attribute], attr_name: &str) -> Result<Option<String>, Error> {
+    let mut name = None;
+
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident(attr_name)) {
+        let list = match try!(attr.parse_meta()) {
+            Meta::Path(_)     => continue,
+            Meta::List(list)  => list,
+            meta              => {
+                return Err(Error::new(meta.span(), format!("expected #[{}(name = \"...\")]",
+                                                           attr_name)))
+            }
+        };
+
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::NameValue(ref pair)) if pair.path.is_ident("name") => {
+                    match pair.lit {
+                        Lit::Str(ref value) => name = Some(value.value()),
+                        ref lit             => {
+                            return Err(Error::new(lit.span(), "expected a string"))
+                        }
+                    }
+                },
+                nested => {
+                    return Err(Error::new(nested.span(), format!("unknown #[{}] argument",
+                                                                 attr_name)))
+                }
+            }
+        }
+    }
+
+    Ok(name)
+}
+
+fn expand_class(input: &DeriveInput) -> Result<Tokens, Error> {
+    let ident = &input.ident;
+
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(input.generics.span(),
+                              "#[derive(MrubyFile)] does not support generic structs"))
+    }
+
+    let fields = match input.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => &fields.named,
+            _                         => {
+                return Err(Error::new(ident.span(),
+                                      "#[derive(MrubyFile)] only supports structs with named \
+                                       fields"))
+            }
+        },
+        _ => return Err(Error::new(ident.span(), "#[derive(MrubyFile)] only supports structs"))
+    };
+
+    let name = try!(name_attr(&input.attrs, "mruby_class")).unwrap_or_else(|| {
+        ident.to_string()
+    });
+
+    let getters = fields.iter().filter(|field| {
+        match field.vis {
+            Visibility::Public(_) => true,
+            _                     => false
+        }
+    }).map(|field| {
+        let field_ident = &field.ident;
+        let ty = &field.ty;
+
+        quote! { #field_ident: #ty }
+    });
+
+    // Calls the mruby_methods function generated by #[mruby_methods], which takes precedence
+    // over the empty fallback of the trait when it exists.
+    Ok(quote! {
+        impl MrubyFile for #ident {
+            fn require(mruby: MrubyType) {
+                #[allow(dead_code)]
+                trait MrubyMethods {
+                    fn mruby_methods(_mruby: MrubyType) {}
+                }
+
+                impl MrubyMethods for #ident {}
+
+                mruby.def_class::<#ident>(#name);
+
+                defines!(mruby, #ident, getters!(#( #getters ),*); );
+
+                #ident::mruby_methods(mruby.clone());
+            }
+        }
+    })
+}
+
+fn expand_methods(mut item: ItemImpl) -> Result<Tokens, Error> {
+    let ident = match *item.self_ty {
+        Type::Path(ref path) if path.qself.is_none() && path.path.get_ident().is_some() => {
+            path.path.get_ident().unwrap().clone()
+        },
+        ref ty => {
+            return Err(Error::new(ty.span(), "#[mruby_methods] only supports impl blocks of \
+                                              structs named without a path"))
+        }
+    };
+
+    if item.trait_.is_some() || !item.generics.params.is_empty() {
+        return Err(Error::new(item.span(), "#[mruby_methods] only supports inherent impl \
+                                            blocks without generics"))
+    }
+
+    let mut defs = vec![];
+
+    for impl_item in &mut item.items {
+        if let ImplItem::Method(ref mut method) = *impl_item {
+            let marked = method.attrs.iter().any(|attr| attr.path.is_ident("mruby_method"));
+
+            if marked {
+                defs.push(try!(def_method(&ident, method)));
+
+                method.attrs.retain(|attr| !attr.path.is_ident("mruby_method"));
+            }
+        }
+    }
+
+    Ok(quote! {
+        #item
+
+        impl #ident {
+            #[doc(hidden)]
+            pub fn mruby_methods(mruby: MrubyType) {
+                #( #defs )*
+            }
+        }
+    })
+}
+
+// Returns the mrfn! type of an argument and the expression passing it to the Rust method.
+fn arg(name: &syn::Ident, ty: &Type) -> Result<(Tokens, Tokens), Error> {
+    if let Type::Reference(ref reference) = *ty {
+        if reference.mutability.is_some() {
+            return Err(Error::new(ty.span(), "#[mruby_method] does not support &mut arguments"))
+        }
+
+        return match *reference.elem {
+            Type::Path(ref path) if path.path.is_ident("str") => {
+                Ok((quote! { str }, quote! { #name }))
+            },
+            Type::Path(ref path) if path.path.get_ident().is_some() => {
+                let elem = &reference.elem;
+
+                Ok((quote! { #elem }, quote! { &*#name }))
+            },
+            ref elem => Err(Error::new(elem.span(), "unsupported #[mruby_method] argument type"))
+        }
+    }
+
+    if let Type::Path(ref path) = *ty {
+        let last = path.path.segments.last().unwrap();
+
+        if path.path.segments.len() == 1 && last.ident == "Vec" {
+            return Ok((quote! { Vec }, quote! { #name }))
+        }
+
+        if path.path.get_ident().is_some() || path.path.segments.len() == 1 &&
+                                              last.ident == "Option" {
+            return Ok((quote! { #ty }, quote! { #name }))
+        }
+    }
+
+    Err(Error::new(ty.span(), "unsupported #[mruby_method] argument type"))
+}
+
+fn def_method(ident: &syn::Ident, method: &ImplItemMethod) -> Result<Tokens, Error> {
+    let rust_name = &method.sig.ident;
+    let name = try!(name_attr(&method.attrs, "mruby_method")).unwrap_or_else(|| {
+        rust_name.to_string()
+    });
+
+    let mut receiver = false;
+    let mut params = vec![];
+    let mut args = vec![];
+
+    for input in &method.sig.inputs {
+        match *input {
+            FnArg::Receiver(ref slf) => {
+                if slf.reference.is_none() || slf.mutability.is_some() {
+                    return Err(Error::new(slf.span(), "#[mruby_method] methods need to take \
+                                                       &self"))
+                }
+
+                receiver = true;
+            },
+            FnArg::Typed(ref typed) => {
+                let arg_name = match *typed.pat {
+                    Pat::Ident(ref pat) => &pat.ident,
+                    ref pat             => {
+                        return Err(Error::new(pat.span(), "expected an argument name"))
+                    }
+                };
+
+                let (ty, value) = try!(arg(arg_name, &typed.ty));
+
+                params.push(quote! { #arg_name: #ty });
+                args.push(value);
+            }
+        }
+    }
+
+    // mrfn! does not accept a trailing comma after self.
+    let params = if params.is_empty() {
+        quote! {}
+    } else {
+        quote! { , #( #params ),* }
+    };
+
+    let call = if receiver {
+        quote! { slf.#rust_name(#( #args ),*) }
+    } else {
+        quote! { #ident::#rust_name(#( #args ),*) }
+    };
+
+    let returns_self = match method.sig.output {
+        ReturnType::Type(_, ref ty) => match **ty {
+            Type::Path(ref path) => path.path.is_ident("Self") || path.path.is_ident(ident),
+            _                    => false
+        },
+        ReturnType::Default => false
+    };
+
+    if !receiver && name == "initialize" {
+        if !returns_self {
+            return Err(Error::new(method.sig.span(), "initialize needs to return Self"))
+        }
+
+        return Ok(quote! {
+            mruby.def_method::<#ident, _>(#name, mrfn!(|mruby, slf: Value #params| {
+                slf.init(#call)
+            }));
+        })
+    }
+
+    let result = match method.sig.output {
+        ReturnType::Default => quote! {{ #call; mruby.nil() }},
+        _ if returns_self   => quote! { mruby.obj(#call) },
+        _                   => quote! { ToValue::to_value(#call, &mruby) }
+    };
+
+    if receiver {
+        Ok(quote! {
+            mruby.def_method::<#ident, _>(#name, mrfn!(|mruby, slf: #ident #params| {
+                #result
+            }));
+        })
+    } else {
+        Ok(quote! {
+            mruby.def_class_method::<#ident, _>(#name, mrfn!(|mruby, _slf: Value #params| {
+                #result
+            }));
+        })
+    }
+}
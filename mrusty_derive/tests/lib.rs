@@ -0,0 +1,122 @@
+// mrusty. mruby safe bindings for Rust
+// Copyright (C) 2016  Dragoș Tiselice
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+#[macro_use]
+extern crate mrusty;
+#[macro_use]
+extern crate mrusty_derive;
+
+use mrusty::*;
+
+/// A vector.
+#[derive(Clone, Debug, PartialEq, MrubyFile)]
+#[mruby_class(name = "Vector")]
+pub struct Vec3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    // not visible in mruby
+    tag: Option<Vec<(i32, String)>>
+}
+
+#[mruby_methods]
+impl Vec3 {
+    #[mruby_method(name = "initialize")]
+    fn new(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3 { x: x, y: y, z: z, tag: None }
+    }
+
+    #[mruby_method]
+    fn zero() -> Self {
+        Vec3::new(0.0, 0.0, 0.0)
+    }
+
+    #[mruby_method]
+    fn dot(&self, other: &Vec3) -> f64 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    #[mruby_method(name = "scale")]
+    fn scaled(&self, factor: f64) -> Vec3 {
+        Vec3::new(self.x * factor, self.y * factor, self.z * factor)
+    }
+
+    #[mruby_method]
+    fn describe(&self, label: &str, precision: Option<i32>) -> String {
+        format!("{} {:.*}", label, precision.unwrap_or(1) as usize, self.x)
+    }
+
+    #[mruby_method]
+    fn check(&self) {}
+
+    // not visible in mruby
+    #[allow(dead_code)]
+    fn hidden(&self) -> f64 {
+        self.x
+    }
+}
+
+#[derive(MrubyFile)]
+struct Named {
+    pub name: String
+}
+
+#[test]
+fn derive_getters() {
+    let mruby = Mruby::new();
+
+    Vec3::require(mruby.clone());
+
+    let result = mruby.run("v = Vector.new 1.0, 2.0, 3.0; [v.x, v.y, v.z]").unwrap();
+
+    assert_eq!(result.to_vec().unwrap(), vec![mruby.float(1.0), mruby.float(2.0),
+                                              mruby.float(3.0)]);
+    assert!(mruby.run("Vector.new(1.0, 2.0, 3.0).tag").is_err());
+}
+
+#[test]
+fn derive_methods() {
+    let mruby = Mruby::new();
+
+    Vec3::require(mruby.clone());
+
+    let result = mruby.run("v = Vector.new 1.0, 2.0, 3.0; v.dot v.scale(2.0)").unwrap();
+
+    assert_eq!(result.to_f64().unwrap(), 28.0);
+
+    let zero = mruby.run("Vector.zero").unwrap();
+
+    assert_eq!(*zero.to_obj::<Vec3>().unwrap(), Vec3::new(0.0, 0.0, 0.0));
+
+    let result = mruby.run("Vector.new(1.0, 2.0, 3.0).describe 'x', 2").unwrap();
+
+    assert_eq!(result.to_str().unwrap(), "x 1.00");
+    assert!(mruby.run("Vector.new(1.0, 2.0, 3.0).check").unwrap().is_nil());
+    assert!(mruby.run("Vector.new(1.0, 2.0, 3.0).hidden").is_err());
+    assert!(mruby.run("Vector.new(1.0, 2.0, 3.0).dot 1").is_err());
+}
+
+#[test]
+fn derive_default_name() {
+    let mruby = Mruby::new();
+
+    Named::require(mruby.clone());
+
+    let named = mruby.obj(Named { name: "mruby".to_owned() });
+
+    assert_eq!(named.call("name", vec![]).unwrap().to_str().unwrap(), "mruby");
+    assert!(mruby.run("Named").is_ok());
+}
@@ -0,0 +1,224 @@
+// mrusty. mruby safe bindings for Rust
+// Copyright (C) 2016  Dragoș Tiselice
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use super::mruby::*;
+
+/// A `trait` for Rust types that can be cast from an mruby `Value`.
+///
+/// Implemented for `Value`, `bool`, `i32`, `i64`, `f32`, `f64`, `String`, `Vec<T>` (from `Array`)
+/// and `Option<T>` (`nil` becomes `None`) where `T` implements `FromValue`.
+///
+/// # Examples
+///
+/// ```
+/// # use mrusty::*;
+/// let mruby = Mruby::new();
+/// let result = mruby.run("[1, nil, 3]").unwrap();
+///
+/// let vec = Vec::<Option<i32>>::from_value(&result).unwrap();
+///
+/// assert_eq!(vec, vec![Some(1), None, Some(3)]);
+/// ```
+pub trait FromValue: Sized {
+    /// Casts `value` and returns a `Self` in an `Ok` or an `Err` if the types mismatch.
+    fn from_value(value: &Value) -> Result<Self, MrubyError>;
+}
+
+/// A `trait` for Rust types that can be converted to an mruby `Value`.
+///
+/// Implemented for `Value`, `bool`, `i32`, `i64`, `f32`, `f64`, `&str`, `String`, `Vec<T>` (to
+/// `Array`) and `Option<T>` (`None` becomes `nil`) where `T` implements `ToValue`.
+///
+/// *Note:* Like mruby's own integer arithmetic, `i64`s that overflow `Fixnum` become `Float`s.
+///
+/// # Examples
+///
+/// ```
+/// # use mrusty::*;
+/// let mruby = Mruby::new();
+///
+/// let array = vec![Some("a"), None].to_value(&mruby);
+///
+/// assert_eq!(array.to_vec().unwrap(), vec![mruby.string("a"), mruby.nil()]);
+/// ```
+pub trait ToValue {
+    /// Converts `self` to an mruby `Value` created on `mruby`.
+    fn to_value(self, mruby: &MrubyType) -> Value;
+}
+
+/// Not meant to be called directly.
+#[doc(hidden)]
+pub fn arg_type_error(value: &Value, expected: &str, argument: &str) -> String {
+    format!("wrong argument type {} for {} (expected {})", value.type_name(), argument, expected)
+}
+
+/// Not meant to be called directly.
+#[doc(hidden)]
+pub fn kwargs_from_value(opts: &Value, keys: &[&str]) -> Result<HashMap<String, Value>, String> {
+    // opts is either nil or a Hash.
+    let pairs = match opts.to_hash() {
+        Ok(pairs) => pairs,
+        Err(_)    => vec![]
+    };
+
+    let mut kwargs = HashMap::new();
+
+    for (key, value) in pairs {
+        match key.to_sym_name() {
+            Ok(name) if keys.contains(&name) => kwargs.insert(name.to_owned(), value),
+            _ => return Err(format!("unknown keyword: {}", key.inspect()))
+        };
+    }
+
+    Ok(kwargs)
+}
+
+/// Not meant to be called directly.
+#[doc(hidden)]
+pub fn arg_from_value<T: FromValue>(value: &Value, argument: &str) -> Result<T, String> {
+    T::from_value(value).map_err(|err| {
+        match err {
+            MrubyError::Cast(expected) => arg_type_error(value, &expected, argument),
+            err                        => err.to_string()
+        }
+    })
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Result<Value, MrubyError> {
+        Ok(value.clone())
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Result<bool, MrubyError> {
+        value.to_bool()
+    }
+}
+
+impl FromValue for i32 {
+    fn from_value(value: &Value) -> Result<i32, MrubyError> {
+        value.to_i32()
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: &Value) -> Result<i64, MrubyError> {
+        value.to_i64()
+    }
+}
+
+impl FromValue for f32 {
+    fn from_value(value: &Value) -> Result<f32, MrubyError> {
+        value.to_f32()
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Result<f64, MrubyError> {
+        value.to_f64()
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<String, MrubyError> {
+        value.to_str().map(|string| string.to_owned())
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: &Value) -> Result<Vec<T>, MrubyError> {
+        value.to_vec_of()
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> Result<Option<T>, MrubyError> {
+        if value.is_nil() {
+            Ok(None)
+        } else {
+            T::from_value(value).map(|value| Some(value))
+        }
+    }
+}
+
+impl ToValue for Value {
+    fn to_value(self, _mruby: &MrubyType) -> Value {
+        self
+    }
+}
+
+impl ToValue for bool {
+    fn to_value(self, mruby: &MrubyType) -> Value {
+        mruby.bool(self)
+    }
+}
+
+impl ToValue for i32 {
+    fn to_value(self, mruby: &MrubyType) -> Value {
+        mruby.fixnum(self)
+    }
+}
+
+impl ToValue for i64 {
+    fn to_value(self, mruby: &MrubyType) -> Value {
+        match mruby.int64(self) {
+            Ok(value) => value,
+            Err(_)    => mruby.float(self as f64)
+        }
+    }
+}
+
+impl ToValue for f32 {
+    fn to_value(self, mruby: &MrubyType) -> Value {
+        mruby.float32(self)
+    }
+}
+
+impl ToValue for f64 {
+    fn to_value(self, mruby: &MrubyType) -> Value {
+        mruby.float(self)
+    }
+}
+
+impl<'a> ToValue for &'a str {
+    fn to_value(self, mruby: &MrubyType) -> Value {
+        mruby.string(self)
+    }
+}
+
+impl ToValue for String {
+    fn to_value(self, mruby: &MrubyType) -> Value {
+        mruby.string(&self)
+    }
+}
+
+impl<T: ToValue> ToValue for Vec<T> {
+    fn to_value(self, mruby: &MrubyType) -> Value {
+        mruby.array_of(self)
+    }
+}
+
+impl<T: ToValue> ToValue for Option<T> {
+    fn to_value(self, mruby: &MrubyType) -> Value {
+        match self {
+            Some(value) => value.to_value(mruby),
+            None        => mruby.nil()
+        }
+    }
+}
@@ -0,0 +1,193 @@
+// mrusty. mruby safe bindings for Rust
+// Copyright (C) 2016  Dragoș Tiselice
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Lesser General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::i64;
+
+use serde_json;
+
+use super::convert::ToValue;
+use super::mruby::*;
+
+impl Value {
+    /// Converts an mruby `Value` to a `serde_json::Value` recursively. `Symbol`s become strings
+    /// and `Hash` keys need to be `String`s or `Symbol`s.
+    ///
+    /// Returns an `Err` for values that cannot be represented in JSON, like `Proc`s, Rust objects
+    /// or an `Array` or `Hash` nested in itself. Use `to_json_lossy` to `inspect` them instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate mrusty;
+    /// # extern crate serde_json;
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// use serde_json::Value as Json;
+    ///
+    /// # fn main() {
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("[1, 2.5, 'a', :b, nil]").unwrap();
+    ///
+    /// assert_eq!(result.to_json().unwrap(), Json::Array(vec![
+    ///     Json::I64(1),
+    ///     Json::F64(2.5),
+    ///     Json::String("a".to_owned()),
+    ///     Json::String("b".to_owned()),
+    ///     Json::Null
+    /// ]));
+    ///
+    /// assert!(mruby.run("[proc {}]").unwrap().to_json().is_err());
+    /// assert!(mruby.run("a = [1]; a << a; a").unwrap().to_json().is_err());
+    /// # }
+    /// ```
+    pub fn to_json(&self) -> Result<serde_json::Value, MrubyError> {
+        to_json(self, false, &mut vec![])
+    }
+
+    /// Converts an mruby `Value` to a `serde_json::Value` like `to_json`, but stringifies values
+    /// that cannot be represented in JSON with `inspect`. An `Array` or `Hash` nested in itself
+    /// becomes `"[...]"` or `"{...}"`, like in Ruby's `inspect`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate mrusty;
+    /// # extern crate serde_json;
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// use serde_json::Value as Json;
+    ///
+    /// # fn main() {
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("[Object]").unwrap();
+    ///
+    /// assert_eq!(result.to_json_lossy(), Json::Array(vec![Json::String("Object".to_owned())]));
+    ///
+    /// let result = mruby.run("a = [1]; a << a; a").unwrap();
+    ///
+    /// assert_eq!(result.to_json_lossy(), Json::Array(vec![Json::I64(1),
+    ///                                                     Json::String("[...]".to_owned())]));
+    /// # }
+    /// ```
+    pub fn to_json_lossy(&self) -> serde_json::Value {
+        to_json(self, true, &mut vec![]).unwrap()
+    }
+}
+
+// path holds the Arrays and Hashes being converted, so that ones containing themselves are caught
+// instead of recursing forever.
+fn to_json(value: &Value, lossy: bool,
+           path: &mut Vec<Value>) -> Result<serde_json::Value, MrubyError> {
+    if value.is_nil() {
+        return Ok(serde_json::Value::Null);
+    }
+
+    if let Ok(value) = value.to_bool() {
+        return Ok(serde_json::Value::Bool(value));
+    }
+
+    if let Ok(value) = value.to_i64() {
+        return Ok(serde_json::Value::I64(value));
+    }
+
+    if let Ok(value) = value.to_f64() {
+        return Ok(serde_json::Value::F64(value));
+    }
+
+    if let Ok(value) = value.to_str() {
+        return Ok(serde_json::Value::String(value.to_owned()));
+    }
+
+    if path.iter().any(|parent| parent.identical(value)) {
+        // mruby's inspect recurses into the cycle as well, so Ruby's placeholders are used.
+        return if lossy {
+            let placeholder = if value.to_vec().is_ok() { "[...]" } else { "{...}" };
+
+            Ok(serde_json::Value::String(placeholder.to_owned()))
+        } else {
+            Err(MrubyError::Cast("Array or Hash without cycles".to_owned()))
+        };
+    }
+
+    if let Ok(vec) = value.to_vec() {
+        path.push(value.clone());
+
+        let vec: Result<Vec<_>, _> = vec.iter().map(|value| to_json(value, lossy, path)).collect();
+
+        path.pop();
+
+        return vec.map(|vec| serde_json::Value::Array(vec));
+    }
+
+    if let Ok(pairs) = value.to_hash() {
+        path.push(value.clone());
+
+        let object = hash_to_json(pairs, lossy, path);
+
+        path.pop();
+
+        return object;
+    }
+
+    if lossy {
+        Ok(serde_json::Value::String(value.inspect()))
+    } else {
+        Err(MrubyError::Cast("nil, TrueClass, FalseClass, Fixnum, Float, String, Symbol, Array \
+                              or Hash".to_owned()))
+    }
+}
+
+fn hash_to_json(pairs: Vec<(Value, Value)>, lossy: bool,
+                path: &mut Vec<Value>) -> Result<serde_json::Value, MrubyError> {
+    let mut object = Vec::with_capacity(pairs.len());
+
+    for (key, value) in pairs {
+        let key = match key.to_str() {
+            Ok(key)         => key.to_owned(),
+            Err(_) if lossy => key.inspect(),
+            Err(err)        => return Err(err)
+        };
+
+        object.push((key, try!(to_json(&value, lossy, path))));
+    }
+
+    Ok(serde_json::Value::Object(object.into_iter().collect()))
+}
+
+pub fn from_json(mruby: &MrubyType, json: &serde_json::Value) -> Value {
+    match *json {
+        serde_json::Value::Null              => mruby.nil(),
+        serde_json::Value::Bool(value)       => mruby.bool(value),
+        serde_json::Value::I64(value)        => value.to_value(mruby),
+        serde_json::Value::U64(value)        => {
+            if value <= i64::MAX as u64 {
+                (value as i64).to_value(mruby)
+            } else {
+                mruby.float(value as f64)
+            }
+        },
+        serde_json::Value::F64(value)        => mruby.float(value),
+        serde_json::Value::String(ref value) => mruby.string(value),
+        serde_json::Value::Array(ref vec)    => {
+            mruby.array(vec.iter().map(|json| from_json(mruby, json)).collect())
+        },
+        serde_json::Value::Object(ref map)   => {
+            mruby.hash(map.iter().map(|(key, json)| {
+                (mruby.string(key), from_json(mruby, json))
+            }).collect())
+        }
+    }
+}
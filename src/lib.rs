@@ -28,7 +28,12 @@
 
 #[cfg(feature = "gnu-readline")]
 extern crate rl_sys;
+#[cfg(feature = "serde")]
+extern crate serde_json;
 
+mod convert;
+#[cfg(feature = "serde")]
+mod json;
 mod macros;
 mod mruby;
 mod mruby_ffi;
@@ -43,13 +48,30 @@ pub use mruby_ffi::MrValue;
 /// Not meant to be called directly.
 #[doc(hidden)]
 pub use mruby_ffi::mrb_get_args;
+/// Not meant to be called directly.
+#[doc(hidden)]
+pub use mruby_ffi::mrb_ext_check_arity;
+/// Not meant to be called directly.
+#[doc(hidden)]
+pub use convert::{arg_from_value, arg_type_error, kwargs_from_value};
 
+pub use convert::FromValue;
+pub use convert::ToValue;
+pub use mruby::CompiledScript;
+pub use mruby::GcStats;
 pub use mruby::Mruby;
 pub use mruby::MrubyError;
 pub use mruby::MrubyFile;
 pub use mruby::MrubyImpl;
+pub use mruby::MrubyOptions;
+pub use mruby::MrubyOwned;
 pub use mruby::MrubyType;
+pub use mruby::ObjMut;
+pub use mruby::ParseStatus;
 pub use mruby::Value;
+pub use mruby::ValueIter;
+pub use mruby::ValueKey;
+pub use mruby::WeakValue;
 pub use read_line::ReadLine;
 pub use repl::Repl;
 pub use spec::Spec;
@@ -22,8 +22,6 @@ macro_rules! init {
     ( $name:ident, bool )    => (let $name = uninitialized::<bool>(););
     ( $name:ident, i32 )     => (let $name = uninitialized::<i32>(););
     ( $name:ident, f64 )     => (let $name = uninitialized::<f64>(););
-    ( $name:ident, str )     => (let $name = uninitialized::<*const c_char>(););
-    ( $name:ident, Vec )     => (let $name = uninitialized::<MrValue>(););
     ( $name:ident, $_t:ty )  => (let $name = uninitialized::<MrValue>(););
     ( $name:ident : $t:tt )  => (init!($name, $t));
     ( $name:ident : $t:tt, $($names:ident : $ts:tt),+ ) => {
@@ -40,8 +38,6 @@ macro_rules! sig {
     ( bool )    => ("b");
     ( i32 )     => ("i");
     ( f64 )     => ("f");
-    ( str )     => ("z");
-    ( Vec )     => ("A");
     ( $_t:ty )  => ("o");
     ( $t:tt, $( $ts:tt ),+ ) => (concat!(sig!($t), sig!($( $ts ),*)));
 }
@@ -54,14 +50,14 @@ macro_rules! args {
     ( $name:ident, bool )    => (&$name as *const bool);
     ( $name:ident, i32 )     => (&$name as *const i32);
     ( $name:ident, f64 )     => (&$name as *const f64);
-    ( $name:ident, str )     => (&$name as *const *const c_char);
-    ( $name:ident, Vec )     => (&$name as *const MrValue);
     ( $name:ident, $_t:ty )  => (&$name as *const MrValue);
     ( $name:ident : $t:tt )  => (args!($name, $t));
     ( $mrb:expr, $sig:expr, $name:ident : $t:tt) => {
+        mrb_ext_check_arity($mrb, $sig);
         mrb_get_args($mrb, $sig, args!($name, $t));
     };
     ( $mrb:expr, $sig:expr, $name:ident : $t:tt, $($names:ident : $ts:tt),+ ) => {
+        mrb_ext_check_arity($mrb, $sig);
         mrb_get_args($mrb, $sig, args!($name, $t), $( args!($names : $ts) ),*);
     };
 }
@@ -77,6 +73,7 @@ macro_rules! args_rest {
             let args = uninitialized::<*mut MrValue>();
             let count = uninitialized::<i32>();
 
+            mrb_ext_check_arity(mrb, $sig);
             mrb_get_args(mrb, $sig, args!($name, $t), &args as *const *mut MrValue,
                          &count as *const i32);
 
@@ -91,6 +88,7 @@ macro_rules! args_rest {
             let args = uninitialized::<*mut MrValue>();
             let count = uninitialized::<i32>();
 
+            mrb_ext_check_arity(mrb, $sig);
             mrb_get_args(mrb, $sig, args!($name, $t), $( args!($names : $ts) ),* ,
                          &args as *const *mut MrValue, &count as *const i32);
 
@@ -104,26 +102,185 @@ macro_rules! args_rest {
 #[doc(hidden)]
 #[macro_export]
 macro_rules! conv {
-    ( $mruby:expr )                       => ();
-    ( $mruby:expr, $name:ident, bool )    => ();
-    ( $mruby:expr, $name:ident, i32 )     => ();
-    ( $mruby:expr, $name:ident, f64 )     => ();
-    ( $mruby:expr, $name:ident, str )     => {
-        let $name = CStr::from_ptr($name).to_str().unwrap();
+    // Arguments are numbered from 1 for TypeError messages.
+    ( @at $mruby:expr, $pos:expr, $name:ident : $t:tt ) => (conv!(@one $mruby, $pos, $name, $t));
+    ( @at $mruby:expr, $pos:expr, $name:ident : $t:tt, $( $names:ident : $ts:tt ),+ ) => {
+        conv!(@one $mruby, $pos, $name, $t);
+        conv!(@at $mruby, $pos + 1, $( $names : $ts ),*);
     };
-    ( $mruby:expr, $name:ident, Vec )     => {
-        let $name = Value::new($mruby.clone(), $name).to_vec().unwrap();
+
+    ( @one $mruby:expr, $pos:expr, $name:ident, bool )    => ();
+    ( @one $mruby:expr, $pos:expr, $name:ident, i32 )     => ();
+    ( @one $mruby:expr, $pos:expr, $name:ident, f64 )     => ();
+    ( @one $mruby:expr, $pos:expr, $name:ident, str )     => {
+        conv!(@from $mruby, $pos, $name, String);
+
+        let $name = &$name[..];
     };
-    ( $mruby:expr, $name:ident, Value )   => {
+    ( @one $mruby:expr, $pos:expr, $name:ident, Vec )     => {
+        conv!(@from $mruby, $pos, $name, Vec<Value>);
+    };
+    ( @one $mruby:expr, $pos:expr, $name:ident, Value )   => {
         let $name = Value::new($mruby.clone(), $name);
     };
-    ( $mruby:expr, $name:ident, $t:ty )   => {
-        let $name = Value::new($mruby.clone(), $name).to_obj::<$t>().unwrap();
+    ( @one $mruby:expr, $pos:expr, $name:ident, i64 )     => {
+        conv!(@from $mruby, $pos, $name, i64);
+    };
+    ( @one $mruby:expr, $pos:expr, $name:ident, String )  => {
+        conv!(@from $mruby, $pos, $name, String);
     };
-    ( $mruby:expr, $name:ident : $t:tt )  => (conv!($mruby, $name, $t));
-    ( $mruby:expr, $name:ident : $t:tt, $($names:ident : $ts:tt),+ ) => {
-        conv!($mruby, $name, $t);
-        conv!($mruby, $( $names : $ts ),*);
+    ( @one $mruby:expr, $pos:expr, $name:ident, $t:ty )   => {
+        let $name = {
+            let value = Value::new($mruby.clone(), $name);
+
+            match value.to_obj::<$t>() {
+                Ok(obj) => obj,
+                Err(_)  => {
+                    let expected = $mruby.class_name::<$t>()
+                                         .unwrap_or(stringify!($t).to_owned());
+                    let message = arg_type_error(&value, &expected,
+                                                 &format!("argument {}", $pos));
+
+                    return $mruby.raise("TypeError", &message);
+                }
+            }
+        };
+    };
+
+    ( @from $mruby:expr, $pos:expr, $name:ident, $t:ty ) => {
+        let $name = match arg_from_value::<$t>(&Value::new($mruby.clone(), $name),
+                                                &format!("argument {}", $pos)) {
+            Ok(value)    => value,
+            Err(message) => return $mruby.raise("TypeError", &message)
+        };
+    };
+
+    ( $mruby:expr )                                  => ();
+    ( $mruby:expr, $( $names:ident : $ts:tt ),+ )    => (conv!(@at $mruby, 1, $( $names : $ts ),*));
+}
+
+/// Not meant to be called directly.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! kwargs {
+    ( @one $mruby:expr, $kwargs:ident, $key:ident : $kt:ty ) => {
+        let $key = match $kwargs.remove(stringify!($key)) {
+            Some(value) => {
+                match arg_from_value::<$kt>(&value, concat!("keyword ", stringify!($key))) {
+                    Ok(value)    => value,
+                    Err(message) => return $mruby.raise("TypeError", &message)
+                }
+            },
+            None        => {
+                match <$kt as FromValue>::from_value(&$mruby.nil()) {
+                    Ok(value) => value,
+                    Err(_)    => {
+                        return $mruby.raise("ArgumentError",
+                                            concat!("missing keyword: ", stringify!($key)));
+                    }
+                }
+            }
+        };
+    };
+    ( @one $mruby:expr, $kwargs:ident, $key:ident : $kt:ty, $( $keys:ident : $kts:ty ),+ ) => {
+        kwargs!(@one $mruby, $kwargs, $key : $kt);
+        kwargs!(@one $mruby, $kwargs, $( $keys : $kts ),*);
+    };
+
+    // Undeclared keywords are rejected before any keyword is converted.
+    ( $mruby:expr, $opts:expr, $( $key:ident : $kt:ty ),+ ) => {
+        let mut kwargs = match kwargs_from_value(&Value::new($mruby.clone(), $opts),
+                                                 &[$( stringify!($key) ),*]) {
+            Ok(kwargs)   => kwargs,
+            Err(message) => return $mruby.raise("ArgumentError", &message)
+        };
+
+        kwargs!(@one $mruby, kwargs, $( $key : $kt ),*);
+    };
+}
+
+/// Not meant to be called directly.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! mrfn_ext {
+    // Required arguments are collected until the first Option or the block.
+    ( @req $head:tt ( $( $name:ident : $t:tt , )* )
+      $oname:ident : Option < $ot:tt > $( $rest:tt )* ) => {
+        mrfn_ext!(@opt $head ( $( $name : $t , )* ) () $oname : Option < $ot > $( $rest )*)
+    };
+    ( @req $head:tt ( $( $name:ident : $t:tt , )* )
+      $new:ident : $nt:tt , $( $rest:tt )* ) => {
+        mrfn_ext!(@req $head ( $( $name : $t , )* $new : $nt , ) $( $rest )*)
+    };
+    ( @req $head:tt $req:tt & $bname:ident | $block:expr ) => {
+        mrfn_ext!(@closure $head $req () ( $bname ) $block)
+    };
+
+    // Only Options or the block can follow the first Option.
+    ( @opt $head:tt $req:tt ( $( $oname:ident : $ot:tt , )* )
+      $new:ident : Option < $nt:tt > , $( $rest:tt )* ) => {
+        mrfn_ext!(@opt $head $req ( $( $oname : $ot , )* $new : $nt , ) $( $rest )*)
+    };
+    ( @opt $head:tt $req:tt ( $( $oname:ident : $ot:tt , )* )
+      $new:ident : Option < $nt:tt > | $block:expr ) => {
+        mrfn_ext!(@closure $head $req ( $( $oname : $ot , )* $new : $nt , ) () $block)
+    };
+    ( @opt $head:tt $req:tt $opt:tt & $bname:ident | $block:expr ) => {
+        mrfn_ext!(@closure $head $req $opt ( $bname ) $block)
+    };
+
+    ( @sig $_oname:ident )   => ("o");
+    ( @block $_bname:ident ) => ("&");
+    ( @one $_name:ident )    => (1);
+
+    // Optional arguments are numbered after the required ones.
+    ( @opts $mruby:ident, $pos:expr, ) => ();
+    ( @opts $mruby:ident, $pos:expr, $oname:ident : $ot:tt , $( $rest:tt )* ) => {
+        let $oname = match arg_from_value::<Option<$ot>>(&Value::new($mruby.clone(), $oname),
+                                                         &format!("argument {}", $pos)) {
+            Ok(value)    => value,
+            Err(message) => return $mruby.raise("TypeError", &message)
+        };
+
+        mrfn_ext!(@opts $mruby, $pos + 1, $( $rest )*);
+    };
+
+    ( @closure ( $mruby:ident, $slf:ident, $st:tt ) ( $( $name:ident : $t:tt , )* )
+      ( $( $oname:ident : $ot:tt , )* ) ( $( $bname:ident )* ) $block:expr ) => {
+        move |$mruby, $slf| {
+            #[allow(unused_imports)]
+            use std::ffi::CStr;
+            use std::ffi::CString;
+            #[allow(unused_imports)]
+            use std::mem::uninitialized;
+            #[allow(unused_imports)]
+            use std::os::raw::c_char;
+
+            unsafe {
+                slf!($slf, $st);
+
+                init!($( $name : $t ),*);
+                $( let $oname = MrValue::nil(); )*
+                $( let $bname = MrValue::nil(); )*
+
+                let mrb = $mruby.borrow().mrb;
+                let sig = CString::new(concat!(sig!($( $t ),*), "|"
+                                               $( , mrfn_ext!(@sig $oname) )*
+                                               $( , mrfn_ext!(@block $bname) )*)).unwrap();
+
+                mrb_ext_check_arity(mrb, sig.as_ptr());
+                mrb_get_args(mrb, sig.as_ptr() $( , args!($name : $t) )*
+                             $( , &$oname as *const MrValue )*
+                             $( , &$bname as *const MrValue )*);
+                conv!($mruby $( , $name : $t )*);
+
+                mrfn_ext!(@opts $mruby, 1 $( + mrfn_ext!(@one $name) )*,
+                          $( $oname : $ot , )*);
+                $( let $bname = Value::new($mruby.clone(), $bname); )*
+
+                $block
+            }
+        }
     };
 }
 
@@ -141,13 +298,27 @@ macro_rules! slf {
 ///
 /// * `bool`
 /// * `i32`
+/// * `i64`
 /// * `f64`
+/// * `String`
 /// * `str` (`&str`; macro limtation)
 /// * `Vec` (`Vec<Value>`; macro limtation)
 /// * `T` (defined with `def_class`)
 /// * `Value`
 ///
-/// Any `panic!` call within the closure will get rescued in a `RustPanic` mruby `Exception`.
+/// `i64` and `String` arguments are cast with `FromValue`.
+///
+/// Arguments that cannot be cast to their type raise a `TypeError` naming the argument, like
+/// `wrong argument type String for argument 2 (expected Container)`.
+///
+/// Any `panic!` call within the closure will get rescued in a `RustPanic` mruby `Exception`. If
+/// the script does not rescue it, it is returned to Rust as a `MrubyError::Panic`.
+///
+/// The closure captures its environment by `move`, like `proc_from`'s, so it can share state
+/// with Rust through an `Rc`.
+///
+/// Calling the method with a wrong number of arguments raises an `ArgumentError` like
+/// `wrong number of arguments (given 1, expected 2)`.
 ///
 /// # Examples
 ///
@@ -205,6 +376,33 @@ macro_rules! slf {
 /// ```
 /// <br/>
 ///
+/// `FromValue` and `ToValue` make it easy to write generic helpers.
+///
+/// ```
+/// # #[macro_use] extern crate mrusty;
+/// use mrusty::*;
+///
+/// # fn main() {
+/// let mruby = Mruby::new();
+///
+/// struct Cont;
+///
+/// fn repeat<T: ToValue + Clone>(mruby: &MrubyType, value: T, times: i64) -> Value {
+///     (0..times).map(|_| value.clone()).collect::<Vec<_>>().to_value(mruby)
+/// }
+///
+/// mruby.def_class::<Cont>("Container");
+/// mruby.def_class_method::<Cont, _>("repeat", mrfn!(|mruby, _slf: Value, s: String, n: i64| {
+///     repeat(&mruby, s, n)
+/// }));
+///
+/// let result = mruby.run("Container.repeat 'a', 2").unwrap();
+///
+/// assert_eq!(Vec::<String>::from_value(&result).unwrap(), vec!["a", "a"]);
+/// # }
+/// ```
+/// <br/>
+///
 /// `mrfn!` does automatic casting on all mruby classes defined with `def_class`.
 ///
 /// ```
@@ -259,17 +457,114 @@ macro_rules! slf {
 /// assert_eq!(result.to_obj::<Cont>().unwrap().value, 3);
 /// # }
 /// ```
+/// <br/>
+///
+/// Trailing optional arguments are typed as `Option`s of any type implementing `FromValue`.
+/// Omitted arguments become `None`, while passing too few or too many arguments raises an
+/// `ArgumentError`.
+///
+/// ```
+/// # #[macro_use] extern crate mrusty;
+/// use mrusty::*;
+///
+/// # fn main() {
+/// let mruby = Mruby::new();
+///
+/// struct Cont;
+///
+/// mruby.def_class::<Cont>("Container");
+/// mruby.def_method::<Cont, _>("slice", mrfn!(|mruby, _slf: Value, start: i32,
+///                                           len: Option<i32>| {
+///     mruby.fixnum(start + len.unwrap_or(1))
+/// }));
+///
+/// assert_eq!(mruby.run("Container.new.slice 1, 2").unwrap().to_i32().unwrap(), 3);
+/// assert_eq!(mruby.run("Container.new.slice 1").unwrap().to_i32().unwrap(), 2);
+/// assert!(mruby.run("Container.new.slice").is_err());
+/// assert!(mruby.run("Container.new.slice 1, 2, 3").is_err());
+/// # }
+/// ```
+/// <br/>
+///
+/// The block passed to the method can be captured as a `Value` with a last `&` argument. It is
+/// `nil` when no block is given.
+///
+/// ```
+/// # #[macro_use] extern crate mrusty;
+/// use mrusty::*;
+///
+/// # fn main() {
+/// let mruby = Mruby::new();
+///
+/// struct Cont;
+///
+/// mruby.def_class::<Cont>("Container");
+/// mruby.def_method::<Cont, _>("twice", mrfn!(|mruby, _slf: Value, a: i32, &block| {
+///     if block.is_proc() {
+///         let once = block.call("call", vec![mruby.fixnum(a)]).unwrap();
+///
+///         block.call("call", vec![once]).unwrap()
+///     } else {
+///         mruby.fixnum(a)
+///     }
+/// }));
+///
+/// let result = mruby.run("Container.new.twice(1) { |x| x * 3 }").unwrap();
+/// assert_eq!(result.to_i32().unwrap(), 9);
+///
+/// let result = mruby.run("Container.new.twice(1)").unwrap();
+/// assert_eq!(result.to_i32().unwrap(), 1);
+/// # }
+/// ```
+/// <br/>
+///
+/// Keyword arguments are typed with any type implementing `FromValue` and separated by a `;`.
+/// Missing keywords raise an `ArgumentError`, unless they are `Option`s, which become `None`.
+///
+/// ```
+/// # #[macro_use] extern crate mrusty;
+/// use mrusty::*;
+///
+/// # fn main() {
+/// let mruby = Mruby::new();
+///
+/// struct Cont;
+///
+/// mruby.def_class::<Cont>("Container");
+/// mruby.def_method::<Cont, _>("paint", mrfn!(|mruby, _slf: Value, times: i32;
+///                                           width: i32, color: Option<String>| {
+///     let color = color.unwrap_or("black".to_owned());
+///
+///     mruby.string(&format!("{}x{} {}", times, width, color))
+/// }));
+///
+/// let result = mruby.run("Container.new.paint 2, width: 3, color: 'red'").unwrap();
+/// assert_eq!(result.to_str().unwrap(), "2x3 red");
+///
+/// let result = mruby.run("Container.new.paint 2, width: 3").unwrap();
+/// assert_eq!(result.to_str().unwrap(), "2x3 black");
+///
+/// let result = mruby.run("Container.new.paint 2");
+/// assert!(result.is_err());
+/// # }
+/// ```
 #[macro_export]
 macro_rules! mrfn {
     ( |$mruby:ident, $slf:ident : $st:tt| $block:expr ) => {
-        |$mruby, $slf| {
+        move |$mruby, $slf| {
+            unsafe {
+                let mrb = $mruby.borrow().mrb;
+
+                mrb_ext_check_arity(mrb, b"\0".as_ptr() as *const _);
+            }
+
             slf!($slf, $st);
 
             $block
         }
     };
     ( |$mruby:ident, $slf:ident : $st:tt; $args:ident| $block:expr ) => {
-        |$mruby, $slf| {
+        move |$mruby, $slf| {
             use std::ffi::CString;
             use std::mem::uninitialized;
             use std::slice;
@@ -295,7 +590,7 @@ macro_rules! mrfn {
         }
     };
     ( |$mruby:ident, $slf:ident : $st:tt, $( $name:ident : $t:tt ),*| $block:expr ) => {
-        |$mruby, $slf| {
+        move |$mruby, $slf| {
             #[allow(unused_imports)]
             use std::ffi::CStr;
             use std::ffi::CString;
@@ -310,9 +605,9 @@ macro_rules! mrfn {
                 init!($( $name : $t ),*);
 
                 let mrb = $mruby.borrow().mrb;
-                let sig = CString::new(sig!($( $t ),*)).unwrap().as_ptr();
+                let sig = CString::new(sig!($( $t ),*)).unwrap();
 
-                args!(mrb, sig, $( $name : $t ),*);
+                args!(mrb, sig.as_ptr(), $( $name : $t ),*);
                 conv!($mruby, $( $name : $t ),*);
 
                 $block
@@ -320,7 +615,7 @@ macro_rules! mrfn {
         }
     };
     ( |$mruby:ident, $slf:ident : $st:tt, $( $name:ident : $t:tt ),* ; $args:ident| $block:expr ) => {
-        |$mruby, $slf| {
+        move |$mruby, $slf| {
             #[allow(unused_imports)]
             use std::ffi::CStr;
             use std::ffi::CString;
@@ -335,15 +630,70 @@ macro_rules! mrfn {
 
                 init!($( $name : $t ),*);
 
-                let sig = CString::new(concat!(sig!($( $t ),*), "*")).unwrap().as_ptr();
+                let sig = CString::new(concat!(sig!($( $t ),*), "*")).unwrap();
+
+                let $args = args_rest!($mruby, sig.as_ptr(), $( $name : $t ),*);
+                conv!($mruby, $( $name : $t ),*);
+
+                $block
+            }
+        }
+    };
+    ( |$mruby:ident, $slf:ident : $st:tt; $( $key:ident : $kt:ty ),+ | $block:expr ) => {
+        move |$mruby, $slf| {
+            use std::ffi::CString;
+
+            unsafe {
+                slf!($slf, $st);
+
+                let mrb = $mruby.borrow().mrb;
+                let opts = MrValue::nil();
+
+                let sig = CString::new("|H").unwrap();
+
+                mrb_ext_check_arity(mrb, sig.as_ptr());
+                mrb_get_args(mrb, sig.as_ptr(), &opts as *const MrValue);
+
+                kwargs!($mruby, opts, $( $key : $kt ),*);
+
+                $block
+            }
+        }
+    };
+    ( |$mruby:ident, $slf:ident : $st:tt, $( $name:ident : $t:tt ),* ;
+      $( $key:ident : $kt:ty ),+ | $block:expr ) => {
+        move |$mruby, $slf| {
+            #[allow(unused_imports)]
+            use std::ffi::CStr;
+            use std::ffi::CString;
+            #[allow(unused_imports)]
+            use std::mem::uninitialized;
+            #[allow(unused_imports)]
+            use std::os::raw::c_char;
+
+            unsafe {
+                slf!($slf, $st);
+
+                init!($( $name : $t ),*);
+
+                let mrb = $mruby.borrow().mrb;
+                let sig = CString::new(concat!(sig!($( $t ),*), "|H")).unwrap();
+                let opts = MrValue::nil();
 
-                let $args = args_rest!($mruby, sig, $( $name : $t ),*);
+                mrb_ext_check_arity(mrb, sig.as_ptr());
+                mrb_get_args(mrb, sig.as_ptr(), $( args!($name : $t) ),* ,
+                             &opts as *const MrValue);
                 conv!($mruby, $( $name : $t ),*);
 
+                kwargs!($mruby, opts, $( $key : $kt ),*);
+
                 $block
             }
         }
     };
+    ( |$mruby:ident, $slf:ident : $st:tt, $( $rest:tt )* ) => {
+        mrfn_ext!(@req ( $mruby, $slf, $st ) () $( $rest )*)
+    };
 }
 
 /// Not meant to be called directly.
@@ -353,6 +703,96 @@ macro_rules! defines {
     // end recursion
     ( $mruby:expr, $name:ty, ) => ();
 
+    // constants
+    ( $mruby:expr, $name:ty, const!($const:expr, | $mrb:ident | $value:expr ); $( $rest:tt )* ) => {
+        {
+            let $mrb = $mruby.clone();
+            let class = $mrb.class_name::<$name>().unwrap();
+
+            $mruby.def_const(&class, $const, $value).unwrap();
+        }
+
+        defines!($mruby, $name, $( $rest )*);
+    };
+
+    // numeric coercion
+    ( $mruby:expr, $name:ty, coerce!(); $( $rest:tt )* ) => {
+        $mruby.def_coerce::<$name>();
+
+        defines!($mruby, $name, $( $rest )*);
+    };
+
+    // module includes
+    ( $mruby:expr, $name:ty, include!($module:expr); $( $rest:tt )* ) => {
+        $mruby.include_module::<$name>($module).unwrap();
+
+        defines!($mruby, $name, $( $rest )*);
+    };
+
+    // getters
+    ( $mruby:expr, $name:ty, getters!( $( $field:ident : $t:ty ),* ); $( $rest:tt )* ) => {
+        $(
+            $mruby.def_method::<$name, _>(stringify!($field), mrfn!(|mruby, slf: $name| {
+                let value: $t = slf.$field.clone().into();
+
+                value.to_value(&mruby)
+            }));
+        )*
+
+        defines!($mruby, $name, $( $rest )*);
+    };
+
+    // accessors
+    ( $mruby:expr, $name:ty, accessors!( $( $field:ident : $t:ty ),* ); $( $rest:tt )* ) => {
+        $(
+            $mruby.def_method::<$name, _>(stringify!($field), mrfn!(|mruby, slf: $name| {
+                let value: $t = slf.borrow().$field.clone().into();
+
+                value.to_value(&mruby)
+            }));
+
+            $mruby.def_method_result::<$name, _>(concat!(stringify!($field), "="),
+                                                 mrfn!(|_mruby, slf: Value, value: Value| {
+                if slf.is_frozen() {
+                    return Err(MrubyError::Frozen(format!("can't modify frozen {}",
+                                                          slf.type_name())));
+                }
+
+                let field = try!(<$t as FromValue>::from_value(&value));
+
+                slf.to_obj::<$name>().unwrap().borrow_mut().$field = field.into();
+
+                Ok(value)
+            }));
+        )*
+
+        defines!($mruby, $name, $( $rest )*);
+    };
+
+    // binary operators
+    ( $mruby:expr, $name:ty, defop!($op:expr, | $slf:ident : $st:tt, $other:ident : $ot:ty | $block:expr ); $( $rest:tt )* ) => {
+        defines!($mruby, $name, defop!($op, |_mruby, $slf: $st, $other: $ot| $block); $( $rest )*);
+    };
+    ( $mruby:expr, $name:ty, defop!($op:expr, | $mrb:ident, $slf:ident : $st:tt, $other:ident : $ot:ty | $block:expr ); $( $rest:tt )* ) => {
+        $mruby.def_method::<$name, _>($op, mrfn!(|$mrb, $slf: Value, $other: Value| {
+            let $other = match $other.to_obj::<$ot>() {
+                Ok(other) => other,
+                Err(_)    => {
+                    let message = format!("{} can't be coerced into {}", $other.type_name(),
+                                          $slf.type_name());
+
+                    return $mrb.raise("TypeError", &message);
+                }
+            };
+
+            slf!($slf, $st);
+
+            $block
+        }));
+
+        defines!($mruby, $name, $( $rest )*);
+    };
+
     // initialize
     ( $mruby:expr, $name:ty, def!("initialize", || $block:expr ); $( $rest:tt )* ) => {
         $mruby.def_method::<$name, _>("initialize", mrfn!(|_mruby, slf: Value| {
@@ -540,8 +980,11 @@ macro_rules! defines {
 /// implementation.
 ///
 /// The macro takes a Rust type, an optional mruby `Class` name, and a block as arguments. Inside
-/// of the block you can define mruby methods with the `def!` and `def_self!` helpers which are
-/// not visible outside of this macro.
+/// of the block you can define mruby methods with the `def!` and `def_self!` helpers, field
+/// readers with `getters!` and `accessors!`, binary operators with `defop!`, constants with
+/// `const!` and include modules with `include!`. `coerce!()` opts into numeric interop through
+/// `def_coerce`. These helpers are not visible outside of this macro and are applied in the order
+/// they are written.
 ///
 /// # Examples
 ///
@@ -610,8 +1053,230 @@ macro_rules! defines {
 /// assert_eq!(result.to_str().unwrap(), "hi");
 /// # }
 /// ```
+/// <br/>
+///
+/// Use `const!` to define a constant from the `Value` returned by the closure and `include!` to
+/// include a previously defined mruby `Module`.
+///
+/// ```
+/// # #[macro_use] extern crate mrusty;
+/// use mrusty::*;
+///
+/// # fn main() {
+/// let mruby = Mruby::new();
+///
+/// struct Cont {
+///     value: i32
+/// };
+///
+/// mrclass!(Cont, "Container", {
+///     const!("VERSION", |mruby| mruby.string("1.0"));
+///
+///     include!("Comparable");
+///
+///     def!("initialize", |v: i32| {
+///         Cont { value: v }
+///     });
+///
+///     def!("<=>", |mruby, slf: Cont, other: Cont| {
+///         mruby.fixnum(slf.value - other.value)
+///     });
+/// });
+///
+/// Cont::require(mruby.clone());
+///
+/// let result = mruby.run("Container.new(3) > Container.new(2)").unwrap();
+///
+/// assert!(result.to_bool().unwrap());
+/// assert_eq!(mruby.run("Container::VERSION").unwrap().to_str().unwrap(), "1.0");
+/// # }
+/// ```
+/// <br/>
+///
+/// Use `getters!` to define a reader method for every listed field. Each field is converted
+/// `Into` the listed type, which must implement `ToValue`.
+///
+/// ```
+/// # #[macro_use] extern crate mrusty;
+/// use mrusty::*;
+///
+/// # fn main() {
+/// let mruby = Mruby::new();
+///
+/// struct Point {
+///     x: f32,
+///     y: f32,
+///     name: String
+/// };
+///
+/// mrclass!(Point, {
+///     def!("initialize", |x: f64, y: f64, name: String| {
+///         Point { x: x as f32, y: y as f32, name: name }
+///     });
+///
+///     getters!(x: f64, y: f64, name: String);
+/// });
+///
+/// Point::require(mruby.clone());
+///
+/// let point = mruby.run("Point.new(1.5, 2.0, 'a')").unwrap();
+///
+/// assert_eq!(point.call("x", vec![]).unwrap().to_f64().unwrap(), 1.5);
+/// assert_eq!(point.call("name", vec![]).unwrap().to_str().unwrap(), "a");
+/// # }
+/// ```
+/// <br/>
+///
+/// Use `defop!` to define a binary operator whose argument is cast to a type defined with
+/// `def_class`. Any other argument raises a `TypeError` like `Fixnum can't be coerced into Cont`.
+///
+/// *Note:* `mruby` argument is optional.
+///
+/// ```
+/// # #[macro_use] extern crate mrusty;
+/// use mrusty::*;
+///
+/// # fn main() {
+/// let mruby = Mruby::new();
+///
+/// struct Cont {
+///     value: i32
+/// };
+///
+/// mrclass!(Cont, "Container", {
+///     def!("initialize", |v: i32| {
+///         Cont { value: v }
+///     });
+///
+///     def!("value", |mruby, slf: Cont| {
+///         mruby.fixnum(slf.value)
+///     });
+///
+///     defop!("+", |mruby, slf: Cont, other: Cont| {
+///         mruby.obj(Cont { value: slf.value + other.value })
+///     });
+/// });
+///
+/// Cont::require(mruby.clone());
+///
+/// let result = mruby.run("(Container.new(1) + Container.new(2)).value").unwrap();
+///
+/// assert_eq!(result.to_i32().unwrap(), 3);
+///
+/// let result = mruby.run("Container.new(1) + 2");
+///
+/// match result {
+///     Err(MrubyError::Runtime(err)) => {
+///         assert_eq!(err, "TypeError: Fixnum can't be coerced into Container");
+///     },
+///     _ => assert!(false)
+/// }
+/// # }
+/// ```
+/// <br/>
+///
+/// Wrap the type in `RefCell` to store it like `def_class_cell` does. In this form, `def!`
+/// methods see a `RefCell<T>` and `accessors!` defines a reader and a writer for every listed
+/// field. Writers cast their argument with `FromValue` and raise `TypeError` on mismatch.
+///
+/// ```
+/// # #[macro_use] extern crate mrusty;
+/// use mrusty::*;
+/// use std::cell::RefCell;
+///
+/// # fn main() {
+/// let mruby = Mruby::new();
+///
+/// struct Config {
+///     timeout: i32
+/// };
+///
+/// mrclass!(RefCell<Config>, "Config", {
+///     def!("initialize", || {
+///         RefCell::new(Config { timeout: 10 })
+///     });
+///
+///     accessors!(timeout: i32);
+/// });
+///
+/// Config::require(mruby.clone());
+///
+/// let result = mruby.run("config = Config.new; config.timeout = 30; config.timeout").unwrap();
+///
+/// assert_eq!(result.to_i32().unwrap(), 30);
+/// assert!(mruby.run("Config.new.timeout = 'a'").is_err());
+/// # }
+/// ```
+/// <br/>
+///
+/// Follow the type with `:` and the name of an already defined mruby `Class` to inherit from it.
+/// In this form, the mruby `Class` is named after the Rust type.
+///
+/// ```
+/// # #[macro_use] extern crate mrusty;
+/// use mrusty::*;
+///
+/// # fn main() {
+/// let mruby = Mruby::new();
+///
+/// struct Money {
+///     cents: i32
+/// };
+///
+/// mrclass!(Money: "Numeric", {
+///     def!("initialize", |cents: i32| {
+///         Money { cents: cents }
+///     });
+///
+///     def!("<=>", |mruby, slf: Money, other: Money| {
+///         mruby.fixnum(slf.cents - other.cents)
+///     });
+/// });
+///
+/// Money::require(mruby.clone());
+///
+/// let result = mruby.run("Money.new(1) < Money.new(2)").unwrap();
+///
+/// assert!(result.to_bool().unwrap());
+/// assert!(mruby.run("Money.new(1).is_a? Numeric").unwrap().to_bool().unwrap());
+/// # }
+/// ```
 #[macro_export]
 macro_rules! mrclass {
+    ( RefCell < $name:ident >, { $( $rest:tt )* } ) => {
+        impl MrubyFile for $name {
+            fn require(mruby: MrubyType) {
+                mruby.def_class_cell::<$name>(stringify!($name));
+
+                defines!(mruby, ::std::cell::RefCell<$name>, $( $rest )*);
+            }
+        }
+    };
+    ( RefCell < $name:ident >, $mrname:expr, { $( $rest:tt )* } ) => {
+        impl MrubyFile for $name {
+            fn require(mruby: MrubyType) {
+                mruby.def_class_cell::<$name>($mrname);
+
+                defines!(mruby, ::std::cell::RefCell<$name>, $( $rest )*);
+            }
+        }
+    };
+    ( $name:ident : $superclass:expr ) => {
+        impl MrubyFile for $name {
+            fn require(mruby: MrubyType) {
+                mruby.def_class_with_super::<$name>(stringify!($name), $superclass).unwrap();
+            }
+        }
+    };
+    ( $name:ident : $superclass:expr, { $( $rest:tt )* } ) => {
+        impl MrubyFile for $name {
+            fn require(mruby: MrubyType) {
+                mruby.def_class_with_super::<$name>(stringify!($name), $superclass).unwrap();
+
+                defines!(mruby, $name, $( $rest )*);
+            }
+        }
+    };
     ( $name:tt ) => {
         impl MrubyFile for $name {
             fn require(mruby: MrubyType) {
@@ -645,3 +1310,77 @@ macro_rules! mrclass {
         }
     };
 }
+
+/// A `macro` that implements `FromValue` and `ToValue` for a Rust `enum` of unit variants, mapping
+/// each variant to an mruby `Symbol`. Casting any other `Value` returns a `Cast` error listing the
+/// valid `Symbol`s.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate mrusty;
+/// use mrusty::*;
+///
+/// # fn main() {
+/// #[derive(Debug, PartialEq)]
+/// enum Mode {
+///     Read,
+///     Write
+/// }
+///
+/// symbol_enum!(Mode {
+///     Read  => "read",
+///     Write => "write"
+/// });
+///
+/// let mruby = Mruby::new();
+///
+/// mruby.def_method_on("Object", "writable?", mrfn!(|mruby, _slf: Value, mode: Value| {
+///     match Mode::from_value(&mode) {
+///         Ok(mode) => mruby.bool(mode == Mode::Write),
+///         Err(err) => mruby.raise("ArgumentError", &err.to_string())
+///     }
+/// })).unwrap();
+///
+/// assert!(mruby.run("writable? :write").unwrap().to_bool().unwrap());
+/// assert!(mruby.run("writable? :append").is_err());
+///
+/// assert_eq!(Mode::from_value(&mruby.symbol("read")).unwrap(), Mode::Read);
+/// assert_eq!(Mode::Write.to_value(&mruby), mruby.symbol("write"));
+///
+/// match Mode::from_value(&mruby.symbol("append")) {
+///     Err(MrubyError::Cast(expected)) => assert_eq!(expected, "one of :read, :write"),
+///     _ => assert!(false)
+/// }
+/// # }
+/// ```
+#[macro_export]
+macro_rules! symbol_enum {
+    ( $name:ident { $( $variant:ident => $symbol:expr ),* $( , )* } ) => {
+        impl FromValue for $name {
+            fn from_value(value: &Value) -> Result<$name, MrubyError> {
+                if let Ok(name) = value.to_sym_name() {
+                    $(
+                        if name == $symbol {
+                            return Ok($name::$variant);
+                        }
+                    )*
+                }
+
+                let symbols: Vec<&str> = vec![$( $symbol ),*];
+
+                Err(MrubyError::Cast(format!("one of :{}", symbols.join(", :"))))
+            }
+        }
+
+        impl ToValue for $name {
+            fn to_value(self, mruby: &MrubyType) -> Value {
+                match self {
+                    $(
+                        $name::$variant => mruby.symbol($symbol),
+                    )*
+                }
+            }
+        }
+    };
+}
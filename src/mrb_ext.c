@@ -14,15 +14,25 @@
 // You should have received a copy of the GNU Lesser General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+#include <stdint.h>
+#include <stdio.h>
 #include <stdlib.h>
+#include <string.h>
 
 #include <mruby.h>
 #include <mruby/array.h>
 #include <mruby/class.h>
+#include <mruby/compile.h>
 #include <mruby/data.h>
+#include <mruby/dump.h>
 #include <mruby/error.h>
+#include <mruby/gc.h>
+#include <mruby/hash.h>
+#include <mruby/range.h>
+#include <mruby/string.h>
 #include <mruby/value.h>
 #include <mruby/proc.h>
+#include <mruby/variable.h>
 
 void* mrb_ext_get_ud(struct mrb_state* mrb) {
   return mrb->ud;
@@ -36,6 +46,10 @@ int mrb_ext_fixnum_to_cint(mrb_value value) {
   return mrb_fixnum(value);
 }
 
+int64_t mrb_ext_fixnum_to_cint64(mrb_value value) {
+  return mrb_fixnum(value);
+}
+
 double mrb_ext_float_to_cdouble(mrb_value value) {
   return mrb_float(value);
 }
@@ -60,6 +74,26 @@ mrb_value mrb_ext_cint_to_fixnum(int value) {
   return mrb_fixnum_value(value);
 }
 
+mrb_value mrb_ext_cint64_to_fixnum(int64_t value) {
+  return mrb_fixnum_value(value);
+}
+
+int mrb_ext_int_bit() {
+  return MRB_INT_BIT;
+}
+
+int64_t mrb_ext_int_min() {
+  return MRB_INT_MIN;
+}
+
+int64_t mrb_ext_int_max() {
+  return MRB_INT_MAX;
+}
+
+mrb_bool mrb_ext_is_nil(mrb_value value) {
+  return mrb_nil_p(value);
+}
+
 mrb_value mrb_ext_cdouble_to_float(struct mrb_state* mrb, double value) {
   return mrb_float_value(mrb, value);
 }
@@ -76,6 +110,10 @@ const char* mrb_ext_sym2name(struct mrb_state* mrb, mrb_value value) {
   return mrb_sym2name(mrb, mrb_symbol(value));
 }
 
+mrb_sym mrb_ext_symbol(mrb_value value) {
+  return mrb_symbol(value);
+}
+
 mrb_value mrb_ext_sym_new(struct mrb_state* mrb, const char* string,
                           size_t len) {
   mrb_value value;
@@ -108,23 +146,619 @@ int mrb_ext_ary_len(struct mrb_state* mrb, mrb_value array) {
   return mrb_ary_len(mrb, array);
 }
 
+const char* mrb_ext_str_ptr(mrb_value string) {
+  return RSTRING_PTR(string);
+}
+
+int mrb_ext_str_len(mrb_value string) {
+  return RSTRING_LEN(string);
+}
+
+int mrb_ext_hash_len(struct mrb_state* mrb, mrb_value hash) {
+  int ai = mrb_gc_arena_save(mrb);
+  int len = mrb_ary_len(mrb, mrb_hash_keys(mrb, hash));
+
+  mrb_gc_arena_restore(mrb, ai);
+
+  return len;
+}
+
+// Unlike mrb_range_new, endpoints are not compared, so nil endpoints are allowed.
+mrb_value mrb_ext_range_new(struct mrb_state* mrb, mrb_value beg, mrb_value end, mrb_bool excl) {
+  struct RRange* range = (struct RRange*) mrb_obj_alloc(mrb, MRB_TT_RANGE,
+                                                        mrb_class_get(mrb, "Range"));
+
+  range->edges = (mrb_range_edges*) mrb_malloc(mrb, sizeof(mrb_range_edges));
+  range->edges->beg = beg;
+  range->edges->end = end;
+  range->excl = excl;
+
+  return mrb_range_value(range);
+}
+
+void mrb_ext_range_edges(mrb_value range, mrb_value* beg, mrb_value* end, mrb_bool* excl) {
+  struct RRange* ptr = mrb_range_ptr(range);
+
+  *beg = ptr->edges->beg;
+  *end = ptr->edges->end;
+  *excl = ptr->excl;
+}
+
+// mruby only freezes Strings. Other objects are marked with a flag bit unused by mruby, which
+// is honored by Kernel#frozen? and by mrusty.
+#define MRB_EXT_FROZEN (1 << 18)
+
+void mrb_ext_freeze(mrb_value value) {
+  if (mrb_string_p(value)) {
+    RSTR_SET_FROZEN_FLAG(mrb_str_ptr(value));
+  } else if (!mrb_immediate_p(value)) {
+    mrb_basic_ptr(value)->flags |= MRB_EXT_FROZEN;
+  }
+}
+
+mrb_bool mrb_ext_is_frozen(mrb_value value) {
+  if (mrb_string_p(value)) {
+    return RSTR_FROZEN_P(mrb_str_ptr(value)) != 0;
+  } else if (mrb_immediate_p(value)) {
+    return TRUE;
+  } else {
+    return (mrb_basic_ptr(value)->flags & MRB_EXT_FROZEN) != 0;
+  }
+}
+
+static mrb_value mrb_ext_kernel_freeze(struct mrb_state* mrb, mrb_value self) {
+  (void) mrb;
+
+  mrb_ext_freeze(self);
+
+  return self;
+}
+
+static mrb_value mrb_ext_kernel_frozen(struct mrb_state* mrb, mrb_value self) {
+  (void) mrb;
+
+  return mrb_bool_value(mrb_ext_is_frozen(self));
+}
+
+void mrb_ext_define_freeze(struct mrb_state* mrb) {
+  mrb_define_method(mrb, mrb->kernel_module, "freeze", mrb_ext_kernel_freeze, MRB_ARGS_NONE());
+  mrb_define_method(mrb, mrb->kernel_module, "frozen?", mrb_ext_kernel_frozen, MRB_ARGS_NONE());
+}
+
+struct RClass* mrb_ext_class_path(struct mrb_state* mrb, const char* path) {
+  struct RClass* current = mrb->object_class;
+
+  while (*path) {
+    const char* end = strstr(path, "::");
+    size_t len = end ? (size_t) (end - path) : strlen(path);
+
+    mrb_sym sym = mrb_intern(mrb, path, len);
+
+    if (!mrb_const_defined(mrb, mrb_obj_value(current), sym)) {
+      return NULL;
+    }
+
+    mrb_value value = mrb_const_get(mrb, mrb_obj_value(current), sym);
+
+    switch (mrb_type(value)) {
+      case MRB_TT_CLASS:
+      case MRB_TT_MODULE:
+        current = mrb_class_ptr(value);
+        break;
+      default:
+        return NULL;
+    }
+
+    path = end ? end + 2 : path + len;
+  }
+
+  return current;
+}
+
+mrb_bool mrb_ext_is_exception_class(struct mrb_state* mrb, struct RClass* class) {
+  struct RClass* current;
+
+  if (class->tt != MRB_TT_CLASS) {
+    return FALSE;
+  }
+
+  for (current = class; current; current = current->super) {
+    if (current == mrb->eException_class) {
+      return TRUE;
+    }
+  }
+
+  return FALSE;
+}
+
+mrb_value mrb_ext_class_value(struct RClass* class) {
+  return mrb_obj_value(class);
+}
+
+const char* mrb_ext_class_name(struct mrb_state* mrb, mrb_value class) {
+  return mrb_class_name(mrb, mrb_class_ptr(class));
+}
+
 unsigned int mrb_ext_get_mid(struct mrb_state* mrb) {
   return mrb_get_mid(mrb);
 }
 
-mrb_value mrb_ext_get_exc(struct mrb_state* mrb) {
+struct RClass* mrb_ext_singleton_class(struct mrb_state* mrb, mrb_value value) {
+  return mrb_class_ptr(mrb_singleton_class(mrb, value));
+}
+
+struct RClass* mrb_ext_get_target_class(struct mrb_state* mrb) {
+  struct RClass* class = mrb->c->ci->target_class;
+
+  if (class->tt == MRB_TT_ICLASS) {
+    class = class->c;
+  }
+
+  return class;
+}
+
+// Unlike mrb_gc_unregister, only removes one registration of value from the GC root table.
+void mrb_ext_gc_release(struct mrb_state* mrb, mrb_value value) {
+  mrb_value table = mrb_gv_get(mrb, mrb_intern_lit(mrb, "_gc_root_"));
+  struct RArray* array;
+  mrb_int i;
+
+  if (mrb_type(table) != MRB_TT_ARRAY) {
+    return;
+  }
+
+  array = mrb_ary_ptr(table);
+
+  for (i = 0; i < array->len; i++) {
+    if (mrb_obj_eq(mrb, array->ptr[i], value)) {
+      mrb_ary_modify(mrb, array);
+      memmove(array->ptr + i, array->ptr + i + 1, (array->len - i - 1) * sizeof(mrb_value));
+      array->len--;
+
+      return;
+    }
+  }
+}
+
+static mrb_value mrb_ext_yield_body(struct mrb_state* mrb, mrb_value args) {
+  mrb_value block = mrb_ary_ref(mrb, args, 0);
+  struct RProc* proc = mrb_proc_ptr(block);
+  mrb_value self = mrb_top_self(mrb);
+
+  if (!MRB_PROC_CFUNC_P(proc) && proc->env) {
+    self = proc->env->stack[0];
+  }
+
+  return mrb_yield_with_class(mrb, block, RARRAY_LEN(args) - 1, RARRAY_PTR(args) + 1, self,
+                              proc->target_class);
+}
+
+// Proc#call cannot be sent to C function procs from C and mrb_yield_argv does not catch
+// exceptions, so the block is yielded to directly under mrb_protect.
+mrb_value mrb_ext_yield_argv(struct mrb_state* mrb, mrb_value block, mrb_int argc,
+                             const mrb_value* argv) {
+  mrb_value args = mrb_ary_new_capa(mrb, argc + 1);
+  mrb_bool raised;
+  mrb_value result;
+  int i;
+
+  mrb_ary_push(mrb, args, block);
+
+  for (i = 0; i < argc; i++) {
+    mrb_ary_push(mrb, args, argv[i]);
+  }
+
+  result = mrb_protect(mrb, mrb_ext_yield_body, args, &raised);
+
+  if (raised) {
+    mrb->exc = mrb_obj_ptr(result);
+
+    return mrb_nil_value();
+  }
+
+  return result;
+}
+
+mrb_value mrb_ext_get_caller_self(struct mrb_state* mrb) {
+  return mrb->c->ci->stackent[0];
+}
+
+mrb_value mrb_ext_get_exc_with_backtrace(struct mrb_state* mrb) {
   if (mrb->exc) {
-    mrb_value exc = mrb_funcall(mrb, mrb_obj_value(mrb->exc), "inspect", 0);
+    // The backtrace needs to be read before any other call overwrites the call stack.
     mrb_value backtrace = mrb_exc_backtrace(mrb, mrb_obj_value(mrb->exc));
+    mrb_value exc = mrb_funcall(mrb, mrb_obj_value(mrb->exc), "inspect", 0);
 
-    mrb_funcall(mrb, backtrace, "unshift", 1, exc);
+    mrb_ary_unshift(mrb, backtrace, exc);
 
     mrb->exc = NULL;
 
-    return mrb_funcall(mrb, backtrace, "join", 1, mrb_str_new_cstr(mrb, "\n"));
+    return backtrace;
+  } else {
+    return mrb_nil_value();
+  }
+}
+
+// Returns the message of the pending exception if it is a kind of the class named name, leaving it
+// pending.
+mrb_value mrb_ext_get_exc_of(struct mrb_state* mrb, const char* name) {
+  mrb_value exc;
+
+  if (!mrb->exc || !mrb_class_defined(mrb, name)) {
+    return mrb_nil_value();
+  }
+
+  exc = mrb_obj_value(mrb->exc);
+
+  if (!mrb_obj_is_kind_of(mrb, exc, mrb_class_get(mrb, name))) {
+    return mrb_nil_value();
+  }
+
+  return mrb_funcall(mrb, exc, "message", 0);
+}
+
+mrb_value mrb_ext_get_exc(struct mrb_state* mrb) {
+  mrb_value exc = mrb_ext_get_exc_with_backtrace(mrb);
+
+  if (mrb_nil_p(exc)) {
+    return exc;
+  } else {
+    return mrb_ary_ref(mrb, exc, 0);
+  }
+}
+
+void mrb_ext_gc_disable(struct mrb_state* mrb) {
+  mrb->gc.disabled = TRUE;
+}
+
+void mrb_ext_gc_enable(struct mrb_state* mrb) {
+  mrb->gc.disabled = FALSE;
+}
+
+#ifndef MRB_HEAP_PAGE_SIZE
+#define MRB_HEAP_PAGE_SIZE 1024
+#endif
+
+void mrb_ext_gc_stats(struct mrb_state* mrb, size_t* live, size_t* total, int* arena) {
+  mrb_heap_page* page = mrb->gc.heaps;
+  size_t pages = 0;
+
+  while (page) {
+    pages++;
+    page = page->next;
+  }
+
+  *live = mrb->gc.live;
+  *total = pages * MRB_HEAP_PAGE_SIZE;
+  *arena = mrb->gc.arena_idx;
+}
+
+void mrb_ext_set_code_fetch_hook(struct mrb_state* mrb,
+                                 void (*hook)(struct mrb_state*, struct mrb_irep*, mrb_code*,
+                                              mrb_value*)) {
+  mrb->code_fetch_hook = hook;
+}
+
+// Returns whether no Ruby code is running, i.e. mruby is entered from the host rather than from a
+// C function called by a running script.
+mrb_bool mrb_ext_vm_idle(struct mrb_state* mrb) {
+  return mrb->c == mrb->root_c && mrb->c->ci == mrb->c->cibase;
+}
+
+// Checks a parser created while capturing errors. Returns NULL with a SyntaxError set if parsing
+// failed. The SyntaxError keeps the parser's line, column and message in hidden instance
+// variables which are read back by mrb_ext_get_syntax_error.
+static struct mrb_parser_state* mrb_ext_check_parser(struct mrb_state* mrb,
+                                                     struct mrb_parser_state* p) {
+  if (!p) {
+    mrb->exc = mrb_obj_ptr(mrb_exc_new_str_lit(mrb, E_SCRIPT_ERROR, "cannot create parser"));
+
+    return NULL;
+  }
+
+  if (p->nerr > 0) {
+    mrb_value message = mrb_str_new_cstr(mrb, p->error_buffer[0].message);
+    mrb_value msg = mrb_format(mrb, "line %S: %S",
+                               mrb_fixnum_value(p->error_buffer[0].lineno), message);
+    mrb_value exc = mrb_exc_new_str(mrb, E_SYNTAX_ERROR, msg);
+
+    mrb_iv_set(mrb, exc, mrb_intern_lit(mrb, "line"),
+               mrb_fixnum_value(p->error_buffer[0].lineno));
+    mrb_iv_set(mrb, exc, mrb_intern_lit(mrb, "column"),
+               mrb_fixnum_value(p->error_buffer[0].column));
+    mrb_iv_set(mrb, exc, mrb_intern_lit(mrb, "message"), message);
+
+    mrb->exc = mrb_obj_ptr(exc);
+    mrb_parser_free(p);
+
+    return NULL;
+  }
+
+  return p;
+}
+
+static struct mrb_parser_state* mrb_ext_parse(struct mrb_state* mrb, struct mrbc_context* ctx,
+                                              const char* code, size_t len) {
+  mrb_bool capture_errors = ctx->capture_errors;
+  struct mrb_parser_state* p;
+
+  ctx->capture_errors = TRUE;
+  p = mrb_parse_nstring(mrb, code, len, ctx);
+  ctx->capture_errors = capture_errors;
+
+  return mrb_ext_check_parser(mrb, p);
+}
+
+static struct mrb_parser_state* mrb_ext_parse_file(struct mrb_state* mrb,
+                                                   struct mrbc_context* ctx, FILE* file) {
+  mrb_bool capture_errors = ctx->capture_errors;
+  struct mrb_parser_state* p;
+
+  ctx->capture_errors = TRUE;
+  p = mrb_parse_file(mrb, file, ctx);
+  ctx->capture_errors = capture_errors;
+
+  return mrb_ext_check_parser(mrb, p);
+}
+
+// Parses code without running it. Returns 0 if it parses, 1 if more input is needed to complete
+// it, like an open block or string, and 2 on syntax errors, setting message and line.
+int mrb_ext_parse_state(struct mrb_state* mrb, const char* code, size_t len, mrb_value* message,
+                        int* line) {
+  const char unexpected_end[] = "syntax error, unexpected $end";
+  struct mrbc_context* ctx = mrbc_context_new(mrb);
+  struct mrb_parser_state* p;
+  int state = 0;
+
+  ctx->capture_errors = TRUE;
+  p = mrb_parse_nstring(mrb, code, len, ctx);
+  mrbc_context_free(mrb, ctx);
+
+  if (!p) {
+    *message = mrb_str_new_lit(mrb, "cannot create parser");
+    *line = 0;
+
+    return 2;
+  }
+
+  if (p->parsing_heredoc || p->lex_strterm) {
+    state = 1;
+  } else if (p->nerr > 0) {
+    if (strncmp(p->error_buffer[0].message, unexpected_end, sizeof(unexpected_end) - 1) == 0) {
+      state = 1;
+    } else {
+      *message = mrb_str_new_cstr(mrb, p->error_buffer[0].message);
+      *line = p->error_buffer[0].lineno;
+
+      state = 2;
+    }
+  }
+
+  mrb_parser_free(p);
+
+  return state;
+}
+
+mrb_value mrb_ext_get_syntax_error(struct mrb_state* mrb, int* line, int* column) {
+  mrb_value exc;
+  mrb_sym message = mrb_intern_lit(mrb, "message");
+
+  if (!mrb->exc) {
+    return mrb_nil_value();
+  }
+
+  exc = mrb_obj_value(mrb->exc);
+
+  if (!mrb_obj_is_kind_of(mrb, exc, E_SYNTAX_ERROR) || !mrb_iv_defined(mrb, exc, message)) {
+    return mrb_nil_value();
+  }
+
+  *line = mrb_fixnum(mrb_iv_get(mrb, exc, mrb_intern_lit(mrb, "line")));
+  *column = mrb_fixnum(mrb_iv_get(mrb, exc, mrb_intern_lit(mrb, "column")));
+
+  mrb->exc = NULL;
+
+  return mrb_iv_get(mrb, exc, message);
+}
+
+void mrb_ext_clear_filename(struct mrbc_context* ctx) {
+  ctx->filename = NULL;
+}
+
+// Like mrb_load_nstring_cxt, but syntax errors are raised by mrb_ext_parse.
+// Generates and runs the code of a checked parser on the top-level, keeping local variables
+// between runs of the same context.
+static mrb_value mrb_ext_run_parser(struct mrb_state* mrb, struct mrbc_context* ctx,
+                                    struct mrb_parser_state* p) {
+  struct RClass* target = mrb->object_class;
+  struct RProc* proc;
+  unsigned int keep = 0;
+  mrb_value value;
+
+  if (!p) {
+    return mrb_nil_value();
+  }
+
+  proc = mrb_generate_code(mrb, p);
+  mrb_parser_free(p);
+
+  if (!proc) {
+    mrb->exc = mrb_obj_ptr(mrb_exc_new_str_lit(mrb, E_SCRIPT_ERROR, "codegen error"));
+
+    return mrb_nil_value();
+  }
+
+  if (ctx->target_class) {
+    target = ctx->target_class;
+  }
+
+  if (ctx->keep_lv) {
+    keep = ctx->slen + 1;
   } else {
+    ctx->keep_lv = TRUE;
+  }
+
+  proc->target_class = target;
+
+  if (mrb->c->ci) {
+    mrb->c->ci->target_class = target;
+  }
+
+  value = mrb_toplevel_run_keep(mrb, proc, keep);
+
+  return mrb->exc ? mrb_nil_value() : value;
+}
+
+mrb_value mrb_ext_load_nstring_cxt(struct mrb_state* mrb, const char* code, size_t len,
+                                   struct mrbc_context* ctx) {
+  return mrb_ext_run_parser(mrb, ctx, mrb_ext_parse(mrb, ctx, code, len));
+}
+
+mrb_value mrb_ext_load_file_cxt(struct mrb_state* mrb, const char* path,
+                                struct mrbc_context* ctx) {
+  FILE* file = fopen(path, "r");
+  struct mrb_parser_state* p;
+
+  if (!file) {
+    mrb->exc = mrb_obj_ptr(mrb_exc_new_str_lit(mrb, E_SCRIPT_ERROR, "cannot open file"));
+
     return mrb_nil_value();
   }
+
+  p = mrb_ext_parse_file(mrb, ctx, file);
+  fclose(file);
+
+  return mrb_ext_run_parser(mrb, ctx, p);
+}
+
+static struct RProc* mrb_ext_generate(struct mrb_state* mrb, struct mrbc_context* ctx,
+                                      const char* code, size_t len) {
+  struct mrb_parser_state* p = mrb_ext_parse(mrb, ctx, code, len);
+  struct RProc* proc;
+
+  if (!p) {
+    return NULL;
+  }
+
+  proc = mrb_generate_code(mrb, p);
+  mrb_parser_free(p);
+
+  if (!proc) {
+    mrb->exc = mrb_obj_ptr(mrb_exc_new_str_lit(mrb, E_SCRIPT_ERROR, "codegen error"));
+  }
+
+  return proc;
+}
+
+int mrb_ext_compile(struct mrb_state* mrb, struct mrbc_context* ctx, const char* code,
+                    size_t len, uint8_t** bin, size_t* bin_size) {
+  struct RProc* proc = mrb_ext_generate(mrb, ctx, code, len);
+
+  if (!proc) {
+    return MRB_DUMP_GENERAL_FAILURE;
+  }
+
+  return mrb_dump_irep(mrb, proc->body.irep, DUMP_DEBUG_INFO, bin, bin_size);
+}
+
+mrb_value mrb_ext_compile_proc(struct mrb_state* mrb, const char* filename, const char* code,
+                               size_t len) {
+  struct mrbc_context* ctx = mrbc_context_new(mrb);
+  struct RProc* proc;
+
+  if (filename) {
+    mrbc_filename(mrb, ctx, filename);
+  }
+
+  proc = mrb_ext_generate(mrb, ctx, code, len);
+  mrbc_context_free(mrb, ctx);
+
+  if (!proc) {
+    return mrb_nil_value();
+  }
+
+  proc->target_class = mrb->object_class;
+
+  return mrb_obj_value(proc);
+}
+
+mrb_value mrb_ext_run_proc(struct mrb_state* mrb, mrb_value proc) {
+  return mrb_toplevel_run_keep(mrb, mrb_proc_ptr(proc), 0);
+}
+
+static mrb_bool mrb_ext_symbol_hash_p(struct mrb_state* mrb, mrb_value value) {
+  mrb_value keys;
+  mrb_int i;
+
+  if (!mrb_hash_p(value)) {
+    return FALSE;
+  }
+
+  keys = mrb_hash_keys(mrb, value);
+
+  for (i = 0; i < RARRAY_LEN(keys); i++) {
+    if (!mrb_symbol_p(RARRAY_PTR(keys)[i])) {
+      return FALSE;
+    }
+  }
+
+  return TRUE;
+}
+
+void mrb_ext_check_arity(struct mrb_state* mrb, const char* format) {
+  int argc = mrb->c->ci->argc;
+  int req = 0;
+  int opt = 0;
+  mrb_bool optional = FALSE;
+  mrb_bool rest = FALSE;
+  size_t len = strlen(format);
+  mrb_bool keywords = len >= 2 && strcmp(format + len - 2, "|H") == 0;
+  mrb_value* argv = mrb->c->stack + 1;
+
+  if (argc < 0) {
+    argc = RARRAY_LEN(mrb->c->stack[1]);
+    argv = RARRAY_PTR(mrb->c->stack[1]);
+  }
+
+  for (; *format; format++) {
+    switch (*format) {
+    case '|':
+      optional = TRUE;
+      break;
+    case '*':
+      rest = TRUE;
+      break;
+    case '&': case '?': case '!':
+      break;
+    default:
+      if (optional) {
+        opt++;
+      } else {
+        req++;
+      }
+    }
+  }
+
+  // A trailing symbol-keyed Hash holds the keywords and cannot fill a required argument.
+  if (keywords && argc > 0 && mrb_ext_symbol_hash_p(mrb, argv[argc - 1])) {
+    argc--;
+    opt--;
+  }
+
+  if (argc < req || (!rest && argc > req + opt)) {
+    mrb_value expected;
+
+    if (rest) {
+      expected = mrb_format(mrb, "%S+", mrb_fixnum_value(req));
+    } else if (opt > 0) {
+      expected = mrb_format(mrb, "%S..%S", mrb_fixnum_value(req), mrb_fixnum_value(req + opt));
+    } else {
+      expected = mrb_fixnum_value(req);
+    }
+
+    mrb_raisef(mrb, E_ARGUMENT_ERROR, "wrong number of arguments (given %S, expected %S)",
+               mrb_fixnum_value(argc), expected);
+  }
 }
 
 mrb_noreturn void mrb_ext_raise(struct mrb_state* mrb, const char* eclass, const char* msg) {
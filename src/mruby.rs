@@ -15,18 +15,34 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::any::{Any, TypeId};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell, RefMut};
+use std::cmp;
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::ffi::{CStr, CString};
 use std::fs::File;
+use std::hash::{Hash, Hasher};
 use std::io::{self, Read};
 use std::mem;
+use std::ops::{Deref, DerefMut};
 use std::os::raw::{c_char, c_void};
 use std::panic::{self, AssertRecoverSafe};
-use std::path::Path;
-use std::rc::Rc;
-
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::rc::{Rc, Weak};
+use std::slice;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "serde")]
+use serde_json;
+
+#[cfg(feature = "serde")]
+use super::json;
+use super::convert::{FromValue, ToValue};
 use super::mruby_ffi::*;
 
 /// A `type` wrapper around a `Rc<RefCell<Mruby>>`. Created with `Mruby::new()`.
@@ -51,10 +67,72 @@ pub struct Mruby {
     ctx:           *const MrContext,
     filename:      Option<String>,
     classes:       HashMap<TypeId, (*const MrClass, MrDataType, String)>,
+    dyn_classes:   HashMap<String, (*const MrClass, MrDataType, String)>,
     methods:       HashMap<TypeId, HashMap<u32, Rc<Fn(MrubyType, Value) -> Value>>>,
     class_methods: HashMap<TypeId, HashMap<u32, Rc<Fn(MrubyType, Value) -> Value>>>,
+    modules:       HashMap<u32, HashMap<u32, Rc<Fn(MrubyType, Value) -> Value>>>,
+    named_methods: HashMap<u32, HashMap<u32, Rc<Fn(MrubyType, Value) -> Value>>>,
+    drops:         HashMap<TypeId, Rc<Any>>,
+    proc_ty:       MrDataType,
+    singleton_ty:  MrDataType,
+    panic_class:   String,
     files:         HashMap<String, Vec<fn(MrubyType)>>,
-    required:      HashSet<String>
+    required:      HashSet<String>,
+    load_paths:    Vec<PathBuf>,
+    file_stack:    Vec<PathBuf>,
+    interrupt:     Option<Arc<AtomicBool>>,
+    instructions:  Option<u64>,
+    executed:      Cell<u64>,
+    inspecting:    bool,
+    user_data:     Option<Box<Any>>,
+    captures:      Vec<String>
+}
+
+/// Options used to configure an `Mruby` created with `Mruby::new_with_opts`.
+///
+/// `MrubyOptions::default()` matches `Mruby::new`.
+///
+/// # Examples
+///
+/// ```
+/// # use mrusty::*;
+/// let mruby = Mruby::new_with_opts(MrubyOptions {
+///     allow_require: false,
+///     ..MrubyOptions::default()
+/// });
+///
+/// assert!(mruby.run("require 'json'").is_err());
+/// ```
+#[derive(Clone, Debug)]
+pub struct MrubyOptions {
+    /// defines `Kernel#require` and `Kernel#require_relative` (default `true`)
+    pub allow_require:     bool,
+    /// initial load paths used by `require`, like `add_load_path` (default empty)
+    pub load_paths:        Vec<PathBuf>,
+    /// enables the garbage collector, like `gc_enable`/`gc_disable` (default `true`)
+    pub gc_enabled:        bool,
+    /// number of VM instructions each `run` or call from Rust can execute before a
+    /// `ResourceError` is raised, like `set_instruction_limit` (default `None`, unlimited)
+    pub instruction_limit: Option<u64>,
+    /// name of the exception class raised for Rust panics, which must be a constant name that is
+    /// not yet defined (default `"RustPanic"`)
+    pub panic_class:       String,
+    /// superclass of the panic class, which must be `Exception` or one of its subclasses;
+    /// `"StandardError"` lets a bare `rescue` catch panics (default `"Exception"`)
+    pub panic_superclass:  String
+}
+
+impl Default for MrubyOptions {
+    fn default() -> MrubyOptions {
+        MrubyOptions {
+            allow_require:     true,
+            load_paths:        Vec::new(),
+            gc_enabled:        true,
+            instruction_limit: None,
+            panic_class:       "RustPanic".to_owned(),
+            panic_superclass:  "Exception".to_owned()
+        }
+    }
 }
 
 impl Mruby {
@@ -67,6 +145,26 @@ impl Mruby {
     /// let mruby = Mruby::new();
     /// ```
     pub fn new() -> MrubyType {
+        Mruby::new_with_opts(MrubyOptions::default())
+    }
+
+    /// Creates an mruby state and context configured with `opts`. Useful for sandboxed
+    /// embedding, where scripts should not `require` files. Panics if `opts.panic_class` is not
+    /// a free constant name or if `opts.panic_superclass` does not name an `Exception` class.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::*;
+    /// let mruby = Mruby::new_with_opts(MrubyOptions {
+    ///     instruction_limit: Some(1000),
+    ///     ..MrubyOptions::default()
+    /// });
+    ///
+    /// assert_eq!(mruby.run("1 + 1").unwrap().to_i32().unwrap(), 2);
+    /// assert!(mruby.run("loop {}").is_err());
+    /// ```
+    pub fn new_with_opts(opts: MrubyOptions) -> MrubyType {
         unsafe {
             let mrb = mrb_open();
 
@@ -76,10 +174,31 @@ impl Mruby {
                     ctx:           mrbc_context_new(mrb),
                     filename:      None,
                     classes:       HashMap::new(),
+                    dyn_classes:   HashMap::new(),
                     methods:       HashMap::new(),
                     class_methods: HashMap::new(),
+                    modules:       HashMap::new(),
+                    named_methods: HashMap::new(),
+                    drops:         HashMap::new(),
+                    proc_ty:       MrDataType {
+                        name: "RustProc\0".as_ptr() as *const c_char,
+                        free: free_rc::<ProcFn>
+                    },
+                    singleton_ty:  MrDataType {
+                        name: "RustSingleton\0".as_ptr() as *const c_char,
+                        free: free_rc::<SingletonMethods>
+                    },
+                    panic_class:   opts.panic_class.clone(),
                     files:         HashMap::new(),
-                    required:      HashSet::new()
+                    required:      HashSet::new(),
+                    load_paths:    opts.load_paths,
+                    file_stack:    Vec::new(),
+                    interrupt:     None,
+                    instructions:  opts.instruction_limit,
+                    executed:      Cell::new(0),
+                    inspecting:    false,
+                    user_data:     None,
+                    captures:      Vec::new()
                 }
             ));
 
@@ -121,45 +240,12 @@ impl Mruby {
                                 mruby.bool(true)
                             },
                             None => {
-                                let filename = mruby.borrow().filename.clone();
-
-                                let execute = |path: &Path, name: String,
-                                               filename: Option<String>| {
-                                    { mruby.borrow_mut().required.insert(name); }
-
-                                    let result = mruby.execute(path);
-
-                                    match filename {
-                                        Some(filename) => mruby.filename(&filename),
-                                        None           => mruby.borrow_mut().filename = None
-                                    }
-
-                                    match result {
-                                        Err(err) => {
-                                            mruby.raise("RuntimeError", &format!("{}", err));
-                                        }
-                                        _ => ()
-                                    }
-
-                                    mruby.bool(true)
-                                };
-
-                                let path = Path::new(name);
-                                let rb = name.to_owned() + ".rb";
-                                let rb = Path::new(&rb);
-                                let mrb = name.to_owned() + ".mrb";
-                                let mrb = Path::new(&mrb);
-
-                                if rb.is_file() {
-                                    execute(rb, name.to_owned(), filename)
-                                } else if mrb.is_file() {
-                                    execute(mrb, name.to_owned(), filename)
-                                } else if path.is_file() {
-                                    execute(path, name.to_owned(), filename)
-                                } else {
-                                    mruby.raise("RuntimeError",
-                                                &format!("cannot load {}.rb or {}.mrb",
-                                                         name, name))
+                                let mut dirs = vec![PathBuf::new()];
+                                dirs.extend(mruby.borrow().load_paths.iter().cloned());
+
+                                match find_file(&dirs, name) {
+                                    Ok(path) => require_file(&mruby, &path, name.to_owned()),
+                                    Err(err) => mruby.raise("RuntimeError", &err)
                                 }
                             }
                         }
@@ -171,26 +257,250 @@ impl Mruby {
                 }
             }
 
-            mrb_define_module_function(mrb, kernel, CString::new("require").unwrap().as_ptr(),
-                                       require, 1 << 12);
+            extern "C" fn require_relative(mrb: *const MrState, _slf: MrValue) -> MrValue {
+                unsafe {
+                    let ptr = mrb_ext_get_ud(mrb);
+                    let mruby = mem::transmute::<*const u8, MrubyType>(ptr);
+
+                    let name = mem::uninitialized::<*const c_char>();
+
+                    mrb_get_args(mrb, CString::new("z").unwrap().as_ptr(),
+                                 &name as *const *const c_char);
+
+                    let name = CStr::from_ptr(name).to_str().unwrap();
+
+                    let dir = match mruby.borrow().file_stack.last() {
+                        Some(path) => path.parent().map(|dir| dir.to_path_buf())
+                                                   .unwrap_or(PathBuf::new()),
+                        None       => PathBuf::new()
+                    };
+
+                    let result = match find_file(&[dir], name) {
+                        Ok(path) => {
+                            let key = path.display().to_string();
+
+                            let already_required = {
+                                mruby.borrow().required.contains(&key)
+                            };
+
+                            if already_required {
+                                mruby.bool(false)
+                            } else {
+                                require_file(&mruby, &path, key)
+                            }
+                        },
+                        Err(err) => mruby.raise("RuntimeError", &err)
+                    };
+
+                    mem::forget(mruby);
+
+                    result.value
+                }
+            }
+
+            if opts.allow_require {
+                mrb_define_module_function(mrb, kernel,
+                                           CString::new("require").unwrap().as_ptr(),
+                                           require, 1 << 12);
+                mrb_define_module_function(mrb, kernel,
+                                           CString::new("require_relative").unwrap().as_ptr(),
+                                           require_relative, 1 << 12);
+            }
+
+            if !opts.gc_enabled {
+                mrb_ext_gc_disable(mrb);
+            }
 
             let ptr = mem::transmute::<MrubyType, *const u8>(mruby);
             mrb_ext_set_ud(mrb, ptr);
 
             let mruby = mem::transmute::<*const u8, MrubyType>(ptr);
 
+            mrb_ext_define_freeze(mrb);
+
+            // mruby-eval is compiled, but not initialized with the other gems.
+            mrb_mruby_eval_gem_init(mrb);
+
+            // mrb_class_get raises when the class is missing, which cannot be rescued here.
+            let superclass = CString::new(opts.panic_superclass.clone()).unwrap();
+            let superclass = mrb_ext_class_path(mrb, superclass.as_ptr());
+
+            if superclass.is_null() || !mrb_ext_is_exception_class(mrb, superclass) {
+                panic!("panic_superclass {} is not an Exception class", opts.panic_superclass);
+            }
+
+            let valid = opts.panic_class.chars().next().map_or(false, |c| c.is_uppercase()) &&
+                        opts.panic_class.chars().all(|c| c.is_alphanumeric() || c == '_');
+            let panic_class = CString::new(opts.panic_class.clone()).unwrap();
+
+            if !valid || mrb_class_defined(mrb, panic_class.as_ptr()) {
+                panic!("panic_class {} is not a free constant name", opts.panic_class);
+            }
+
+            mrb_define_class(mrb, panic_class.as_ptr(), superclass);
+
             mruby.run_unchecked("
-              class RustPanic < Exception
-                def initialize(message)
-                  super message
-                end
+              class FrozenError < RuntimeError
+              end
+
+              class ResourceError < Exception
               end
             ");
 
+            set_code_fetch_hook(&mruby);
+
             mruby
         }
     }
 
+    /// Creates an mruby state like `new_with_opts` with `allow_require: false` and removes
+    /// methods that reach outside the interpreter. `Kernel#require`, `require_relative`, `load`,
+    /// `exit`, `exit!`, `system`, `exec`, `spawn`, `fork`, `open` and `` ` `` raise a
+    /// `SecurityError`, which is not a `StandardError` and cannot be rescued by a bare `rescue`.
+    /// The `File` and `IO` classes are removed when present.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::*;
+    /// let mruby = Mruby::sandboxed();
+    ///
+    /// let result = mruby.run("
+    ///   begin
+    ///     system 'rm -rf /'
+    ///   rescue SecurityError => e
+    ///     e.message
+    ///   end
+    /// ").unwrap();
+    ///
+    /// assert_eq!(result.to_str().unwrap(), "system is not allowed in a sandbox");
+    /// ```
+    pub fn sandboxed() -> MrubyType {
+        let mruby = Mruby::new_with_opts(MrubyOptions {
+            allow_require: false,
+            ..MrubyOptions::default()
+        });
+
+        mruby.undef_method("Kernel", "exit").ok();
+
+        unsafe {
+            let mrb = mruby.borrow().mrb;
+
+            let object = mrb_ext_class_value(mrb_class_get(mrb,
+                                                           CString::new("Object").unwrap()
+                                                                                 .as_ptr()));
+
+            for name in &["File", "IO"] {
+                let sym = mruby.sym(name);
+
+                if mrb_const_defined(mrb, object, sym) {
+                    mrb_const_remove(mrb, object, sym);
+                }
+            }
+        }
+
+        mruby.run_unchecked("
+          class SecurityError < Exception
+          end
+
+          module Kernel
+            [:require, :require_relative, :load, :exit, :exit!, :system, :exec, :spawn, :fork,
+             :open, :`].each do |name|
+              define_method(name) do |*args|
+                raise SecurityError, \"#{name} is not allowed in a sandbox\"
+              end
+            end
+          end
+        ");
+
+        mruby
+    }
+
+    /// Returns the width in bits of mruby's `Fixnum`, as probed from the running libmruby. This is
+    /// `16` when compiled with `MRB_INT16`, `64` when compiled with `MRB_INT64` and `32` otherwise.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// assert_eq!(Mruby::int_bits(), 32);
+    /// ```
+    pub fn int_bits() -> u32 {
+        unsafe {
+            mrb_ext_int_bit() as u32
+        }
+    }
+
+    /// Converts a `MrubyType` into a `MrubyOwned` that can be moved to another thread. Returns
+    /// the `MrubyType` back in an `Err` if it is still shared, e.g. by live `Value`s.
+    ///
+    /// *Note:* This is `unsafe` because Rust closures defined as methods are not required to be
+    /// `Send`. They must not share non-thread-safe state with the current thread.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// use std::thread;
+    ///
+    /// let mruby = Mruby::new();
+    /// mruby.run("$x = 1").unwrap();
+    ///
+    /// let owned = unsafe { Mruby::into_owned(mruby) }.ok().unwrap();
+    ///
+    /// let worker = thread::spawn(move || {
+    ///     let mruby = Mruby::from_owned(owned);
+    ///
+    ///     mruby.run("$x + 1").unwrap().to_i32().unwrap()
+    /// });
+    ///
+    /// assert_eq!(worker.join().unwrap(), 2);
+    /// ```
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let value = mruby.fixnum(1);
+    ///
+    /// assert!(unsafe { Mruby::into_owned(mruby) }.is_err());
+    /// ```
+    pub unsafe fn into_owned(mruby: MrubyType) -> Result<MrubyOwned, MrubyType> {
+        match Rc::try_unwrap(mruby) {
+            Ok(mruby) => {
+                mrb_ext_set_ud(mruby.borrow().mrb, ptr::null());
+
+                Ok(MrubyOwned { mruby: Box::new(mruby) })
+            },
+            Err(mruby) => Err(mruby)
+        }
+    }
+
+    /// Converts a `MrubyOwned` back into a `MrubyType` on the thread that will use it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let owned = unsafe { Mruby::into_owned(Mruby::new()) }.ok().unwrap();
+    /// let mruby = Mruby::from_owned(owned);
+    ///
+    /// assert_eq!(mruby.run("1 + 1").unwrap().to_i32().unwrap(), 2);
+    /// ```
+    pub fn from_owned(owned: MrubyOwned) -> MrubyType {
+        unsafe {
+            let mruby = Rc::new(*owned.mruby);
+            let mrb = mruby.borrow().mrb;
+
+            let ptr = mem::transmute::<MrubyType, *const u8>(mruby);
+            mrb_ext_set_ud(mrb, ptr);
+
+            mem::transmute::<*const u8, MrubyType>(ptr)
+        }
+    }
+
     fn close(&self) {
         unsafe {
             mrb_close(self.mrb);
@@ -198,15 +508,95 @@ impl Mruby {
     }
 }
 
+/// A uniquely owned `Mruby` that can be moved to another thread. Created with
+/// `Mruby::into_owned` and turned back into a `MrubyType` with `Mruby::from_owned`.
+///
+/// *Note:* `MrubyOwned` is `Send` but not `Sync`. mruby is not thread-safe, so accessing the same
+/// state from multiple threads concurrently is undefined behavior.
+pub struct MrubyOwned {
+    mruby: Box<RefCell<Mruby>>
+}
+
+unsafe impl Send for MrubyOwned {}
+
+/// A script compiled once with `compile_proc` and run with `run_compiled`.
+///
+/// The compiled `Proc` is kept from the garbage collector and keeps its `Mruby` alive until the
+/// `CompiledScript` is dropped.
+pub struct CompiledScript {
+    mruby: MrubyType,
+    value: MrValue
+}
+
+impl Drop for CompiledScript {
+    fn drop(&mut self) {
+        unsafe {
+            mrb_gc_unregister(self.mruby.borrow().mrb, self.value);
+        }
+    }
+}
+
 /// An `enum` containing all possbile types of errors.
+///
+/// Exceptions raised by scripts are returned as `RuntimeWithBacktrace` when their backtrace has
+/// more than one frame, i.e. when they are raised from inside a method or block of a script run
+/// with a file name (`run_named`, `execute` or `require`). Otherwise, they are returned as
+/// `Runtime`, where the message already contains the location. mruby only records frames for
+/// scripts with a file name, so exceptions of scripts run with `run` are always `Runtime`. Code
+/// handling every mruby exception needs to match both.
+///
+/// # Examples
+///
+/// ```
+/// # use mrusty::Mruby;
+/// # use mrusty::MrubyError;
+/// # use mrusty::MrubyImpl;
+/// let mruby = Mruby::new();
+///
+/// match mruby.run("def nested; raise 'nested'; end; nested") {
+///     Err(MrubyError::Runtime(message)) => assert_eq!(message, "RuntimeError: nested"),
+///     _ => assert!(false)
+/// }
+///
+/// match mruby.run_named("a.rb", "raise 'top'") {
+///     Err(MrubyError::Runtime(message)) => assert_eq!(message, "a.rb:1: top (RuntimeError)"),
+///     _ => assert!(false)
+/// }
+///
+/// match mruby.run_named("b.rb", "def nested; raise 'nested'; end; nested") {
+///     Err(MrubyError::RuntimeWithBacktrace { message, backtrace }) => {
+///         assert_eq!(message, "b.rb:1: nested (RuntimeError)");
+///         assert_eq!(backtrace.len(), 2);
+///     },
+///     _ => assert!(false)
+/// }
+/// ```
 #[derive(Debug)]
 pub enum MrubyError {
     /// type cast error
     Cast(String),
     /// undefined type error
     Undef,
-    /// mruby runtime error
+    /// mruby syntax error found while parsing, before anything is run
+    Syntax {
+        message: String,
+        line:    u32,
+        column:  u32
+    },
+    /// mruby runtime error without a backtrace of more than one frame
     Runtime(String),
+    /// frozen mruby object mutated from Rust
+    Frozen(String),
+    /// Rust `panic!` inside a method or `Proc` defined in Rust, with the panic's message
+    Panic(String),
+    /// `ResourceError` raised when a script exceeds its instruction limit
+    Resource(String),
+    /// mruby runtime error raised inside a method or block of a script run with a file name, with
+    /// its backtrace
+    RuntimeWithBacktrace {
+        message:   String,
+        backtrace: Vec<String>
+    },
     /// unrecognized file type error
     Filetype,
     /// Rust `Io` error
@@ -222,9 +612,30 @@ impl fmt::Display for MrubyError {
             MrubyError::Undef => {
                 write!(f, "Undefined error: type is not defined")
             },
+            MrubyError::Syntax { ref message, line, column } => {
+                write!(f, "Syntax error: line {}, column {}: {}", line, column, message)
+            },
             MrubyError::Runtime(ref err) => {
                 write!(f, "Runtime error: {}", err)
             },
+            MrubyError::Frozen(ref err) => {
+                write!(f, "Frozen error: {}", err)
+            },
+            MrubyError::Panic(ref err) => {
+                write!(f, "Rust panic: {}", err)
+            },
+            MrubyError::Resource(ref err) => {
+                write!(f, "Resource error: {}", err)
+            },
+            MrubyError::RuntimeWithBacktrace { ref message, ref backtrace } => {
+                try!(write!(f, "Runtime error: {}", message));
+
+                for frame in backtrace {
+                    try!(write!(f, "\n    from {}", frame));
+                }
+
+                Ok(())
+            },
             MrubyError::Filetype => {
                 write!(f, "Filetype error: script needs a compatible (.rb, .mrb) extension")
             },
@@ -236,11 +647,16 @@ impl fmt::Display for MrubyError {
 impl Error for MrubyError {
     fn description(&self) -> &str {
         match *self {
-            MrubyError::Cast(_)     => "mruby value cast error",
-            MrubyError::Undef       => "mruby undefined error",
-            MrubyError::Runtime(_)  => "mruby runtime error",
-            MrubyError::Filetype    => "filetype mistmatch",
-            MrubyError::Io(ref err) => err.description()
+            MrubyError::Cast(_)                     => "mruby value cast error",
+            MrubyError::Undef                       => "mruby undefined error",
+            MrubyError::Syntax { .. }               => "mruby syntax error",
+            MrubyError::Runtime(_)                  => "mruby runtime error",
+            MrubyError::Frozen(_)                   => "mruby frozen error",
+            MrubyError::Panic(_)                    => "Rust panic",
+            MrubyError::Resource(_)                 => "mruby resource limit error",
+            MrubyError::RuntimeWithBacktrace { .. } => "mruby runtime error",
+            MrubyError::Filetype                    => "filetype mistmatch",
+            MrubyError::Io(ref err)                 => err.description()
         }
     }
 }
@@ -251,6 +667,110 @@ impl From<io::Error> for MrubyError {
     }
 }
 
+impl MrubyError {
+    /// Renders the error like `Display` followed by the offending line of `source` with a caret
+    /// under the column for syntax errors, or the whole line underlined for runtime errors raised
+    /// in a named file. Falls back to the `Display` output when `source` is `None` or the error
+    /// carries no line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let source = "a = 1\n  a.nope";
+    /// let err = mruby.run_named("script.rb", source).unwrap_err();
+    ///
+    /// assert_eq!(err.pretty(Some(source)),
+    ///            "Runtime error: script.rb:2: undefined method 'nope' for 1 (NoMethodError)\n\
+    ///             \x20 |\n\
+    ///             2 |   a.nope\n\
+    ///             \x20 |   ^^^^^^");
+    ///
+    /// assert_eq!(err.pretty(None), err.to_string());
+    /// ```
+    pub fn pretty(&self, source: Option<&str>) -> String {
+        let message = self.to_string();
+
+        let (line, column) = match *self {
+            MrubyError::Syntax { line, column, .. } => (Some(line), Some(column)),
+            MrubyError::Runtime(ref err) |
+            MrubyError::RuntimeWithBacktrace { message: ref err, .. } => (error_line(err), None),
+            _ => (None, None)
+        };
+
+        let code = match (source, line) {
+            (Some(source), Some(line)) if line > 0 => source.lines().nth(line as usize - 1),
+            _                                      => None
+        };
+
+        let (line, code) = match (line, code) {
+            (Some(line), Some(code)) => (line.to_string(), code),
+            _                        => return message
+        };
+
+        let gutter: String = line.chars().map(|_| ' ').collect();
+
+        let marker = match column {
+            Some(column) => {
+                let column = cmp::min(column as usize, code.chars().count());
+
+                code.chars().take(column.saturating_sub(1)).map(|c| {
+                    if c == '\t' { '\t' } else { ' ' }
+                }).collect::<String>() + "^"
+            },
+            None => {
+                let indent = code.len() - code.trim_left().len();
+
+                code[..indent].to_owned() +
+                    &code.trim().chars().map(|_| '^').collect::<String>()
+            }
+        };
+
+        format!("{}\n{} |\n{} | {}\n{} | {}", message, gutter, line, code, gutter, marker)
+    }
+}
+
+// Parses the line number of runtime errors raised in a named file, e.g. "file.rb:3: message".
+fn error_line(message: &str) -> Option<u32> {
+    let mut parts = message.splitn(3, ':');
+
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(_), Some(line), Some(rest)) if rest.starts_with(' ') => line.parse().ok(),
+        _                                                          => None
+    }
+}
+
+/// An `enum` returned by `parse_state` describing whether a script parses.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseStatus {
+    /// script parses and can be run
+    Complete,
+    /// script ends early, e.g. in an open block or `String`, and needs more input
+    Incomplete,
+    /// script has a syntax error which more input cannot fix
+    Error {
+        message: String,
+        line:    u32
+    }
+}
+
+/// A `struct` returned by `gc_stats` with a snapshot of the mruby GC heap.
+///
+/// mruby does not keep a running count of allocations, so `total` reports the capacity of the
+/// allocated heap pages instead.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GcStats {
+    /// number of live objects on the heap
+    pub live:        usize,
+    /// number of object slots in all allocated heap pages
+    pub total:       usize,
+    /// current depth of the GC arena, as returned by `gc_arena_save`
+    pub arena_index: usize
+}
+
 /// A `trait` useful for organising Rust types into dynamic mruby files.
 ///
 /// # Examples
@@ -304,7 +824,8 @@ pub trait MrubyImpl {
     fn filename(&self, filename: &str);
 
     /// Runs mruby `script` on a state and context and returns a `Value` in an `Ok`
-    /// or an `Err` containing an mruby `Exception`'s message.
+    /// or an `Err` containing an mruby `Exception`'s message. Scripts that do not parse return a
+    /// `MrubyError::Syntax` without being run.
     ///
     /// # Examples
     ///
@@ -330,34 +851,118 @@ pub trait MrubyImpl {
     /// },
     ///     _ => assert!(false)
     /// }
+    ///
+    /// match mruby.run("def f(\n  1 +") {
+    ///     Err(MrubyError::Syntax { line, .. }) => assert_eq!(line, 2),
+    ///     _ => assert!(false)
+    /// }
     /// ```
     #[inline]
     fn run(&self, script: &str) -> Result<Value, MrubyError>;
 
-    /// Runs mruby `script` on a state and context and returns a `Value`. If an mruby Exception is
-    /// raised, mruby will be left to handle it.
+    /// Runs mruby `script` like `run` while reporting errors as coming from `filename`. The
+    /// previous filename is restored afterwards, so later runs are not affected.
+    ///
     /// # Examples
     ///
     /// ```
     /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyError;
     /// # use mrusty::MrubyImpl;
     /// let mruby = Mruby::new();
-    /// let result = mruby.run_unchecked("true");
+    /// let result = mruby.run_named("config.rb", "1.nope");
     ///
-    /// assert_eq!(result.to_bool().unwrap(), true);
-    /// ```
+    /// match result {
+    ///     Err(MrubyError::Runtime(err)) => {
+    ///         assert_eq!(err, "config.rb:1: undefined method \'nope\' for 1 (NoMethodError)");
+    /// },
+    ///     _ => assert!(false)
+    /// }
     ///
+    /// match mruby.run("1.nope") {
+    ///     Err(MrubyError::Runtime(err)) => {
+    ///         assert_eq!(err, "NoMethodError: undefined method \'nope\' for 1");
+    /// },
+    ///     _ => assert!(false)
+    /// }
     /// ```
-    /// # #[macro_use] extern crate mrusty;
-    /// use mrusty::*;
+    fn run_named(&self, filename: &str, script: &str) -> Result<Value, MrubyError>;
+
+    /// Runs `scripts` in order on the same context, like consecutive calls to `run`, and returns
+    /// their `Value`s. Later scripts see everything defined by earlier ones. Running stops at the
+    /// first error, which is returned along with the index of the failing script.
     ///
-    /// # fn main() {
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyError;
+    /// # use mrusty::MrubyImpl;
     /// let mruby = Mruby::new();
+    /// let results = mruby.run_all(&["def double(n); n * 2; end", "double 2"]).unwrap();
     ///
-    /// struct Cont;
+    /// assert_eq!(results[1].to_i32().unwrap(), 4);
     ///
-    /// mruby.def_class::<Cont>("Container");
-    /// mruby.def_class_method::<Cont, _>("raise", mrfn!(|mruby, _slf: Value| {
+    /// match mruby.run_all(&["$ran = 1", "triple 2", "$ran = 3"]) {
+    ///     Err((1, MrubyError::Runtime(err))) => {
+    ///         assert_eq!(err, "NoMethodError: undefined method 'triple' for main");
+    ///     },
+    ///     _ => assert!(false)
+    /// }
+    ///
+    /// assert_eq!(mruby.run("$ran").unwrap().to_i32().unwrap(), 1);
+    /// ```
+    fn run_all(&self, scripts: &[&str]) -> Result<Vec<Value>, (usize, MrubyError)>;
+
+    /// Runs mruby `script` like `run` and returns its `Value` together with everything it printed
+    /// with `print`, `puts` and `p`, instead of writing it to stdout. Captures can be nested,
+    /// e.g. from a method called by a capturing script, and each one only collects its own
+    /// output. Printing goes back to stdout once the outermost capture ends.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let (value, output) = mruby.run_capturing("
+    ///   puts 'Hello'
+    ///   print 1, 2
+    ///   p :three
+    ///
+    ///   4
+    /// ").unwrap();
+    ///
+    /// assert_eq!(value.to_i32().unwrap(), 4);
+    /// assert_eq!(output, "Hello\n12:three\n");
+    /// ```
+    fn run_capturing(&self, script: &str) -> Result<(Value, String), MrubyError>;
+
+    /// Runs mruby `script` on a state and context and returns a `Value`. If an mruby Exception is
+    /// raised, mruby will be left to handle it.
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run_unchecked("true");
+    ///
+    /// assert_eq!(result.to_bool().unwrap(), true);
+    /// ```
+    ///
+    /// ```
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::*;
+    ///
+    /// # fn main() {
+    /// let mruby = Mruby::new();
+    ///
+    /// struct Cont;
+    ///
+    /// mruby.def_class::<Cont>("Container");
+    /// mruby.def_class_method::<Cont, _>("raise", mrfn!(|mruby, _slf: Value| {
     ///     mruby.run_unchecked("fail 'surprize'")
     /// }));
     ///
@@ -375,6 +980,88 @@ pub trait MrubyImpl {
     #[inline]
     fn run_unchecked(&self, script: &str) -> Value;
 
+    /// Runs mruby `script` like `run`, with the local variables `vars` bound to their `Value`s.
+    /// Since the values are never spliced into the source, this is safe to use with user data.
+    /// The bindings only live for the duration of the run.
+    ///
+    /// Returns an `Err` if a name in `vars` is not a valid local variable name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let result = mruby.run_with_binding("a + b", vec![
+    ///     ("a", mruby.string("'; fail; '")),
+    ///     ("b", mruby.string("!"))
+    /// ]).unwrap();
+    ///
+    /// assert_eq!(result.to_str().unwrap(), "'; fail; '!");
+    /// assert!(mruby.run("a").is_err());
+    /// ```
+    fn run_with_binding(&self, script: &str,
+                        vars: Vec<(&str, Value)>) -> Result<Value, MrubyError>;
+
+    /// Runs mruby `script` like `run`, but raises a `RuntimeError` once `dur` has passed. The error
+    /// is raised a single time, so scripts can `rescue` it like any other `StandardError`; a script
+    /// that rescues it and keeps running is not interrupted again. Use `set_instruction_limit` to
+    /// bound scripts that must not be able to recover.
+    ///
+    /// *Note:* The deadline is only checked between VM instructions, so it cannot interrupt a Rust
+    /// method or C function that does not return control to the script.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// use std::time::Duration;
+    ///
+    /// let mruby = Mruby::new();
+    ///
+    /// let result = mruby.run_with_timeout("loop {}", Duration::from_millis(10));
+    ///
+    /// assert!(result.is_err());
+    ///
+    /// let result = mruby.run_with_timeout("2 + 2", Duration::from_millis(10)).unwrap();
+    ///
+    /// assert_eq!(result.to_i32().unwrap(), 4);
+    /// ```
+    fn run_with_timeout(&self, script: &str, dur: Duration) -> Result<Value, MrubyError>;
+
+    /// Limits the number of VM instructions each `run`, or call into mruby from Rust, can execute
+    /// to `max`, replacing any previous limit. Once the limit is reached, a `ResourceError` is
+    /// raised and returned as a `MrubyError::Resource`. Unlike a timeout, this bounds the work
+    /// scripts do in the same way on every machine.
+    ///
+    /// `ResourceError` does not inherit from `StandardError`, so a bare `rescue` does not catch
+    /// it. Rescuing it explicitly does not grant more instructions either: every further
+    /// instruction raises again until the run returns to Rust, so scripts cannot recover from it.
+    ///
+    /// *Note:* Like the timeout of `run_with_timeout`, the limit is only checked between VM
+    /// instructions, so work done in Rust methods or C functions is not counted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyError;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.set_instruction_limit(1000);
+    ///
+    /// match mruby.run("loop {}") {
+    ///     Err(MrubyError::Resource(err)) => assert_eq!(err, "instruction limit exceeded"),
+    ///     _ => assert!(false)
+    /// }
+    ///
+    /// assert_eq!(mruby.run("2 + 2").unwrap().to_i32().unwrap(), 4);
+    /// ```
+    fn set_instruction_limit(&self, max: u64);
+
     /// Runs mruby compiled (.mrb) `script` on a state and context and returns a `Value` in an `Ok`
     /// or an `Err` containing an mruby `Exception`'s message.
     ///
@@ -387,8 +1074,103 @@ pub trait MrubyImpl {
     #[inline]
     fn runb(&self, script: &[u8]) -> Result<Value, MrubyError>;
 
+    /// Compiles mruby `script` to bytecode and returns it in an `Ok` or an `Err` containing the
+    /// syntax error's message and line. The resulting bytes can be run with `runb` or saved to
+    /// an .mrb file and `execute`d.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let bytecode = mruby.compile("2 + 2").unwrap();
+    ///
+    /// let result = mruby.runb(&bytecode).unwrap();
+    ///
+    /// assert_eq!(result.to_i32().unwrap(), 4);
+    /// ```
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyError;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.compile("1 +\n)");
+    ///
+    /// match result {
+    ///     Err(MrubyError::Syntax { message, line, .. }) => {
+    ///         assert_eq!(message, "syntax error, unexpected ')'");
+    ///         assert_eq!(line, 2);
+    /// },
+    ///     _ => assert!(false)
+    /// }
+    /// ```
+    fn compile(&self, script: &str) -> Result<Vec<u8>, MrubyError>;
+
+    /// Parses and compiles mruby `script` once into a `CompiledScript` which can then be run
+    /// repeatedly with `run_compiled`. Returns an `Err` on syntax errors, like `compile`.
+    ///
+    /// *Note:* Unlike `run`, every run of a `CompiledScript` gets its own local variables.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.run("$count = 0").unwrap();
+    ///
+    /// let script = mruby.compile_proc("$count += 1").unwrap();
+    ///
+    /// for _ in 0..3 {
+    ///     mruby.run_compiled(&script).unwrap();
+    /// }
+    ///
+    /// assert_eq!(mruby.run("$count").unwrap().to_i32().unwrap(), 3);
+    /// assert!(mruby.compile_proc("1 +\n)").is_err());
+    /// ```
+    fn compile_proc(&self, script: &str) -> Result<CompiledScript, MrubyError>;
+
+    /// Parses mruby `script` without running it and returns whether it is `Complete`,
+    /// `Incomplete` or has a syntax `Error`. Useful for REPLs to know when to read more lines.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::*;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.parse_state("def foo"), ParseStatus::Incomplete);
+    /// assert_eq!(mruby.parse_state("def foo; end"), ParseStatus::Complete);
+    /// assert_eq!(mruby.parse_state("1 +\n)"), ParseStatus::Error {
+    ///     message: "syntax error, unexpected ')'".to_owned(),
+    ///     line:    2
+    /// });
+    /// ```
+    fn parse_state(&self, script: &str) -> ParseStatus;
+
+    /// Runs a `CompiledScript` created with `compile_proc` and returns a `Value` in an `Ok` or an
+    /// `Err` containing an mruby `Exception`'s message. Returns an `Err` if `script` was compiled
+    /// on a different `Mruby`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let script = mruby.compile_proc("a = 2; a * 2").unwrap();
+    ///
+    /// assert_eq!(mruby.run_compiled(&script).unwrap().to_i32().unwrap(), 4);
+    /// assert!(Mruby::new().run_compiled(&script).is_err());
+    /// ```
+    fn run_compiled(&self, script: &CompiledScript) -> Result<Value, MrubyError>;
+
     /// Runs mruby (compiled (.mrb) or not (.rb)) `script` on a state and context and returns a
-    /// `Value` in an `Ok` or an `Err` containing an mruby `Exception`'s message.
+    /// `Value` in an `Ok` or an `Err` containing an mruby `Exception`'s message. Files
+    /// `require_relative`d from `script` are resolved relative to its directory.
     ///
     /// # Examples
     ///
@@ -399,6 +1181,24 @@ pub trait MrubyImpl {
     #[inline]
     fn execute(&self, script: &Path) -> Result<Value, MrubyError>;
 
+    /// Runs the file at `path` like `execute`, but lets mruby read and parse `.rb` files directly
+    /// instead of loading them into a Rust `String` first. Compiled `.mrb` files are run through
+    /// `execute`. Errors report the file's name and the line they occurred on.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// # use std::path::Path;
+    /// let mruby = Mruby::new();
+    ///
+    /// let result = mruby.load_file(Path::new("tests/a/main.rb")).unwrap();
+    ///
+    /// assert_eq!(result.to_str().unwrap(), "helper");
+    /// ```
+    fn load_file(&self, path: &Path) -> Result<Value, MrubyError>;
+
     /// Raises an mruby `RuntimeError` with `message` message and `eclass` mruby Exception Class.
     ///
     /// # Examples
@@ -476,59 +1276,57 @@ pub trait MrubyImpl {
     #[inline]
     fn def_file<T: MrubyFile>(&self, name: &str);
 
-    /// Defines Rust type `T` as an mruby `Class` named `name`.
+    /// Adds `dir` to the directories searched by `require`, similar to Ruby's `$LOAD_PATH`.
+    /// The current directory is always searched first, followed by the added directories in
+    /// order.
     ///
     /// # Examples
     ///
     /// ```
     /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyError;
     /// # use mrusty::MrubyImpl;
+    /// use std::path::Path;
+    ///
     /// let mruby = Mruby::new();
     ///
-    /// struct Cont {
-    ///     value: i32
-    /// }
+    /// mruby.add_load_path(Path::new("lib"));
     ///
-    /// mruby.def_class::<Cont>("Container");
+    /// let result = mruby.run("require 'missing'");
+    ///
+    /// match result {
+    ///     Err(MrubyError::Runtime(err)) => {
+    ///         assert_eq!(err, "RuntimeError: cannot load missing; tried missing.rb, missing.mrb, \
+    ///                          missing, lib/missing.rb, lib/missing.mrb, lib/missing");
+    /// },
+    ///     _ => assert!(false)
+    /// }
     /// ```
-    fn def_class<T: Any>(&self, name: &str);
+    #[inline]
+    fn add_load_path(&self, dir: &Path);
 
-    /// Defines an mruby method named `name`. The closure to be run when the `name` method is
-    /// called should be passed through the `mrfn!` macro.
+    /// Defines Rust type `T` as an mruby `Class` named `name`. Defining an already defined `T`
+    /// again reopens its existing `Class`, keeping the methods defined so far.
     ///
     /// # Examples
     ///
     /// ```
-    /// # #[macro_use] extern crate mrusty;
-    /// use mrusty::*;
-    ///
-    /// # fn main() {
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
     /// let mruby = Mruby::new();
     ///
     /// struct Cont {
     ///     value: i32
-    /// };
+    /// }
     ///
     /// mruby.def_class::<Cont>("Container");
-    /// mruby.def_method::<Cont, _>("initialize", mrfn!(|_mruby, slf: Value, v: i32| {
-    ///     let cont = Cont { value: v };
-    ///
-    ///     slf.init(cont)
-    /// }));
-    /// mruby.def_method::<Cont, _>("value", mrfn!(|mruby, slf: Cont| {
-    ///     mruby.fixnum(slf.value)
-    /// }));
-    ///
-    /// let result = mruby.run("Container.new(3).value").unwrap();
-    ///
-    /// assert_eq!(result.to_i32().unwrap(), 3);
-    /// # }
+    /// mruby.def_class::<Cont>("Container");
     /// ```
-    fn def_method<T: Any, F>(&self, name: &str,
-                             method: F) where F: Fn(MrubyType, Value) -> Value + 'static;
+    fn def_class<T: Any>(&self, name: &str);
 
-    /// Defines an mruby class method named `name`. The closure to be run when the `name` method is
-    /// called should be passed through the `mrfn!` macro.
+    /// Defines Rust type `T` as an mruby `Class` named `name` nested inside `module`. Missing
+    /// modules in a nested `module` path like `"A::B"` are created. The `Class` is registered
+    /// under its fully qualified name, e.g. `"A::B::Container"`.
     ///
     /// # Examples
     ///
@@ -539,138 +1337,259 @@ pub trait MrubyImpl {
     /// # fn main() {
     /// let mruby = Mruby::new();
     ///
-    /// struct Cont;
+    /// struct Vector;
     ///
-    /// mruby.def_class::<Cont>("Container");
-    /// mruby.def_class_method::<Cont, _>("hi", mrfn!(|mruby, _slf: Value, v: i32| {
-    ///     mruby.fixnum(v)
+    /// mruby.def_class_under::<Vector>("Geometry", "Vector");
+    /// mruby.def_method::<Vector, _>("initialize", mrfn!(|_mruby, slf: Value| {
+    ///     slf.init(Vector)
     /// }));
     ///
-    /// let result = mruby.run("Container.hi 3").unwrap();
+    /// let result = mruby.run("Geometry::Vector.new").unwrap();
     ///
-    /// assert_eq!(result.to_i32().unwrap(), 3);
+    /// assert_eq!(mruby.class_name::<Vector>().unwrap(), "Geometry::Vector");
+    /// assert!(result.to_obj::<Vector>().is_ok());
     /// # }
     /// ```
-    fn def_class_method<T: Any, F>(&self, name: &str,
-                                   method: F) where F: Fn(MrubyType, Value) -> Value + 'static;
+    fn def_class_under<T: Any>(&self, module: &str, name: &str);
 
-    /// Return the mruby name of a previously defined Rust type `T` with `def_class`.
+    /// Defines Rust type `T` as an mruby `Class` named `name` which inherits from the previously
+    /// defined mruby `Class` `superclass`. Returns an `Err` if `superclass` is not defined.
     ///
     /// # Examples
     ///
     /// ```
-    /// # use mrusty::*;
-    ///
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
     /// let mruby = Mruby::new();
     ///
-    /// struct Cont;
+    /// struct Failure;
     ///
-    /// mruby.def_class::<Cont>("Container");
+    /// mruby.def_class_with_super::<Failure>("Failure", "StandardError").unwrap();
     ///
-    /// assert_eq!(mruby.class_name::<Cont>().unwrap(), "Container");
+    /// let result = mruby.run("
+    ///   begin
+    ///     raise Failure, 'failed'
+    ///   rescue => e
+    ///     e.message
+    ///   end
+    /// ").unwrap();
+    ///
+    /// assert_eq!(result.to_str().unwrap(), "failed");
+    /// assert!(mruby.def_class_with_super::<Failure>("Failure", "Nope").is_err());
     /// ```
-    fn class_name<T: Any>(&self) -> Result<String, MrubyError>;
+    fn def_class_with_super<T: Any>(&self, name: &str,
+                                    superclass: &str) -> Result<(), MrubyError>;
 
-    /// Creates mruby `Value` `nil`.
+    /// Defines Rust type `T` as an mruby `Class` named `name`, like `def_class`, and calls
+    /// `on_free` with every `T` freed by mruby. Useful for flushing or closing resources.
+    ///
+    /// mruby objects hold their `T` in an `Rc`. `on_free` is called when mruby frees the object,
+    /// either by garbage collection or when the `Mruby` is dropped, right before mruby's `Rc` is
+    /// dropped. It is only called if that `Rc` is the last one, so it always runs right before
+    /// `T` itself is dropped. If Rust still holds `T` (e.g. through a cloned `Value`), `on_free`
+    /// is skipped and `T` is dropped normally once the last `Rc` is gone.
+    ///
+    /// *Note:* `on_free` runs during garbage collection and must not call into mruby.
     ///
     /// # Examples
     ///
     /// ```
     /// # use mrusty::Mruby;
     /// # use mrusty::MrubyImpl;
-    /// let mruby = Mruby::new();
-    ///
-    /// struct Cont;
+    /// use std::cell::Cell;
+    /// use std::rc::Rc;
     ///
-    /// mruby.def_class::<Cont>("Container");
-    /// mruby.def_method::<Cont, _>("nil", |mruby, _slf| mruby.nil());
+    /// struct File {
+    ///     flushed: bool
+    /// }
     ///
-    /// let result = mruby.run("Container.new.nil.nil?").unwrap();
+    /// let flushed = Rc::new(Cell::new(0));
     ///
-    /// assert_eq!(result.to_bool().unwrap(), true);
-    /// ```
-    #[inline]
-    fn nil(&self) -> Value;
-
-    /// Creates mruby `Value` containing `true` or `false`.
+    /// {
+    ///     let flushed = flushed.clone();
+    ///     let mruby = Mruby::new();
     ///
-    /// # Examples
-    /// ```
-    /// # use mrusty::Mruby;
-    /// # use mrusty::MrubyImpl;
-    /// let mruby = Mruby::new();
+    ///     mruby.def_class_with_drop::<File, _>("File", move |file| {
+    ///         file.flushed = true;
+    ///         flushed.set(flushed.get() + 1);
+    ///     });
     ///
-    /// let b = mruby.bool(true);
+    ///     mruby.obj(File { flushed: false });
+    /// }
     ///
-    /// assert_eq!(b.to_bool().unwrap(), true);
+    /// assert_eq!(flushed.get(), 1);
     /// ```
-    #[inline]
-    fn bool(&self, value: bool) -> Value;
+    fn def_class_with_drop<T: Any, D>(&self, name: &str, on_free: D)
+        where D: Fn(&mut T) + 'static;
 
-    /// Creates mruby `Value` of `Class` `Fixnum`.
+    /// Defines Rust type `T` as an mruby `Class` named `name` whose objects hold a
+    /// `Rc<RefCell<T>>` instead of a `Rc<T>`, so methods can mutate them in place with
+    /// `Value::to_obj_mut`.
+    ///
+    /// The `Class` is registered as `RefCell<T>`, which is the type to use with `def_method`,
+    /// `init` and `obj`. Methods should take `slf: Value` and call `to_obj_mut`.
     ///
     /// # Examples
     ///
     /// ```
-    /// # use mrusty::Mruby;
-    /// # use mrusty::MrubyImpl;
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::*;
+    /// use std::cell::RefCell;
+    ///
+    /// # fn main() {
     /// let mruby = Mruby::new();
     ///
-    /// let fixn = mruby.fixnum(2);
+    /// struct Counter {
+    ///     count: i32
+    /// }
     ///
-    /// assert_eq!(fixn.to_i32().unwrap(), 2);
+    /// mruby.def_class_cell::<Counter>("Counter");
+    /// mruby.def_method::<RefCell<Counter>, _>("initialize", mrfn!(|_mruby, slf: Value| {
+    ///     slf.init(RefCell::new(Counter { count: 0 }))
+    /// }));
+    /// mruby.def_method::<RefCell<Counter>, _>("increment!", mrfn!(|_mruby, slf: Value| {
+    ///     slf.to_obj_mut::<Counter>().unwrap().count += 1;
+    ///
+    ///     slf
+    /// }));
+    ///
+    /// let counter = mruby.run("Counter.new.increment!.increment!").unwrap();
+    ///
+    /// assert_eq!(counter.to_obj_mut::<Counter>().unwrap().count, 2);
+    /// # }
     /// ```
-    #[inline]
-    fn fixnum(&self, value: i32) -> Value;
+    fn def_class_cell<T: Any>(&self, name: &str);
 
-    /// Creates mruby `Value` of `Class` `Float`.
+    /// Defines an mruby `Class` named `name` whose objects hold a `Rc<Any>`, registered under
+    /// `key` instead of a Rust type. This lets several Rust types, e.g. implementations of the
+    /// same trait, back a single mruby `Class`. Defining an already defined `key` again does
+    /// nothing.
+    ///
+    /// Objects are created with `obj_dyn` or `Value::init_dyn` and read back with
+    /// `Value::to_obj_dyn`, which returns a `Cast` error when the object holds another Rust type.
+    /// Storing a `Box<Trait>` lets every implementation be read back with the same type. Methods
+    /// are defined with `def_method_on` and should take `slf: Value`.
     ///
     /// # Examples
+    ///
     /// ```
-    /// # use mrusty::Mruby;
-    /// # use mrusty::MrubyImpl;
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::*;
+    ///
+    /// # fn main() {
+    /// trait Shape {
+    ///     fn area(&self) -> f64;
+    /// }
+    ///
+    /// struct Square(f64);
+    /// struct Circle(f64);
+    ///
+    /// impl Shape for Square {
+    ///     fn area(&self) -> f64 { self.0 * self.0 }
+    /// }
+    ///
+    /// impl Shape for Circle {
+    ///     fn area(&self) -> f64 { 3.0 * self.0 * self.0 }
+    /// }
+    ///
     /// let mruby = Mruby::new();
     ///
-    /// let fl = mruby.float(2.3);
+    /// mruby.def_class_dyn("shape", "Shape");
+    /// mruby.def_method_on("Shape", "area", mrfn!(|mruby, slf: Value| {
+    ///     mruby.float(slf.to_obj_dyn::<Box<Shape>>().unwrap().area())
+    /// })).unwrap();
     ///
-    /// assert_eq!(fl.to_f64().unwrap(), 2.3);
+    /// let square: Box<Shape> = Box::new(Square(2.0));
+    /// let circle: Box<Shape> = Box::new(Circle(1.0));
+    ///
+    /// mruby.top_self().set_var("shapes", mruby.array(vec![
+    ///     mruby.obj_dyn("shape", square),
+    ///     mruby.obj_dyn("shape", circle)
+    /// ]));
+    ///
+    /// let result = mruby.run("@shapes.map(&:area)").unwrap();
+    ///
+    /// assert_eq!(result.to_vec_of::<f64>().unwrap(), vec![4.0, 3.0]);
+    /// # }
     /// ```
-    #[inline]
-    fn float(&self, value: f64) -> Value;
+    fn def_class_dyn(&self, key: &str, name: &str);
 
-    /// Creates mruby `Value` of `Class` `String`.
+    /// Defines an mruby `Module` named `name`. Missing modules in a nested path like `"A::B"` are
+    /// created as well. Already defined modules are left untouched.
     ///
     /// # Examples
+    ///
     /// ```
     /// # use mrusty::Mruby;
     /// # use mrusty::MrubyImpl;
     /// let mruby = Mruby::new();
     ///
-    /// let s = mruby.string("hi");
+    /// mruby.def_module("Config::Defaults");
     ///
-    /// assert_eq!(s.to_str().unwrap(), "hi");
+    /// let result = mruby.run("Config::Defaults.class").unwrap();
+    ///
+    /// assert_eq!(result.call("to_s", vec![]).unwrap().to_str().unwrap(), "Module");
     /// ```
-    #[inline]
-    fn string(&self, value: &str) -> Value;
+    fn def_module(&self, name: &str);
 
-    /// Creates mruby `Value` of `Class` `Symbol`.
+    /// Defines an mruby module function named `name` on the previously defined mruby `Module`
+    /// `module`, like Ruby's `module_function`. It is callable as `Module.name` and as an instance
+    /// method of objects that include `module`. The closure to be run when the `name` method is
+    /// called should be passed through the `mrfn!` macro.
     ///
     /// # Examples
+    ///
     /// ```
-    /// # use mrusty::Mruby;
-    /// # use mrusty::MrubyImpl;
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::*;
+    ///
+    /// # fn main() {
     /// let mruby = Mruby::new();
     ///
-    /// let s = mruby.symbol("hi");
+    /// mruby.def_module("Config");
+    /// mruby.def_module_function("Config", "double", mrfn!(|mruby, _slf: Value, v: i32| {
+    ///     mruby.fixnum(v * 2)
+    /// }));
     ///
-    /// assert_eq!(s.to_str().unwrap(), "hi");
+    /// let result = mruby.run("Config.double 3").unwrap();
+    ///
+    /// assert_eq!(result.to_i32().unwrap(), 6);
+    /// # }
     /// ```
-    #[inline]
-    fn symbol(&self, value: &str) -> Value;
+    fn def_module_function<F>(&self, module: &str, name: &str,
+                              method: F) where F: Fn(MrubyType, Value) -> Value + 'static;
 
-    /// Creates mruby `Value` of `Class` `name` containing a Rust object of type `T`.
+    /// Defines an mruby method named `name` on an existing `Class` or `Module` `class`, like the
+    /// built-in `String` or `Integer`. Unlike `def_method`, `class` does not need to be defined
+    /// from Rust. The closure to be run when the `name` method is called should be passed
+    /// through the `mrfn!` macro.
     ///
-    /// *Note:* `T` must be defined on the current `Mruby` with `def_class`.
+    /// Returns an `Err` if `class` is not defined.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::*;
+    ///
+    /// # fn main() {
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.def_method_on("String", "shout", mrfn!(|mruby, slf: Value| {
+    ///     mruby.string(&slf.to_str().unwrap().to_uppercase())
+    /// })).unwrap();
+    ///
+    /// let result = mruby.run("'hi'.shout").unwrap();
+    ///
+    /// assert_eq!(result.to_str().unwrap(), "HI");
+    /// # }
+    /// ```
+    fn def_method_on<F>(&self, class: &str, name: &str, method: F) -> Result<(), MrubyError>
+        where F: Fn(MrubyType, Value) -> Value + 'static;
+
+    /// Defines a constant named `name` with `value` on the mruby `Class` or `Module` `class`.
+    /// Returns an `Err` if `class` is not defined or if `name` does not start with an uppercase
+    /// letter.
     ///
     /// # Examples
     ///
@@ -679,20 +1598,19 @@ pub trait MrubyImpl {
     /// # use mrusty::MrubyImpl;
     /// let mruby = Mruby::new();
     ///
-    /// struct Cont {
-    ///     value: i32
-    /// }
+    /// mruby.def_module("Config");
+    /// mruby.def_const("Config", "VERSION", mruby.string("1.0")).unwrap();
     ///
-    /// mruby.def_class::<Cont>("Container");
+    /// let result = mruby.run("Config::VERSION").unwrap();
     ///
-    /// let value = mruby.obj(Cont { value: 3 });
+    /// assert_eq!(result.to_str().unwrap(), "1.0");
+    /// assert!(mruby.def_const("Config", "version", mruby.nil()).is_err());
+    /// assert!(mruby.def_const("Nope", "VERSION", mruby.nil()).is_err());
     /// ```
-    #[inline]
-    fn obj<T: Any>(&self, obj: T) -> Value;
+    fn def_const(&self, class: &str, name: &str, value: Value) -> Result<(), MrubyError>;
 
-    /// Creates mruby `Value` of `Class` `name` containing a Rust `Option` of type `T`.
-    ///
-    /// *Note:* `T` must be defined on the current `Mruby` with `def_class`.
+    /// Returns the constant found at `path`, where nested constants are separated by `::`.
+    /// Returns `Undef` if any segment of `path` is not defined.
     ///
     /// # Examples
     ///
@@ -701,720 +1619,5048 @@ pub trait MrubyImpl {
     /// # use mrusty::MrubyImpl;
     /// let mruby = Mruby::new();
     ///
+    /// mruby.run("
+    ///   module App
+    ///     class Config
+    ///       NAME = 'mrusty'
+    ///     end
+    ///   end
+    /// ").unwrap();
+    ///
+    /// let config = mruby.const_get("App::Config").unwrap();
+    ///
+    /// assert_eq!(config.call("new", vec![]).unwrap().class().to_string(), "App::Config");
+    /// assert_eq!(mruby.const_get("App::Config::NAME").unwrap().to_str().unwrap(), "mrusty");
+    /// assert!(mruby.const_get("App::Nope::NAME").is_err());
+    /// ```
+    fn const_get(&self, path: &str) -> Result<Value, MrubyError>;
+
+    /// Defines an mruby method named `name`. The closure to be run when the `name` method is
+    /// called should be passed through the `mrfn!` macro.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::*;
+    ///
+    /// # fn main() {
+    /// let mruby = Mruby::new();
+    ///
     /// struct Cont {
     ///     value: i32
-    /// }
+    /// };
     ///
     /// mruby.def_class::<Cont>("Container");
+    /// mruby.def_method::<Cont, _>("initialize", mrfn!(|_mruby, slf: Value, v: i32| {
+    ///     let cont = Cont { value: v };
     ///
-    /// let none = mruby.option::<Cont>(None);
-    /// let some = mruby.option(Some(Cont { value: 3 }));
+    ///     slf.init(cont)
+    /// }));
+    /// mruby.def_method::<Cont, _>("value", mrfn!(|mruby, slf: Cont| {
+    ///     mruby.fixnum(slf.value)
+    /// }));
     ///
-    /// assert_eq!(none.call("nil?", vec![]).unwrap().to_bool().unwrap(), true);
-    /// assert_eq!(some.to_obj::<Cont>().unwrap().value, 3);
+    /// let result = mruby.run("Container.new(3).value").unwrap();
+    ///
+    /// assert_eq!(result.to_i32().unwrap(), 3);
+    /// # }
     /// ```
-    #[inline]
-    fn option<T: Any>(&self, obj: Option<T>) -> Value;
+    fn def_method<T: Any, F>(&self, name: &str,
+                             method: F) where F: Fn(MrubyType, Value) -> Value + 'static;
 
-    /// Creates mruby `Value` of `Class` `Array`.
+    /// Defines every `(name, method)` pair of `methods` as an mruby method on the mruby `Class`
+    /// of Rust type `T`, like `def_method`. Useful when method names and bodies are only known at
+    /// runtime.
     ///
     /// # Examples
+    ///
     /// ```
-    /// # use mrusty::Mruby;
-    /// # use mrusty::MrubyImpl;
+    /// # use mrusty::*;
+    /// # use std::rc::Rc;
     /// let mruby = Mruby::new();
     ///
-    /// let array = mruby.array(vec![
-    ///     mruby.fixnum(1),
-    ///     mruby.fixnum(2),
-    ///     mruby.fixnum(3)
-    /// ]);
+    /// struct Record;
     ///
-    /// assert_eq!(array.to_vec().unwrap(), vec![
-    ///     mruby.fixnum(1),
-    ///     mruby.fixnum(2),
-    ///     mruby.fixnum(3)
-    /// ]);
+    /// mruby.def_class::<Record>("Record");
+    ///
+    /// let fields = vec![("id", 7), ("size", 3)];
+    ///
+    /// let methods = fields.into_iter().map(|(name, value)| {
+    ///     let method: Rc<Fn(MrubyType, Value) -> Value> = Rc::new(move |mruby, _slf| {
+    ///         mruby.fixnum(value)
+    ///     });
+    ///
+    ///     (name.to_owned(), method)
+    /// }).collect();
+    ///
+    /// mruby.def_methods::<Record>(methods);
+    ///
+    /// let result = mruby.run("r = Record.new; r.id * r.size").unwrap();
+    ///
+    /// assert_eq!(result.to_i32().unwrap(), 21);
     /// ```
-    #[inline]
-    fn array(&self, value: Vec<Value>) -> Value;
-}
-
-impl MrubyImpl for MrubyType {
-    #[inline]
-    fn filename(&self, filename: &str) {
-        self.borrow_mut().filename = Some(filename.to_owned());
+    fn def_methods<T: Any>(&self, methods: Vec<(String, Rc<Fn(MrubyType, Value) -> Value>)>);
 
-        unsafe {
-            mrbc_filename(self.borrow().mrb, self.borrow().ctx,
-                          CString::new(filename).unwrap().as_ptr());
-        }
-    }
+    /// Defines an mruby method named `name` on the mruby `Class` reflecting type `T`, like
+    /// `def_method`, but for closures returning a `Result`. An `Err` is raised as an mruby
+    /// `Exception` whose `Class` depends on the error:
+    ///
+    /// * `Cast` raises `TypeError`
+    /// * `Undef` raises `NameError`
+    /// * `Runtime` and `RuntimeWithBacktrace` raise `RuntimeError`
+    /// * `Filetype` raises `ArgumentError`
+    /// * `Io` raises `RuntimeError`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::*;
+    /// let mruby = Mruby::new();
+    ///
+    /// struct Cont;
+    ///
+    /// mruby.def_class::<Cont>("Container");
+    /// mruby.def_method_result::<Cont, _>("double", |mruby, _slf| {
+    ///     let value = try!(mruby.run("$value")).to_i32();
+    ///
+    ///     Ok(mruby.fixnum(try!(value) * 2))
+    /// });
+    ///
+    /// let result = mruby.run("$value = 2; Container.new.double").unwrap();
+    ///
+    /// assert_eq!(result.to_i32().unwrap(), 4);
+    ///
+    /// let result = mruby.run("
+    ///   begin
+    ///     $value = 'a'
+    ///     Container.new.double
+    ///   rescue TypeError => e
+    ///     e.message
+    ///   end
+    /// ").unwrap();
+    ///
+    /// assert_eq!(result.to_str().unwrap(), "Cast error: expected Fixnum");
+    /// ```
+    fn def_method_result<T: Any, F>(&self, name: &str, method: F)
+        where F: Fn(MrubyType, Value) -> Result<Value, MrubyError> + 'static;
 
-    #[inline]
-    fn run(&self, script: &str) -> Result<Value, MrubyError> {
-        unsafe {
-            let (mrb, ctx) = {
-                let borrow = self.borrow();
+    /// Defines `coerce` on the mruby `Class` reflecting type `T` so that it can be the right
+    /// operand of `Fixnum` and `Float` addition and multiplication. `coerce` returns
+    /// `[self, other]`, which turns `2 * obj` into `obj * 2` and is thus only used for the
+    /// commutative `+` and `*`. `-` and `/` keep raising a `TypeError` for such operands.
+    ///
+    /// Since mruby numerics do not use `coerce` on their own, the first call also redefines `+`
+    /// and `*` on `Fixnum` and `Float` to fall back to `coerce` for non-`Numeric` arguments. This
+    /// affects every script run on the `Mruby`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::*;
+    ///
+    /// # fn main() {
+    /// let mruby = Mruby::new();
+    ///
+    /// struct Cont {
+    ///     value: f64
+    /// };
+    ///
+    /// mruby.def_class::<Cont>("Container");
+    /// mruby.def_method::<Cont, _>("initialize", mrfn!(|_mruby, slf: Value, v: f64| {
+    ///     slf.init(Cont { value: v })
+    /// }));
+    /// mruby.def_method::<Cont, _>("*", mrfn!(|mruby, slf: Cont, v: f64| {
+    ///     mruby.float(slf.value * v)
+    /// }));
+    ///
+    /// mruby.def_coerce::<Cont>();
+    ///
+    /// let result = mruby.run("2 * Container.new(1.5)").unwrap();
+    ///
+    /// assert_eq!(result.to_f64().unwrap(), 3.0);
+    /// # }
+    /// ```
+    fn def_coerce<T: Any>(&self);
 
-                (borrow.mrb, borrow.ctx)
-            };
+    /// Defines `<=>` on the mruby `Class` reflecting type `T` using `T`'s `Ord` implementation.
+    /// `<=>` returns `-1`, `0` or `1` and `nil` when the other operand is not a `T`, which is
+    /// what mruby's `Comparable` expects.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::*;
+    ///
+    /// # fn main() {
+    /// let mruby = Mruby::new();
+    ///
+    /// #[derive(PartialEq, Eq, PartialOrd, Ord)]
+    /// struct Version(i32, i32);
+    ///
+    /// mruby.def_class::<Version>("Version");
+    /// mruby.def_method::<Version, _>("initialize", mrfn!(|_mruby, slf: Value, major: i32,
+    ///                                                      minor: i32| {
+    ///     slf.init(Version(major, minor))
+    /// }));
+    ///
+    /// mruby.def_spaceship::<Version>();
+    /// mruby.include_module::<Version>("Comparable").unwrap();
+    ///
+    /// let result = mruby.run("Version.new(1, 10) > Version.new(1, 9)").unwrap();
+    ///
+    /// assert!(result.to_bool().unwrap());
+    /// assert!(mruby.run("Version.new(1, 0) <=> 1").unwrap().is_nil());
+    /// # }
+    /// ```
+    fn def_spaceship<T: Any + Ord>(&self);
 
-            let value = mrb_load_nstring_cxt(mrb, script.as_ptr(), script.len() as i32, ctx);
-            let exc = mrb_ext_get_exc(self.borrow().mrb);
+    /// Defines a private mruby method named `name` on the mruby `Class` reflecting type `T`, like
+    /// `def_method`. Calling it with a receiver other than `self`, including from Rust through
+    /// `call`, raises a `NoMethodError`.
+    ///
+    /// *Note:* mruby ignores `private`, so visibility is enforced when the method gets called.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::*;
+    ///
+    /// # fn main() {
+    /// let mruby = Mruby::new();
+    ///
+    /// struct Cont;
+    ///
+    /// mruby.def_class::<Cont>("Container");
+    /// mruby.def_private_method::<Cont, _>("secret", mrfn!(|mruby, _slf: Value| {
+    ///     mruby.fixnum(42)
+    /// }));
+    ///
+    /// let result = mruby.run("
+    ///   class Container
+    ///     def reveal
+    ///       secret
+    ///     end
+    ///   end
+    ///
+    ///   Container.new.reveal
+    /// ").unwrap();
+    ///
+    /// assert_eq!(result.to_i32().unwrap(), 42);
+    ///
+    /// let result = mruby.run("Container.new.secret");
+    ///
+    /// match result {
+    ///     Err(MrubyError::Runtime(err)) => {
+    ///         assert_eq!(err, "NoMethodError: private method 'secret' called for Container");
+    ///     },
+    ///     _ => assert!(false)
+    /// }
+    /// # }
+    /// ```
+    fn def_private_method<T: Any, F>(&self, name: &str, method: F)
+        where F: Fn(MrubyType, Value) -> Value + 'static;
 
-            match exc.typ {
-                MrType::MRB_TT_FALSE => {
-                    Ok(Value::new(self.clone(), value))
-                },
-                _ => Err(MrubyError::Runtime(exc.to_str(self.borrow().mrb).unwrap().to_owned()))
-            }
+    /// Defines a protected mruby method named `name` on the mruby `Class` reflecting type `T`, like
+    /// `def_method`. Calling it from outside of an instance of that mruby `Class` raises a
+    /// `NoMethodError`.
+    ///
+    /// *Note:* mruby ignores `protected`, so visibility is enforced when the method gets called.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::*;
+    ///
+    /// # fn main() {
+    /// let mruby = Mruby::new();
+    ///
+    /// struct Cont {
+    ///     value: i32
+    /// };
+    ///
+    /// mruby.def_class::<Cont>("Container");
+    /// mruby.def_method::<Cont, _>("initialize", mrfn!(|_mruby, slf: Value, v: i32| {
+    ///     slf.init(Cont { value: v })
+    /// }));
+    /// mruby.def_protected_method::<Cont, _>("value", mrfn!(|mruby, slf: Cont| {
+    ///     mruby.fixnum(slf.value)
+    /// }));
+    ///
+    /// let result = mruby.run("
+    ///   class Container
+    ///     def >(other)
+    ///       value > other.value
+    ///     end
+    ///   end
+    ///
+    ///   Container.new(2) > Container.new(1)
+    /// ").unwrap();
+    ///
+    /// assert!(result.to_bool().unwrap());
+    /// assert!(mruby.run("Container.new(1).value").is_err());
+    /// # }
+    /// ```
+    fn def_protected_method<T: Any, F>(&self, name: &str, method: F)
+        where F: Fn(MrubyType, Value) -> Value + 'static;
+
+    /// Defines an mruby class method named `name`. The closure to be run when the `name` method is
+    /// called should be passed through the `mrfn!` macro.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::*;
+    ///
+    /// # fn main() {
+    /// let mruby = Mruby::new();
+    ///
+    /// struct Cont;
+    ///
+    /// mruby.def_class::<Cont>("Container");
+    /// mruby.def_class_method::<Cont, _>("hi", mrfn!(|mruby, _slf: Value, v: i32| {
+    ///     mruby.fixnum(v)
+    /// }));
+    ///
+    /// let result = mruby.run("Container.hi 3").unwrap();
+    ///
+    /// assert_eq!(result.to_i32().unwrap(), 3);
+    /// # }
+    /// ```
+    fn def_class_method<T: Any, F>(&self, name: &str,
+                                   method: F) where F: Fn(MrubyType, Value) -> Value + 'static;
+
+    /// Undefines the instance method `name` of the mruby `Class` or `Module` `class`. Calling it
+    /// afterwards raises `NoMethodError`, even if an ancestor defines it. `class` can be a path
+    /// like `"A::B"`.
+    ///
+    /// Returns an `Err` if `class` is not defined or does not respond to `name`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.undef_method("Integer", "times").unwrap();
+    ///
+    /// assert!(mruby.run("3.times {}").is_err());
+    /// assert!(mruby.undef_method("Nope", "times").is_err());
+    /// assert!(mruby.undef_method("Integer", "nope").is_err());
+    /// ```
+    fn undef_method(&self, class: &str, name: &str) -> Result<(), MrubyError>;
+
+    /// Undefines the class method `name` of the mruby `Class` or `Module` `class`. `class` can
+    /// be a path like `"A::B"`.
+    ///
+    /// Returns an `Err` if `class` is not defined or does not respond to `name`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.undef_class_method("Struct", "new").unwrap();
+    ///
+    /// assert!(mruby.run("Struct.new(:a)").is_err());
+    /// assert!(mruby.undef_class_method("Nope", "new").is_err());
+    /// ```
+    fn undef_class_method(&self, class: &str, name: &str) -> Result<(), MrubyError>;
+
+    /// Defines the instance method `new_name` of the mruby `Class` or `Module` `class` as a copy
+    /// of `old_name`, like Ruby's `alias_method`. The alias keeps the original behavior even after
+    /// `old_name` is redefined. `class` can be a path like `"A::B"`. Methods defined in Rust,
+    /// including ones inherited from an ancestor, keep calling the same closure under both names.
+    ///
+    /// Returns an `Err` if `class` is not defined or does not respond to `old_name`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::*;
+    ///
+    /// # fn main() {
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.alias_method("String", "original_upcase", "upcase").unwrap();
+    ///
+    /// mruby.def_method_on("String", "upcase", mrfn!(|_mruby, slf: Value| {
+    ///     let upcased = slf.call("original_upcase", vec![]).unwrap();
+    ///
+    ///     upcased.call("+", vec![slf.call("size", vec![]).unwrap()
+    ///                               .call("to_s", vec![]).unwrap()]).unwrap()
+    /// })).unwrap();
+    ///
+    /// let result = mruby.run("'abc'.upcase").unwrap();
+    ///
+    /// assert_eq!(result.to_str().unwrap(), "ABC3");
+    /// assert!(mruby.alias_method("Nope", "a", "b").is_err());
+    /// assert!(mruby.alias_method("String", "a", "nope").is_err());
+    /// # }
+    /// ```
+    fn alias_method(&self, class: &str, new_name: &str, old_name: &str)
+        -> Result<(), MrubyError>;
+
+    /// Defines `new_name` as an alias of the method `existing` on the mruby `Class` of Rust type
+    /// `T`. Methods defined in Rust keep a single closure which is called under both names.
+    /// Returns an `Err` if `T` is not defined or `existing` is not a method of `T`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::*;
+    ///
+    /// # fn main() {
+    /// let mruby = Mruby::new();
+    ///
+    /// struct List {
+    ///     items: Vec<i32>
+    /// }
+    ///
+    /// mruby.def_class::<List>("List");
+    /// mruby.def_method::<List, _>("initialize", mrfn!(|_mruby, slf: Value| {
+    ///     slf.init(List { items: vec![1, 2, 3] })
+    /// }));
+    /// mruby.def_method::<List, _>("length", mrfn!(|mruby, slf: List| {
+    ///     mruby.fixnum(slf.items.len() as i32)
+    /// }));
+    ///
+    /// mruby.def_alias::<List>("size", "length").unwrap();
+    ///
+    /// assert_eq!(mruby.run("List.new.size").unwrap().to_i32().unwrap(), 3);
+    /// assert!(mruby.def_alias::<List>("count", "nope").is_err());
+    /// # }
+    /// ```
+    fn def_alias<T: Any>(&self, new_name: &str, existing: &str) -> Result<(), MrubyError>;
+
+    /// Runs the Ruby source `ruby_src` inside the body of the mruby `Class` of Rust type `T`,
+    /// which is handy for defining helper methods in Ruby on top of the ones defined in Rust.
+    /// Returns an `Err` if `T` is not defined or if `ruby_src` fails to run.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::*;
+    ///
+    /// # fn main() {
+    /// let mruby = Mruby::new();
+    ///
+    /// struct Temperature {
+    ///     celsius: f64
+    /// }
+    ///
+    /// mruby.def_class::<Temperature>("Temperature");
+    /// mruby.def_method::<Temperature, _>("initialize", mrfn!(|_mruby, slf: Value, c: f64| {
+    ///     slf.init(Temperature { celsius: c })
+    /// }));
+    /// mruby.def_method::<Temperature, _>("celsius", mrfn!(|mruby, slf: Temperature| {
+    ///     mruby.float(slf.celsius)
+    /// }));
+    ///
+    /// mruby.def_ruby_method::<Temperature>("
+    ///   def fahrenheit
+    ///     celsius * 9 / 5 + 32
+    ///   end
+    /// ").unwrap();
+    ///
+    /// let result = mruby.run("Temperature.new(100.0).fahrenheit").unwrap();
+    ///
+    /// assert_eq!(result.to_f64().unwrap(), 212.0);
+    /// # }
+    /// ```
+    fn def_ruby_method<T: Any>(&self, ruby_src: &str) -> Result<(), MrubyError>;
+
+    /// Returns the names of the instance methods of the mruby `Class` or `Module` `class`,
+    /// including inherited ones, like Ruby's `instance_methods`. Returns an empty `Vec` if
+    /// `class` is not defined.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.run("
+    ///   class Greeter
+    ///     def hello; end
+    ///   end
+    /// ").unwrap();
+    ///
+    /// let methods = mruby.class_methods("Greeter");
+    ///
+    /// assert!(methods.contains(&"hello".to_owned()));
+    /// assert!(methods.contains(&"to_s".to_owned()));
+    /// assert!(mruby.class_methods("Nope").is_empty());
+    /// ```
+    fn class_methods(&self, class: &str) -> Vec<String>;
+
+    /// Includes the mruby `Module` `module` into the mruby `Class` of Rust type `T`. Returns an
+    /// `Err` if either `T` or `module` is not defined.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::*;
+    ///
+    /// # fn main() {
+    /// let mruby = Mruby::new();
+    ///
+    /// struct Cont {
+    ///     value: i32
+    /// };
+    ///
+    /// mruby.def_class::<Cont>("Container");
+    /// mruby.def_method::<Cont, _>("<=>", mrfn!(|mruby, slf: Cont, other: Cont| {
+    ///     mruby.fixnum(slf.value - other.value)
+    /// }));
+    ///
+    /// mruby.include_module::<Cont>("Comparable").unwrap();
+    ///
+    /// let a = mruby.obj(Cont { value: 3 });
+    /// let b = mruby.obj(Cont { value: 2 });
+    ///
+    /// assert_eq!(a.call(">", vec![b]).unwrap().to_bool().unwrap(), true);
+    /// # }
+    /// ```
+    fn include_module<T: Any>(&self, module: &str) -> Result<(), MrubyError>;
+
+    /// Return the mruby name of a previously defined Rust type `T` with `def_class`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::*;
+    ///
+    /// let mruby = Mruby::new();
+    ///
+    /// struct Cont;
+    ///
+    /// mruby.def_class::<Cont>("Container");
+    ///
+    /// assert_eq!(mruby.class_name::<Cont>().unwrap(), "Container");
+    /// ```
+    fn class_name<T: Any>(&self) -> Result<String, MrubyError>;
+
+    /// Runs a full mruby garbage collection.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.run("100.times { |i| i.to_s }").unwrap();
+    ///
+    /// mruby.full_gc();
+    /// ```
+    #[inline]
+    fn full_gc(&self);
+
+    /// Runs a single step of mruby's incremental garbage collection.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.run("100.times { |i| i.to_s }").unwrap();
+    ///
+    /// mruby.incremental_gc();
+    /// ```
+    #[inline]
+    fn incremental_gc(&self);
+
+    /// Disables mruby garbage collection until `gc_enable` is called. Useful around
+    /// latency-sensitive sections.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.gc_disable();
+    ///
+    /// let result = mruby.run("GC.enable").unwrap();
+    ///
+    /// assert_eq!(result.to_bool().unwrap(), true);
+    /// ```
+    #[inline]
+    fn gc_disable(&self);
+
+    /// Enables mruby garbage collection after a `gc_disable`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.gc_disable();
+    /// mruby.gc_enable();
+    ///
+    /// let result = mruby.run("GC.enable").unwrap();
+    ///
+    /// assert_eq!(result.to_bool().unwrap(), false);
+    /// ```
+    #[inline]
+    fn gc_enable(&self);
+
+    /// Saves the mruby GC arena index. Objects created from Rust stay in the arena, and so
+    /// cannot be collected, until the index is restored with `gc_arena_restore`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let idx = mruby.gc_arena_save();
+    ///
+    /// mruby.string("temporary");
+    ///
+    /// mruby.gc_arena_restore(idx);
+    ///
+    /// assert_eq!(mruby.gc_arena_save(), idx);
+    /// ```
+    #[inline]
+    fn gc_arena_save(&self) -> i32;
+
+    /// Restores the mruby GC arena index to `idx`, saved with `gc_arena_save`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let idx = mruby.gc_arena_save();
+    ///
+    /// mruby.gc_arena_restore(idx);
+    /// ```
+    #[inline]
+    fn gc_arena_restore(&self, idx: i32);
+
+    /// Runs `f` and restores the mruby GC arena afterwards, freeing the temporary objects created
+    /// inside `f` for collection. `Value`s that outlive `f` need to be kept alive from mruby.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let idx = mruby.gc_arena_save();
+    ///
+    /// let len = mruby.with_gc_arena(|| {
+    ///     mruby.array(vec![mruby.fixnum(1), mruby.fixnum(2)]).to_vec().unwrap().len()
+    /// });
+    ///
+    /// assert_eq!(len, 2);
+    /// assert_eq!(mruby.gc_arena_save(), idx);
+    /// ```
+    fn with_gc_arena<F, R>(&self, f: F) -> R where F: FnOnce() -> R;
+
+    /// Returns a `GcStats` snapshot of the mruby GC: the live object count, the number of object
+    /// slots in the heap and the GC arena depth.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let stats = mruby.gc_stats();
+    ///
+    /// assert!(stats.live > 0);
+    /// assert!(stats.live <= stats.total);
+    /// assert_eq!(stats.arena_index, mruby.gc_arena_save() as usize);
+    /// ```
+    fn gc_stats(&self) -> GcStats;
+
+    /// Returns the top-level `self` (`main`) that scripts run with. Useful for reading instance
+    /// variables set at the top level of a script or for defining singleton methods on it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.run("@x = 5").unwrap();
+    ///
+    /// assert_eq!(mruby.top_self().get_var("x").unwrap().to_i32().unwrap(), 5);
+    /// assert_eq!(mruby.top_self().to_string(), "main");
+    /// ```
+    fn top_self(&self) -> Value;
+
+    /// Stores `data` as application context on this `Mruby`, replacing any previously set data.
+    /// Methods can then reach it through the `MrubyType` they receive with `user_data`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::*;
+    ///
+    /// # fn main() {
+    /// struct Host {
+    ///     greeting: String
+    /// }
+    ///
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.set_user_data(Host { greeting: "hello".to_owned() });
+    /// mruby.def_method_on("Object", "greet", mrfn!(|mruby, _slf: Value| {
+    ///     let host = mruby.user_data::<Host>().unwrap();
+    ///
+    ///     mruby.string(&host.greeting)
+    /// })).unwrap();
+    ///
+    /// assert_eq!(mruby.run("greet").unwrap().to_str().unwrap(), "hello");
+    /// # }
+    /// ```
+    fn set_user_data<U: Any>(&self, data: U);
+
+    /// Returns the application context set with `set_user_data`, or `None` if none is set or it
+    /// is not a `U`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert!(mruby.user_data::<u32>().is_none());
+    ///
+    /// mruby.set_user_data(3u32);
+    ///
+    /// assert_eq!(*mruby.user_data::<u32>().unwrap(), 3);
+    /// assert!(mruby.user_data::<String>().is_none());
+    /// ```
+    fn user_data<U: Any>(&self) -> Option<Rc<U>>;
+
+    /// Sets the global variable `name` to `value`. `name` can be passed either with or without
+    /// its leading `$`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.set_global("limit", mruby.fixnum(3));
+    ///
+    /// assert_eq!(mruby.run("$limit * 2").unwrap().to_i32().unwrap(), 6);
+    /// ```
+    fn set_global(&self, name: &str, value: Value);
+
+    /// Returns the value of the global variable `name`, or `nil` if it is not set. `name` can be
+    /// passed either with or without its leading `$`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.run("$result = 'done'").unwrap();
+    ///
+    /// assert_eq!(mruby.get_global("$result").to_str().unwrap(), "done");
+    /// assert!(mruby.get_global("unset").is_nil());
+    /// ```
+    fn get_global(&self, name: &str) -> Value;
+
+    /// Sets the program name `$0` and the `ARGV` constant seen by scripts. Like the arguments
+    /// of `std::env::args`, the first element of `args` is the program name and the rest end up
+    /// in `ARGV`. Calling `set_argv` again replaces both, while an empty `args` sets `$0` to
+    /// `nil` and clears `ARGV`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.set_argv(vec!["deploy", "staging", "--force"]);
+    ///
+    /// assert_eq!(mruby.run("$0").unwrap().to_str().unwrap(), "deploy");
+    /// assert_eq!(mruby.run("ARGV.first").unwrap().to_str().unwrap(), "staging");
+    ///
+    /// mruby.set_argv(vec![]);
+    ///
+    /// assert!(mruby.run("$0").unwrap().is_nil());
+    /// assert_eq!(mruby.run("ARGV.size").unwrap().to_i32().unwrap(), 0);
+    /// ```
+    fn set_argv(&self, args: Vec<&str>);
+
+    /// Creates mruby `Value` `nil`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// struct Cont;
+    ///
+    /// mruby.def_class::<Cont>("Container");
+    /// mruby.def_method::<Cont, _>("nil", |mruby, _slf| mruby.nil());
+    ///
+    /// let result = mruby.run("Container.new.nil.nil?").unwrap();
+    ///
+    /// assert_eq!(result.to_bool().unwrap(), true);
+    /// ```
+    #[inline]
+    fn nil(&self) -> Value;
+
+    /// Creates mruby `Value` containing `true` or `false`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let b = mruby.bool(true);
+    ///
+    /// assert_eq!(b.to_bool().unwrap(), true);
+    /// ```
+    #[inline]
+    fn bool(&self, value: bool) -> Value;
+
+    /// Creates mruby `Value` of `Class` `Fixnum`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let fixn = mruby.fixnum(2);
+    ///
+    /// assert_eq!(fixn.to_i32().unwrap(), 2);
+    /// ```
+    #[inline]
+    fn fixnum(&self, value: i32) -> Value;
+
+    /// Creates mruby `Value` of `Class` `Fixnum` from an `i64` in an `Ok` or an `Err` if `value`
+    /// does not fit in the `Fixnum` width libmruby was compiled with. (see `Mruby::int_bits`)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let fixn = mruby.int64(2).unwrap();
+    ///
+    /// assert_eq!(fixn.to_i64().unwrap(), 2);
+    ///
+    /// if Mruby::int_bits() < 64 {
+    ///     assert!(mruby.int64(1 << 40).is_err());
+    /// }
+    /// ```
+    #[inline]
+    fn int64(&self, value: i64) -> Result<Value, MrubyError>;
+
+    /// Creates mruby `Value` of `Class` `Float`. Infinities and `NaN` are kept as they are, like
+    /// Ruby's `Float::INFINITY` and `Float::NAN`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let fl = mruby.float(2.3);
+    ///
+    /// assert_eq!(fl.to_f64().unwrap(), 2.3);
+    /// ```
+    #[inline]
+    fn float(&self, value: f64) -> Value;
+
+    /// Creates mruby `Value` of `Class` `Float` from an `f32`, widening it to an `f64`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let fl = mruby.float32(2.5);
+    ///
+    /// assert_eq!(fl.to_f64().unwrap(), 2.5);
+    /// ```
+    #[inline]
+    fn float32(&self, value: f32) -> Value;
+
+    /// Creates mruby `Value` of `Class` `String`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let s = mruby.string("hi");
+    ///
+    /// assert_eq!(s.to_str().unwrap(), "hi");
+    /// ```
+    #[inline]
+    fn string(&self, value: &str) -> Value;
+
+    /// Creates mruby `Value` of `Class` `String` containing the single `char` `value`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let c = mruby.char('é');
+    ///
+    /// assert_eq!(c.to_str().unwrap(), "é");
+    /// ```
+    #[inline]
+    fn char(&self, value: char) -> Value;
+
+    /// Creates mruby `Value` of `Class` `String` from arbitrary, possibly non-UTF-8, bytes.
+    ///
+    /// # Examples
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let s = mruby.string_bytes(&[0, 0xff]);
+    ///
+    /// assert_eq!(s.to_bytes().unwrap(), &[0, 0xff]);
+    /// assert!(s.to_str().is_err());
+    /// ```
+    #[inline]
+    fn string_bytes(&self, value: &[u8]) -> Value;
+
+    /// Creates mruby `Value` of `Class` `Symbol`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let s = mruby.symbol("hi");
+    ///
+    /// assert_eq!(s.to_str().unwrap(), "hi");
+    /// ```
+    #[inline]
+    fn symbol(&self, value: &str) -> Value;
+
+    /// Creates mruby `Value` of `Class` `Time` from a `SystemTime`, like `Time.at`. mruby `Time`s
+    /// have a precision of one microsecond, so smaller fractions of a second are truncated.
+    ///
+    /// # Examples
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let mruby = Mruby::new();
+    ///
+    /// let time = mruby.time(UNIX_EPOCH + Duration::new(1_000_000_000, 250_000_000));
+    ///
+    /// assert_eq!(time.call("to_i", vec![]).unwrap().to_i32().unwrap(), 1_000_000_000);
+    /// assert_eq!(time.call("usec", vec![]).unwrap().to_i32().unwrap(), 250_000);
+    /// ```
+    fn time(&self, value: SystemTime) -> Value;
+
+    /// Interns `name` and returns its mruby symbol. Symbols live as long as the `Mruby` and
+    /// interning a name again returns the same symbol from mruby's symbol table.
+    ///
+    /// # Examples
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.sym("hi"), mruby.sym("hi"));
+    /// assert!(mruby.sym("hi") != mruby.sym("bye"));
+    /// ```
+    #[inline]
+    fn sym(&self, name: &str) -> u32;
+
+    /// Creates mruby `Value` of `Class` `name` containing a Rust object of type `T`.
+    ///
+    /// *Note:* `T` must be defined on the current `Mruby` with `def_class`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// struct Cont {
+    ///     value: i32
+    /// }
+    ///
+    /// mruby.def_class::<Cont>("Container");
+    ///
+    /// let value = mruby.obj(Cont { value: 3 });
+    /// ```
+    #[inline]
+    fn obj<T: Any>(&self, obj: T) -> Value;
+
+    /// Creates mruby `Value` of `Class` `name` containing a Rust `Option` of type `T`.
+    ///
+    /// *Note:* `T` must be defined on the current `Mruby` with `def_class`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// struct Cont {
+    ///     value: i32
+    /// }
+    ///
+    /// mruby.def_class::<Cont>("Container");
+    ///
+    /// let none = mruby.option::<Cont>(None);
+    /// let some = mruby.option(Some(Cont { value: 3 }));
+    ///
+    /// assert_eq!(none.call("nil?", vec![]).unwrap().to_bool().unwrap(), true);
+    /// assert_eq!(some.to_obj::<Cont>().unwrap().value, 3);
+    /// ```
+    #[inline]
+    fn option<T: Any>(&self, obj: Option<T>) -> Value;
+
+    /// Creates mruby `Value` of `Class` `String` from `Some` or `nil` from `None`. This is the
+    /// same as `ToValue` for `Option<&str>`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let nickname: Option<String> = None;
+    ///
+    /// assert_eq!(mruby.string_or_nil(Some("Ann")).to_str().unwrap(), "Ann");
+    /// assert!(mruby.string_or_nil(nickname.as_ref().map(|s| s.as_ref())).is_nil());
+    /// ```
+    #[inline]
+    fn string_or_nil(&self, value: Option<&str>) -> Value;
+
+    /// Creates mruby `Value` of the `Class` registered under `key` containing a Rust object of
+    /// type `T` stored as a `Rc<Any>`.
+    ///
+    /// *Note:* `key` must be defined on the current `Mruby` with `def_class_dyn`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.def_class_dyn("plugin", "Plugin");
+    ///
+    /// let value = mruby.obj_dyn("plugin", 3u8);
+    ///
+    /// assert_eq!(*value.to_obj_dyn::<u8>().unwrap(), 3);
+    /// assert!(value.to_obj_dyn::<i32>().is_err());
+    /// ```
+    fn obj_dyn<T: Any>(&self, key: &str, obj: T) -> Value;
+
+    /// Creates mruby `Value` of `Class` `Array`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let array = mruby.array(vec![
+    ///     mruby.fixnum(1),
+    ///     mruby.fixnum(2),
+    ///     mruby.fixnum(3)
+    /// ]);
+    ///
+    /// assert_eq!(array.to_vec().unwrap(), vec![
+    ///     mruby.fixnum(1),
+    ///     mruby.fixnum(2),
+    ///     mruby.fixnum(3)
+    /// ]);
+    /// ```
+    #[inline]
+    fn array(&self, value: Vec<Value>) -> Value;
+
+    /// Creates mruby `Value` of `Class` `Array` from `items`, converting each item with
+    /// `ToValue`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let array = mruby.array_of(vec![1, 2, 3]);
+    ///
+    /// assert_eq!(array.to_vec().unwrap(), vec![
+    ///     mruby.fixnum(1),
+    ///     mruby.fixnum(2),
+    ///     mruby.fixnum(3)
+    /// ]);
+    ///
+    /// let words = mruby.array_of("a b".split(' '));
+    ///
+    /// assert_eq!(words.to_vec().unwrap(), vec![mruby.string("a"), mruby.string("b")]);
+    /// ```
+    fn array_of<T: ToValue, I: IntoIterator<Item = T>>(&self, items: I) -> Value;
+
+    /// Creates mruby `Value` of `Class` `Hash`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let hash = mruby.hash(vec![
+    ///     (mruby.symbol("a"), mruby.fixnum(1)),
+    ///     (mruby.symbol("b"), mruby.fixnum(2))
+    /// ]);
+    ///
+    /// assert_eq!(hash.to_hash().unwrap(), vec![
+    ///     (mruby.symbol("a"), mruby.fixnum(1)),
+    ///     (mruby.symbol("b"), mruby.fixnum(2))
+    /// ]);
+    /// ```
+    #[inline]
+    fn hash(&self, value: Vec<(Value, Value)>) -> Value;
+
+    /// Creates mruby `Value` of `Class` `Range` from `from` to `to`, excluding `to` if
+    /// `exclusive` is `true`. Endpoints can be `nil`. Unlike `Range.new`, they are not checked
+    /// for being comparable.
+    ///
+    /// # Examples
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let range = mruby.range(mruby.fixnum(1), mruby.fixnum(4), true);
+    /// let sum = range.call("reduce", vec![mruby.symbol("+")]).unwrap();
+    ///
+    /// assert_eq!(sum.to_i32().unwrap(), 6);
+    /// ```
+    #[inline]
+    fn range(&self, from: Value, to: Value, exclusive: bool) -> Value;
+
+    /// Creates mruby `Value` from a `serde_json::Value`, building the corresponding `nil`,
+    /// `true`/`false`, `Fixnum`, `Float`, `String`, `Array` and `Hash` values. Integers that
+    /// overflow `Fixnum` become `Float`s.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate mrusty;
+    /// # extern crate serde_json;
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// use serde_json::Value as Json;
+    ///
+    /// # fn main() {
+    /// let mruby = Mruby::new();
+    ///
+    /// let json = Json::Object(vec![
+    ///     ("name".to_owned(), Json::String("mrusty".to_owned())),
+    ///     ("tags".to_owned(), Json::Array(vec![Json::U64(1), Json::Null]))
+    /// ].into_iter().collect());
+    ///
+    /// let value = mruby.from_json(&json);
+    ///
+    /// let result = value.call("[]", vec![mruby.string("tags")]).unwrap();
+    ///
+    /// assert_eq!(result.to_vec().unwrap(), vec![mruby.fixnum(1), mruby.nil()]);
+    /// # }
+    /// ```
+    #[cfg(feature = "serde")]
+    fn from_json(&self, json: &serde_json::Value) -> Value;
+
+    /// Creates mruby `Value` of `Class` `Proc` which runs the Rust closure `f` when called. The
+    /// closure receives all the arguments the `Proc` was called with. It can be used as a block
+    /// with `Value::call_with_block`.
+    ///
+    /// Any `panic!` call within the closure will get rescued in a `RustPanic` mruby `Exception`, or
+    /// in the class named by `MrubyOptions::panic_class`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let array = mruby.array(vec![mruby.fixnum(1), mruby.fixnum(2), mruby.fixnum(3)]);
+    /// let add = mruby.proc_from(|mruby, args| {
+    ///     mruby.fixnum(args[0].to_i32().unwrap() + args[1].to_i32().unwrap())
+    /// });
+    ///
+    /// let result = array.call_with_block("inject", vec![], add).unwrap();
+    ///
+    /// assert_eq!(result.to_i32().unwrap(), 6);
+    /// ```
+    fn proc_from<F>(&self, f: F) -> Value where F: Fn(MrubyType, Vec<Value>) -> Value + 'static;
+}
+
+impl MrubyImpl for MrubyType {
+    #[inline]
+    fn filename(&self, filename: &str) {
+        self.borrow_mut().filename = Some(filename.to_owned());
+
+        unsafe {
+            mrbc_filename(self.borrow().mrb, self.borrow().ctx,
+                          CString::new(filename).unwrap().as_ptr());
+        }
+    }
+
+    #[inline]
+    fn run(&self, script: &str) -> Result<Value, MrubyError> {
+        unsafe {
+            let (mrb, ctx) = {
+                let borrow = self.borrow();
+
+                (borrow.mrb, borrow.ctx)
+            };
+
+            reset_instructions(self);
+
+            let value = mrb_ext_load_nstring_cxt(mrb, script.as_ptr(), script.len(), ctx);
+            match get_exc(mrb) {
+                Some(err) => Err(err),
+                None      => Ok(Value::new(self.clone(), value))
+            }
+        }
+    }
+
+    fn run_named(&self, filename: &str, script: &str) -> Result<Value, MrubyError> {
+        let previous = self.borrow().filename.clone();
+
+        self.filename(filename);
+
+        let result = self.run(script);
+
+        match previous {
+            Some(previous) => self.filename(&previous),
+            None           => {
+                self.borrow_mut().filename = None;
+
+                unsafe {
+                    mrb_ext_clear_filename(self.borrow().ctx);
+                }
+            }
+        }
+
+        result
+    }
+
+    fn run_all(&self, scripts: &[&str]) -> Result<Vec<Value>, (usize, MrubyError)> {
+        let mut results = Vec::with_capacity(scripts.len());
+
+        for (i, script) in scripts.iter().enumerate() {
+            match self.run(script) {
+                Ok(value) => results.push(value),
+                Err(err)  => return Err((i, err))
+            }
+        }
+
+        Ok(results)
+    }
+
+    fn run_capturing(&self, script: &str) -> Result<(Value, String), MrubyError> {
+        // print, puts and p all write through Kernel#__printstr__, which is swapped while
+        // capturing.
+        if self.borrow().captures.is_empty() {
+            self.run_unchecked("
+              module Kernel
+                alias __mrusty_printstr__ __printstr__
+              end
+            ");
+
+            self.def_method_on("Kernel", "__printstr__", |mruby, slf| {
+                let string = unsafe {
+                    let string = MrValue::nil();
+
+                    mrb_get_args(mruby.borrow().mrb, CString::new("o").unwrap().as_ptr(),
+                                 &string as *const MrValue);
+
+                    Value::new(mruby.clone(), string)
+                };
+
+                if mruby.borrow().captures.is_empty() {
+                    return slf.call("__mrusty_printstr__", vec![string])
+                              .unwrap_or_else(|_| mruby.nil());
+                }
+
+                if let Ok(bytes) = string.to_bytes() {
+                    let text = String::from_utf8_lossy(bytes);
+
+                    mruby.borrow_mut().captures.last_mut().unwrap().push_str(&text);
+                }
+
+                string
+            }).unwrap();
+        }
+
+        self.borrow_mut().captures.push(String::new());
+
+        let result = self.run(script);
+
+        let output = self.borrow_mut().captures.pop().unwrap();
+
+        if self.borrow().captures.is_empty() {
+            self.run_unchecked("
+              module Kernel
+                alias __printstr__ __mrusty_printstr__
+                remove_method :__mrusty_printstr__
+              end
+            ");
+        }
+
+        result.map(|value| (value, output))
+    }
+
+    #[inline]
+    fn run_unchecked(&self, script: &str) -> Value {
+        unsafe {
+            let (mrb, ctx) = {
+                let borrow = self.borrow();
+
+                (borrow.mrb, borrow.ctx)
+            };
+
+            let value = mrb_load_nstring_cxt(mrb, script.as_ptr(), script.len() as i32, ctx);
+
+            Value::new(self.clone(), value)
+        }
+    }
+
+    fn run_with_binding(&self, script: &str,
+                        vars: Vec<(&str, Value)>) -> Result<Value, MrubyError> {
+        for &(name, _) in vars.iter() {
+            let mut chars = name.chars();
+
+            let valid = match chars.next() {
+                Some(c) => c == '_' || c.is_lowercase(),
+                None    => false
+            } && chars.all(|c| c == '_' || c.is_alphanumeric());
+
+            if !valid {
+                return Err(MrubyError::Runtime(format!("invalid local variable name {}", name)))
+            }
+        }
+
+        let names: Vec<&str> = vars.iter().map(|&(name, _)| name).collect();
+
+        // Keeps the script on its original lines so that error messages stay accurate.
+        let lambda = try!(self.run(&format!("lambda {{ |{}| {}\n}}", names.join(", "), script)));
+
+        lambda.call("call", vars.into_iter().map(|(_, value)| value).collect())
+    }
+
+    fn set_instruction_limit(&self, max: u64) {
+        {
+            let mut borrow = self.borrow_mut();
+
+            borrow.instructions = Some(max);
+            borrow.executed.set(0);
+        }
+
+        set_code_fetch_hook(self);
+    }
+
+    fn run_with_timeout(&self, script: &str, dur: Duration) -> Result<Value, MrubyError> {
+        let flag = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel::<()>();
+
+        let watchdog = {
+            let flag = flag.clone();
+
+            // The watchdog never touches the VM; it only flips the flag checked by the hook.
+            thread::spawn(move || {
+                if let Err(RecvTimeoutError::Timeout) = receiver.recv_timeout(dur) {
+                    flag.store(true, Ordering::Relaxed);
+                }
+            })
+        };
+
+        let previous = mem::replace(&mut self.borrow_mut().interrupt, Some(flag));
+
+        set_code_fetch_hook(self);
+
+        let result = self.run(script);
+
+        self.borrow_mut().interrupt = previous;
+
+        set_code_fetch_hook(self);
+
+        drop(sender);
+        watchdog.join().unwrap();
+
+        result
+    }
+
+    #[inline]
+    fn runb(&self, script: &[u8]) -> Result<Value, MrubyError> {
+        unsafe {
+            let (mrb, ctx) = {
+                let borrow = self.borrow();
+
+                (borrow.mrb, borrow.ctx)
+            };
+
+            reset_instructions(self);
+
+            let value = mrb_load_irep_cxt(mrb, script.as_ptr(), ctx);
+            match get_exc(mrb) {
+                Some(err) => Err(err),
+                None      => Ok(Value::new(self.clone(), value))
+            }
+        }
+    }
+
+    fn compile(&self, script: &str) -> Result<Vec<u8>, MrubyError> {
+        unsafe {
+            let (mrb, ctx) = {
+                let borrow = self.borrow();
+
+                (borrow.mrb, borrow.ctx)
+            };
+
+            let mut bin = mem::uninitialized::<*mut u8>();
+            let mut bin_size = 0;
+
+            let result = mrb_ext_compile(mrb, ctx, script.as_ptr(), script.len(), &mut bin,
+                                         &mut bin_size);
+
+            if result == 0 {
+                let bytes = slice::from_raw_parts(bin, bin_size).to_vec();
+
+                mrb_free(mrb, bin);
+
+                Ok(bytes)
+            } else {
+                match get_exc(mrb) {
+                    Some(err) => Err(err),
+                    None      => Err(MrubyError::Runtime("cannot compile script".to_owned()))
+                }
+            }
+        }
+    }
+
+    fn compile_proc(&self, script: &str) -> Result<CompiledScript, MrubyError> {
+        unsafe {
+            let (mrb, filename) = {
+                let borrow = self.borrow();
+
+                (borrow.mrb, borrow.filename.clone())
+            };
+
+            let filename = filename.map(|filename| CString::new(filename).unwrap());
+            let filename_ptr = match filename {
+                Some(ref filename) => filename.as_ptr(),
+                None               => ptr::null()
+            };
+
+            let value = mrb_ext_compile_proc(mrb, filename_ptr, script.as_ptr(), script.len());
+
+            match get_exc(mrb) {
+                Some(err) => Err(err),
+                None      => {
+                    mrb_gc_register(mrb, value);
+
+                    Ok(CompiledScript {
+                        mruby: self.clone(),
+                        value: value
+                    })
+                }
+            }
+        }
+    }
+
+    fn parse_state(&self, script: &str) -> ParseStatus {
+        unsafe {
+            let mrb = self.borrow().mrb;
+
+            let mut message = MrValue::nil();
+            let mut line = 0;
+
+            match mrb_ext_parse_state(mrb, script.as_ptr(), script.len(), &mut message, &mut line) {
+                0 => ParseStatus::Complete,
+                1 => ParseStatus::Incomplete,
+                _ => {
+                    ParseStatus::Error {
+                        message: message.to_str(mrb).unwrap().to_owned(),
+                        line:    line as u32
+                    }
+                }
+            }
+        }
+    }
+
+    fn run_compiled(&self, script: &CompiledScript) -> Result<Value, MrubyError> {
+        let mrb = self.borrow().mrb;
+
+        if script.mruby.borrow().mrb != mrb {
+            return Err(MrubyError::Runtime("script was compiled on another Mruby".to_owned()));
+        }
+
+        reset_instructions(self);
+
+        unsafe {
+            let value = mrb_ext_run_proc(mrb, script.value);
+
+            match get_exc(mrb) {
+                Some(err) => Err(err),
+                None      => Ok(Value::new(self.clone(), value))
+            }
+        }
+    }
+
+    #[inline]
+    fn execute(&self, script: &Path) -> Result<Value, MrubyError> {
+        match script.extension() {
+            Some(ext) => {
+                self.filename(script.file_name().unwrap().to_str().unwrap());
+
+                let mut file = try!(File::open(script));
+
+                // Tracks executing files so that require_relative resolves nested requires.
+                self.borrow_mut().file_stack.push(script.to_path_buf());
+
+                let result = match ext.to_str().unwrap() {
+                    "rb" => {
+                        let mut script = String::new();
+
+                        match file.read_to_string(&mut script) {
+                            Ok(_)    => self.run(&script),
+                            Err(err) => Err(MrubyError::from(err))
+                        }
+                    },
+                    "mrb" => {
+                        let mut script = Vec::new();
+
+                        match file.read_to_end(&mut script) {
+                            Ok(_)    => self.runb(&script),
+                            Err(err) => Err(MrubyError::from(err))
+                        }
+                    },
+                    _ => {
+                        Err(MrubyError::Filetype)
+                    }
+                };
+
+                self.borrow_mut().file_stack.pop();
+
+                result
+            },
+            None => Err(MrubyError::Filetype)
+        }
+    }
+
+    fn load_file(&self, path: &Path) -> Result<Value, MrubyError> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("rb") => {
+                try!(File::open(path));
+
+                self.filename(path.file_name().unwrap().to_str().unwrap());
+
+                let c_path = CString::new(path.to_str().unwrap()).unwrap();
+
+                // Tracks executing files so that require_relative resolves nested requires.
+                self.borrow_mut().file_stack.push(path.to_path_buf());
+
+                let result = unsafe {
+                    let (mrb, ctx) = {
+                        let borrow = self.borrow();
+
+                        (borrow.mrb, borrow.ctx)
+                    };
+
+                    reset_instructions(self);
+
+                    let value = mrb_ext_load_file_cxt(mrb, c_path.as_ptr(), ctx);
+
+                    match get_exc(mrb) {
+                        Some(err) => Err(err),
+                        None      => Ok(Value::new(self.clone(), value))
+                    }
+                };
+
+                self.borrow_mut().file_stack.pop();
+
+                result
+            },
+            _ => self.execute(path)
+        }
+    }
+
+    #[inline]
+    fn raise(&self, eclass: &str, message: &str) -> Value {
+        unsafe {
+            // mrb_ext_raise can jump out of this frame, so the borrow must not outlive this line.
+            let mrb = self.borrow().mrb;
+
+            mrb_ext_raise(mrb, CString::new(eclass).unwrap().as_ptr(),
+                          CString::new(message).unwrap().as_ptr());
+
+            self.nil()
+        }
+    }
+
+    #[inline]
+    fn def_file<T: MrubyFile>(&self, name: &str) {
+        let mut borrow = self.borrow_mut();
+
+        if borrow.files.contains_key(name) {
+            let mut file = borrow.files.get_mut(name).unwrap();
+
+            file.push(T::require);
+        } else {
+            borrow.files.insert(name.to_owned(), vec![T::require]);
+        }
+    }
+
+    #[inline]
+    fn add_load_path(&self, dir: &Path) {
+        self.borrow_mut().load_paths.push(dir.to_path_buf());
+    }
+
+    fn def_class<T: Any>(&self, name: &str) {
+        if self.borrow().classes.contains_key(&TypeId::of::<T>()) {
+            return;
+        }
+
+        unsafe {
+            let c_name = CString::new(name).unwrap();
+            let object = CString::new("Object").unwrap();
+            let object = mrb_class_get(self.borrow().mrb, object.as_ptr());
+
+            let class = mrb_define_class(self.borrow().mrb, c_name.as_ptr(), object);
+
+            register_class::<T>(self, class, name.to_owned());
+        }
+    }
+
+    fn def_class_with_super<T: Any>(&self, name: &str,
+                                    superclass: &str) -> Result<(), MrubyError> {
+        unsafe {
+            let mrb = self.borrow().mrb;
+
+            let sup = mrb_ext_class_path(mrb, CString::new(superclass).unwrap().as_ptr());
+
+            if sup.is_null() || mrb_ext_class_value(sup).typ != MrType::MRB_TT_CLASS {
+                return Err(MrubyError::Undef)
+            }
+
+            if self.borrow().classes.contains_key(&TypeId::of::<T>()) {
+                return Ok(());
+            }
+
+            let class = mrb_define_class(mrb, CString::new(name).unwrap().as_ptr(), sup);
+
+            register_class::<T>(self, class, name.to_owned());
+        }
+
+        Ok(())
+    }
+
+    fn def_class_with_drop<T: Any, D>(&self, name: &str, on_free: D)
+        where D: Fn(&mut T) + 'static {
+        self.def_class::<T>(name);
+
+        let on_free: Box<Fn(&mut T)> = Box::new(on_free);
+
+        self.borrow_mut().drops.insert(TypeId::of::<T>(), Rc::new(on_free));
+    }
+
+    fn def_class_cell<T: Any>(&self, name: &str) {
+        self.def_class::<RefCell<T>>(name);
+    }
+
+    fn def_class_dyn(&self, key: &str, name: &str) {
+        if self.borrow().dyn_classes.contains_key(key) {
+            return;
+        }
+
+        unsafe {
+            let mrb = self.borrow().mrb;
+
+            let object = mrb_class_get(mrb, CString::new("Object").unwrap().as_ptr());
+            let class = mrb_define_class(mrb, CString::new(name).unwrap().as_ptr(), object);
+
+            mrb_ext_set_instance_tt(class, MrType::MRB_TT_DATA);
+
+            let c_name = CString::new(name).unwrap();
+            let data_type = MrDataType { name: c_name.as_ptr(), free: free::<Rc<Any>> };
+
+            self.borrow_mut().dyn_classes.insert(key.to_owned(),
+                                                 (class, data_type, name.to_owned()));
+        }
+
+        self.def_method_on(name, "dup", |_mruby, slf| {
+            slf.clone()
+        }).unwrap();
+    }
+
+    fn def_class_under<T: Any>(&self, module: &str, name: &str) {
+        if self.borrow().classes.contains_key(&TypeId::of::<T>()) {
+            return;
+        }
+
+        unsafe {
+            let mrb = self.borrow().mrb;
+
+            let outer = module_path(self, module);
+            let object = mrb_class_get(mrb, CString::new("Object").unwrap().as_ptr());
+
+            let class = mrb_define_class_under(mrb, outer, CString::new(name).unwrap().as_ptr(),
+                                               object);
+
+            register_class::<T>(self, class, module.to_owned() + "::" + name);
+        }
+    }
+
+    fn def_module(&self, name: &str) {
+        module_path(self, name);
+    }
+
+    fn def_module_function<F>(&self, module: &str, name: &str, method: F)
+        where F: Fn(MrubyType, Value) -> Value + 'static {
+        let class = unsafe {
+            mrb_ext_class_path(self.borrow().mrb, CString::new(module).unwrap().as_ptr())
+        };
+
+        if class.is_null() {
+            panic!("Module not found.");
+        }
+
+        {
+            let (module_sym, sym) = unsafe {
+                let mrb = self.borrow().mrb;
+
+                (mrb_intern(mrb, module.as_ptr(), module.len()),
+                 mrb_intern(mrb, name.as_ptr(), name.len()))
+            };
+
+            let mut borrow = self.borrow_mut();
+
+            let methods = borrow.modules.entry(module_sym).or_insert_with(|| HashMap::new());
+
+            methods.insert(sym, Rc::new(method));
+        }
+
+        extern "C" fn call_module_function(mrb: *const MrState, slf: MrValue) -> MrValue {
+            unsafe {
+                let ptr = mrb_ext_get_ud(mrb);
+                let mruby = mem::transmute::<*const u8, MrubyType>(ptr);
+
+                let result = {
+                    let value = Value::new(mruby.clone(), slf);
+
+                    let method = {
+                        // Instance methods are found through the including Class, while
+                        // singleton methods are called on the Module itself.
+                        let class = mrb_ext_class_value(mrb_ext_get_target_class(mrb));
+                        let names = [CStr::from_ptr(mrb_ext_class_name(mrb, class)).to_bytes(),
+                                     CStr::from_ptr(mrb_ext_class_name(mrb, slf)).to_bytes()];
+                        let syms: Vec<u32> = names.iter().map(|name| {
+                            mrb_intern(mrb, name.as_ptr(), name.len())
+                        }).collect();
+
+                        let borrow = mruby.borrow();
+
+                        let sym = mrb_ext_get_mid(mrb);
+
+                        match syms.iter().filter_map(|sym| borrow.modules.get(sym)).next() {
+                            Some(methods) => methods.get(&sym).cloned().ok_or("Method not found."),
+                            None          => Err("Module not found.")
+                        }
+                    };
+
+                    // The borrow has to be released before raising, which does not return.
+                    let method = match method {
+                        Ok(method)   => method,
+                        Err(message) => return mruby.raise("TypeError", message).value
+                    };
+
+                    recover_panic(&mruby, || method(mruby.clone(), value).value)
+                };
+
+                mem::forget(mruby);
+
+                result
+            }
+        }
+
+        unsafe {
+            mrb_define_module_function(self.borrow().mrb, class,
+                                       CString::new(name).unwrap().as_ptr(),
+                                       call_module_function, 1 << 12);
+        }
+    }
+
+    fn def_method_on<F>(&self, class: &str, name: &str, method: F) -> Result<(), MrubyError>
+        where F: Fn(MrubyType, Value) -> Value + 'static {
+        let mrb = self.borrow().mrb;
+
+        let class = unsafe { mrb_ext_class_path(mrb, CString::new(class).unwrap().as_ptr()) };
+
+        if class.is_null() {
+            return Err(MrubyError::Undef)
+        }
+
+        {
+            let class_sym = unsafe {
+                let name = CStr::from_ptr(mrb_ext_class_name(mrb, mrb_ext_class_value(class)))
+                                .to_bytes();
+
+                mrb_intern(mrb, name.as_ptr(), name.len())
+            };
+            let sym = self.sym(name);
+
+            let mut borrow = self.borrow_mut();
+
+            let methods = borrow.named_methods.entry(class_sym)
+                                              .or_insert_with(|| HashMap::new());
+
+            methods.insert(sym, Rc::new(method));
+        }
+
+        extern "C" fn call_method_named(mrb: *const MrState, slf: MrValue) -> MrValue {
+            unsafe {
+                let ptr = mrb_ext_get_ud(mrb);
+                let mruby = mem::transmute::<*const u8, MrubyType>(ptr);
+
+                let result = {
+                    let value = Value::new(mruby.clone(), slf);
+
+                    let method = {
+                        let class = mrb_ext_get_target_class(mrb);
+                        let name = CStr::from_ptr(mrb_ext_class_name(mrb,
+                                                                     mrb_ext_class_value(class)))
+                                        .to_bytes();
+                        let class_sym = mrb_intern(mrb, name.as_ptr(), name.len());
+
+                        let borrow = mruby.borrow();
+
+                        let sym = mrb_ext_get_mid(mrb);
+
+                        match borrow.named_methods.get(&class_sym) {
+                            Some(methods) => methods.get(&sym).cloned().ok_or("Method not found."),
+                            None          => Err("Class not found.")
+                        }
+                    };
+
+                    // The borrow has to be released before raising, which does not return.
+                    let method = match method {
+                        Ok(method)   => method,
+                        Err(message) => return mruby.raise("TypeError", message).value
+                    };
+
+                    recover_panic(&mruby, || method(mruby.clone(), value).value)
+                };
+
+                mem::forget(mruby);
+
+                result
+            }
+        }
+
+        unsafe {
+            mrb_define_method(mrb, class, CString::new(name).unwrap().as_ptr(), call_method_named,
+                              1 << 12);
+        }
+
+        Ok(())
+    }
+
+    fn def_const(&self, class: &str, name: &str, value: Value) -> Result<(), MrubyError> {
+        match name.chars().next() {
+            Some(c) if c.is_uppercase() => (),
+            _ => {
+                return Err(MrubyError::Runtime(format!("constant name {} needs to start with \
+                                                        an uppercase letter", name)))
+            }
+        }
+
+        unsafe {
+            let mrb = self.borrow().mrb;
+
+            let class = mrb_ext_class_path(mrb, CString::new(class).unwrap().as_ptr());
+
+            if class.is_null() {
+                return Err(MrubyError::Undef)
+            }
+
+            mrb_define_const(mrb, class, CString::new(name).unwrap().as_ptr(), value.value);
+        }
+
+        Ok(())
+    }
+
+    fn const_get(&self, path: &str) -> Result<Value, MrubyError> {
+        let (namespace, name) = match path.rfind("::") {
+            Some(i) => (&path[..i], &path[i + 2..]),
+            None    => ("", path)
+        };
+
+        unsafe {
+            let mrb = self.borrow().mrb;
+
+            let class = mrb_ext_class_path(mrb, CString::new(namespace).unwrap().as_ptr());
+
+            if class.is_null() {
+                return Err(MrubyError::Undef)
+            }
+
+            let class = mrb_ext_class_value(class);
+            let sym = mrb_intern(mrb, name.as_ptr(), name.len());
+
+            if mrb_const_defined(mrb, class, sym) {
+                Ok(Value::new(self.clone(), mrb_const_get(mrb, class, sym)))
+            } else {
+                Err(MrubyError::Undef)
+            }
+        }
+    }
+
+    fn def_method<T: Any, F>(&self, name: &str,
+                             method: F) where F: Fn(MrubyType, Value) -> Value + 'static {
+        {
+            let sym = self.sym(name);
+
+            let mut borrow = self.borrow_mut();
+
+            let methods = match borrow.methods.get_mut(&TypeId::of::<T>()) {
+                Some(methods) => methods,
+                None          => panic!("Class not found.")
+            };
+
+            methods.insert(sym, Rc::new(method));
+        }
+
+        extern "C" fn call_method<T: Any>(mrb: *const MrState, slf: MrValue) -> MrValue {
+            unsafe {
+                let ptr = mrb_ext_get_ud(mrb);
+                let mruby = mem::transmute::<*const u8, MrubyType>(ptr);
+
+                let result = {
+                    let value = Value::new(mruby.clone(), slf);
+
+                    let method = {
+                        let borrow = mruby.borrow();
+
+                        let sym = mrb_ext_get_mid(mrb);
+
+                        match borrow.methods.get(&TypeId::of::<T>()) {
+                            Some(methods) => methods.get(&sym).cloned().ok_or("Method not found."),
+                            None          => Err("Class not found.")
+                        }
+                    };
+
+                    // The borrow has to be released before raising, which does not return.
+                    let method = match method {
+                        Ok(method)   => method,
+                        Err(message) => return mruby.raise("TypeError", message).value
+                    };
+
+                    recover_panic(&mruby, || method(mruby.clone(), value).value)
+                };
+
+                mem::forget(mruby);
+
+                result
+            }
+        }
+
+        let borrow = self.borrow();
+
+        let class = match borrow.classes.get(&TypeId::of::<T>()) {
+            Some(class) => class,
+            None       => panic!("Class not found.")
+        };
+
+        unsafe {
+            mrb_define_method(borrow.mrb, class.0, CString::new(name).unwrap().as_ptr(),
+                              call_method::<T>, 1 << 12);
+        }
+    }
+
+    fn def_methods<T: Any>(&self, methods: Vec<(String, Rc<Fn(MrubyType, Value) -> Value>)>) {
+        for (name, method) in methods {
+            self.def_method::<T, _>(&name, move |mruby, slf| method(mruby, slf));
+        }
+    }
+
+    fn def_method_result<T: Any, F>(&self, name: &str, method: F)
+        where F: Fn(MrubyType, Value) -> Result<Value, MrubyError> + 'static {
+        self.def_method::<T, _>(name, move |mruby, slf| {
+            match method(mruby.clone(), slf) {
+                Ok(value) => value,
+                Err(err)  => {
+                    let message = match err {
+                        MrubyError::Runtime(ref message)                     => message.clone(),
+                        MrubyError::Frozen(ref message)                      => message.clone(),
+                        MrubyError::Panic(ref message)                       => message.clone(),
+                        MrubyError::Resource(ref message)                    => message.clone(),
+                        MrubyError::RuntimeWithBacktrace { ref message, .. } => message.clone(),
+                        ref err                                              => format!("{}", err)
+                    };
+
+                    let class = match err {
+                        MrubyError::Panic(_) => mruby.borrow().panic_class.clone(),
+                        ref err              => exception_class(err).to_owned()
+                    };
+
+                    mruby.raise(&class, &message)
+                }
+            }
+        });
+    }
+
+    fn def_coerce<T: Any>(&self) {
+        let class = match self.class_name::<T>() {
+            Ok(class) => class,
+            Err(_)    => panic!("Class not found.")
+        };
+
+        self.run_unchecked(&format!("
+          {}.class_eval do
+            def coerce(other)
+              [self, other]
+            end
+          end
+
+          [Fixnum, Float].each do |klass|
+            klass.class_eval do
+              {{ :+ => :__coerce_add, :* => :__coerce_mul }}.each do |op, original|
+                next if method_defined?(original)
+
+                alias_method original, op
+
+                define_method(op) do |other|
+                  if !other.is_a?(Numeric) && other.respond_to?(:coerce)
+                    left, right = other.coerce(self)
+
+                    left.__send__(op, right)
+                  else
+                    __send__(original, other)
+                  end
+                end
+              end
+            end
+          end
+        ", class));
+    }
+
+    fn def_spaceship<T: Any + Ord>(&self) {
+        self.def_method::<T, _>("<=>", |mruby, slf| {
+            let other = unsafe {
+                let other = MrValue::nil();
+
+                mrb_get_args(mruby.borrow().mrb, CString::new("o").unwrap().as_ptr(),
+                             &other as *const MrValue);
+
+                Value::new(mruby.clone(), other)
+            };
+
+            match (slf.to_obj::<T>(), other.to_obj::<T>()) {
+                (Ok(slf), Ok(other)) => match slf.cmp(&other) {
+                    cmp::Ordering::Less    => mruby.fixnum(-1),
+                    cmp::Ordering::Equal   => mruby.fixnum(0),
+                    cmp::Ordering::Greater => mruby.fixnum(1)
+                },
+                _ => mruby.nil()
+            }
+        });
+    }
+
+    fn def_private_method<T: Any, F>(&self, name: &str, method: F)
+        where F: Fn(MrubyType, Value) -> Value + 'static {
+        let method_name = name.to_owned();
+
+        self.def_method::<T, _>(name, move |mruby, slf| {
+            let private = unsafe {
+                let mrb = mruby.borrow().mrb;
+
+                !mrb_obj_eq(mrb, mrb_ext_get_caller_self(mrb), slf.value)
+            };
+
+            if private {
+                let message = format!("private method '{}' called for {}", method_name,
+                                      slf.type_name());
+
+                return mruby.raise("NoMethodError", &message);
+            }
+
+            method(mruby, slf)
+        });
+    }
+
+    fn def_protected_method<T: Any, F>(&self, name: &str, method: F)
+        where F: Fn(MrubyType, Value) -> Value + 'static {
+        let class = match self.borrow().classes.get(&TypeId::of::<T>()) {
+            Some(class) => class.0,
+            None        => panic!("Class not found.")
+        };
+
+        let method_name = name.to_owned();
+
+        self.def_method::<T, _>(name, move |mruby, slf| {
+            let protected = unsafe {
+                let mrb = mruby.borrow().mrb;
+
+                !mrb_obj_is_kind_of(mrb, mrb_ext_get_caller_self(mrb), class)
+            };
+
+            if protected {
+                let message = format!("protected method '{}' called for {}", method_name,
+                                      slf.type_name());
+
+                return mruby.raise("NoMethodError", &message);
+            }
+
+            method(mruby, slf)
+        });
+    }
+
+    fn def_class_method<T: Any, F>(&self, name: &str, method: F)
+        where F: Fn(MrubyType, Value) -> Value + 'static {
+        {
+            let sym = self.sym(name);
+
+            let mut borrow = self.borrow_mut();
+
+            let methods = match borrow.class_methods.get_mut(&TypeId::of::<T>()) {
+                Some(methods) => methods,
+                None          => panic!("Class not found.")
+            };
+
+            methods.insert(sym, Rc::new(method));
+        }
+
+        extern "C" fn call_class_method<T: Any>(mrb: *const MrState, slf: MrValue) -> MrValue {
+            unsafe {
+                let ptr = mrb_ext_get_ud(mrb);
+                let mruby = mem::transmute::<*const u8, MrubyType>(ptr);
+
+                let result = {
+                    let value = Value::new(mruby.clone(), slf);
+
+                    let method = {
+                        let borrow = mruby.borrow();
+
+                        let methods = match borrow.class_methods.get(&TypeId::of::<T>()) {
+                            Some(methods) => methods,
+                            None          => {
+                                return mruby.raise("TypeError", "Class not found.").value
+                            }
+                        };
+
+                        let sym = mrb_ext_get_mid(mrb);
+
+                        match methods.get(&sym) {
+                            Some(method) => method.clone(),
+                            None         => {
+                                return mruby.raise("TypeError", "Method not found.").value
+                            }
+                        }
+                    };
+
+                    recover_panic(&mruby, || method(mruby.clone(), value).value)
+                };
+
+                mem::forget(mruby);
+
+                result
+            }
+        }
+
+        let borrow = self.borrow();
+
+        let class = match borrow.classes.get(&TypeId::of::<T>()) {
+            Some(class) => class,
+            None       => panic!("Class not found.")
+        };
+
+        unsafe {
+            mrb_define_class_method(borrow.mrb, class.0, CString::new(name).unwrap().as_ptr(),
+                                    call_class_method::<T>, 1 << 12);
+        }
+    }
+
+    fn undef_method(&self, class: &str, name: &str) -> Result<(), MrubyError> {
+        let sym = self.sym(name);
+
+        unsafe {
+            let mrb = self.borrow().mrb;
+
+            let class = mrb_ext_class_path(mrb, CString::new(class).unwrap().as_ptr());
+
+            if class.is_null() || !mrb_obj_respond_to(mrb, class, sym) {
+                return Err(MrubyError::Undef)
+            }
+
+            mrb_undef_method(mrb, class, CString::new(name).unwrap().as_ptr());
+        }
+
+        Ok(())
+    }
+
+    fn undef_class_method(&self, class: &str, name: &str) -> Result<(), MrubyError> {
+        let sym = self.sym(name);
+
+        unsafe {
+            let mrb = self.borrow().mrb;
+
+            let class = mrb_ext_class_path(mrb, CString::new(class).unwrap().as_ptr());
+
+            if class.is_null() || !mrb_respond_to(mrb, mrb_ext_class_value(class), sym) {
+                return Err(MrubyError::Undef)
+            }
+
+            mrb_undef_class_method(mrb, class, CString::new(name).unwrap().as_ptr());
+        }
+
+        Ok(())
+    }
+
+    fn alias_method(&self, class: &str, new_name: &str, old_name: &str)
+        -> Result<(), MrubyError> {
+        let sym = self.sym(old_name);
+
+        unsafe {
+            let mrb = self.borrow().mrb;
+
+            let class = mrb_ext_class_path(mrb, CString::new(class).unwrap().as_ptr());
+
+            if class.is_null() || !mrb_obj_respond_to(mrb, class, sym) {
+                return Err(MrubyError::Undef)
+            }
+
+            // Rust methods are looked up by the name they are called with, on the Rust class or
+            // the class the alias ends up in, which may inherit the method from an ancestor.
+            let class_value = mrb_ext_class_value(class);
+            let ancestors = try!(try!(Value::new(self.clone(), class_value)
+                                          .call("ancestors", vec![])).to_vec());
+
+            let class_sym = |value| {
+                let name = CStr::from_ptr(mrb_ext_class_name(mrb, value)).to_bytes();
+
+                mrb_intern(mrb, name.as_ptr(), name.len())
+            };
+            let new_sym = self.sym(new_name);
+
+            let mut borrow = self.borrow_mut();
+            let borrow = &mut *borrow;
+
+            for ancestor in &ancestors {
+                let named = borrow.named_methods.get(&class_sym(ancestor.value))
+                                                .and_then(|methods| methods.get(&sym).cloned());
+
+                if let Some(method) = named {
+                    borrow.named_methods.entry(class_sym(class_value))
+                                        .or_insert_with(|| HashMap::new())
+                                        .insert(new_sym, method);
+                    break;
+                }
+
+                let id = borrow.classes.iter().find(|&(_, rust_class)| {
+                    mrb_obj_eq(mrb, mrb_ext_class_value(rust_class.0), ancestor.value)
+                }).map(|(id, _)| *id);
+
+                if let Some(methods) = id.and_then(|id| borrow.methods.get_mut(&id)) {
+                    if let Some(method) = methods.get(&sym).cloned() {
+                        methods.insert(new_sym, method);
+                        break;
+                    }
+                }
+            }
+
+            mrb_define_alias(mrb, class, CString::new(new_name).unwrap().as_ptr(),
+                             CString::new(old_name).unwrap().as_ptr());
+        }
+
+        Ok(())
+    }
+
+    fn def_ruby_method<T: Any>(&self, ruby_src: &str) -> Result<(), MrubyError> {
+        let name = match self.borrow().classes.get(&TypeId::of::<T>()) {
+            Some(class) => class.2.clone(),
+            None        => return Err(MrubyError::Undef)
+        };
+
+        self.run(&format!("class {}\n{}\nend", name, ruby_src)).map(|_| ())
+    }
+
+    fn def_alias<T: Any>(&self, new_name: &str, existing: &str) -> Result<(), MrubyError> {
+        let sym = self.sym(existing);
+        let new_sym = self.sym(new_name);
+
+        let class = match self.borrow().classes.get(&TypeId::of::<T>()) {
+            Some(class) => class.0,
+            None        => return Err(MrubyError::Undef)
+        };
+
+        unsafe {
+            if !mrb_obj_respond_to(self.borrow().mrb, class, sym) {
+                return Err(MrubyError::Undef)
+            }
+        }
+
+        // Rust methods are looked up by the name they are called with.
+        {
+            let mut borrow = self.borrow_mut();
+
+            let methods = borrow.methods.get_mut(&TypeId::of::<T>()).unwrap();
+
+            if let Some(method) = methods.get(&sym).map(|method| method.clone()) {
+                methods.insert(new_sym, method);
+            }
+        }
+
+        unsafe {
+            mrb_define_alias(self.borrow().mrb, class, CString::new(new_name).unwrap().as_ptr(),
+                             CString::new(existing).unwrap().as_ptr());
+        }
+
+        Ok(())
+    }
+
+    fn class_methods(&self, class: &str) -> Vec<String> {
+        let class = unsafe {
+            mrb_ext_class_path(self.borrow().mrb, CString::new(class).unwrap().as_ptr())
+        };
+
+        if class.is_null() {
+            return vec![];
+        }
+
+        let class = unsafe { Value::new(self.clone(), mrb_ext_class_value(class)) };
+
+        match class.call("instance_methods", vec![]) {
+            Ok(methods) => method_names(methods),
+            Err(_)      => vec![]
+        }
+    }
+
+    fn include_module<T: Any>(&self, module: &str) -> Result<(), MrubyError> {
+        let borrow = self.borrow();
+
+        let class = match borrow.classes.get(&TypeId::of::<T>()) {
+            Some(class) => class,
+            None        => return Err(MrubyError::Undef)
+        };
+
+        unsafe {
+            let module = mrb_ext_class_path(borrow.mrb, CString::new(module).unwrap().as_ptr());
+
+            if module.is_null() || mrb_ext_class_value(module).typ != MrType::MRB_TT_MODULE {
+                return Err(MrubyError::Undef)
+            }
+
+            mrb_include_module(borrow.mrb, class.0, module);
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    fn class_name<T: Any>(&self) -> Result<String, MrubyError> {
+        let borrow = self.borrow();
+
+        match borrow.classes.get(&TypeId::of::<T>()) {
+            Some(class) => Ok(class.2.clone()),
+            None        => Err(MrubyError::Undef)
+        }
+    }
+
+    #[inline]
+    fn full_gc(&self) {
+        unsafe {
+            mrb_full_gc(self.borrow().mrb);
+        }
+    }
+
+    #[inline]
+    fn incremental_gc(&self) {
+        unsafe {
+            mrb_incremental_gc(self.borrow().mrb);
+        }
+    }
+
+    #[inline]
+    fn gc_disable(&self) {
+        unsafe {
+            mrb_ext_gc_disable(self.borrow().mrb);
+        }
+    }
+
+    #[inline]
+    fn gc_enable(&self) {
+        unsafe {
+            mrb_ext_gc_enable(self.borrow().mrb);
+        }
+    }
+
+    #[inline]
+    fn gc_arena_save(&self) -> i32 {
+        unsafe {
+            mrb_gc_arena_save(self.borrow().mrb)
+        }
+    }
+
+    #[inline]
+    fn gc_arena_restore(&self, idx: i32) {
+        unsafe {
+            mrb_gc_arena_restore(self.borrow().mrb, idx);
+        }
+    }
+
+    fn with_gc_arena<F, R>(&self, f: F) -> R where F: FnOnce() -> R {
+        let idx = self.gc_arena_save();
+
+        let result = f();
+
+        self.gc_arena_restore(idx);
+
+        result
+    }
+
+    fn gc_stats(&self) -> GcStats {
+        let mut live = 0;
+        let mut total = 0;
+        let mut arena = 0;
+
+        unsafe {
+            mrb_ext_gc_stats(self.borrow().mrb, &mut live, &mut total, &mut arena);
+        }
+
+        GcStats {
+            live:        live,
+            total:       total,
+            arena_index: arena as usize
+        }
+    }
+
+    fn top_self(&self) -> Value {
+        unsafe {
+            Value::new(self.clone(), mrb_top_self(self.borrow().mrb))
+        }
+    }
+
+    fn set_user_data<U: Any>(&self, data: U) {
+        self.borrow_mut().user_data = Some(Box::new(Rc::new(data)));
+    }
+
+    fn user_data<U: Any>(&self) -> Option<Rc<U>> {
+        match self.borrow().user_data {
+            Some(ref data) => data.downcast_ref::<Rc<U>>().cloned(),
+            None           => None
+        }
+    }
+
+    fn set_global(&self, name: &str, value: Value) {
+        let sym = self.sym(&global_name(name));
+
+        unsafe {
+            mrb_gv_set(self.borrow().mrb, sym, value.value);
+        }
+    }
+
+    fn get_global(&self, name: &str) -> Value {
+        let sym = self.sym(&global_name(name));
+
+        unsafe {
+            Value::new(self.clone(), mrb_gv_get(self.borrow().mrb, sym))
+        }
+    }
+
+    fn set_argv(&self, args: Vec<&str>) {
+        let program = match args.first() {
+            Some(program) => self.string(program),
+            None          => self.nil()
+        };
+        let argv = args.iter().skip(1).map(|arg| self.string(arg)).collect();
+
+        self.set_global("$0", program);
+        self.def_const("Object", "ARGV", self.array(argv)).unwrap();
+    }
+
+    #[inline]
+    fn nil(&self) -> Value {
+        unsafe {
+            Value::new(self.clone(), MrValue::nil())
+        }
+    }
+
+    #[inline]
+    fn bool(&self, value: bool) -> Value {
+        unsafe {
+            Value::new(self.clone(), MrValue::bool(value))
+        }
+    }
+
+    #[inline]
+    fn fixnum(&self, value: i32) -> Value {
+        unsafe {
+            Value::new(self.clone(), MrValue::fixnum(value))
+        }
+    }
+
+    #[inline]
+    fn int64(&self, value: i64) -> Result<Value, MrubyError> {
+        unsafe {
+            MrValue::int64(value).map(|value| Value::new(self.clone(), value))
+        }
+    }
+
+    #[inline]
+    fn float(&self, value: f64) -> Value {
+        unsafe {
+            Value::new(self.clone(), MrValue::float(self.borrow().mrb, value))
+        }
+    }
+
+    #[inline]
+    fn float32(&self, value: f32) -> Value {
+        self.float(value as f64)
+    }
+
+    #[inline]
+    fn string(&self, value: &str) -> Value {
+        unsafe {
+            Value::new(self.clone(), MrValue::string(self.borrow().mrb, value))
+        }
+    }
+
+    #[inline]
+    fn char(&self, value: char) -> Value {
+        self.string(&value.to_string())
+    }
+
+    #[inline]
+    fn string_bytes(&self, value: &[u8]) -> Value {
+        unsafe {
+            Value::new(self.clone(), MrValue::string_bytes(self.borrow().mrb, value))
+        }
+    }
+
+    #[inline]
+    fn symbol(&self, value: &str) -> Value {
+        unsafe {
+            Value::new(self.clone(), MrValue::symbol(self.borrow().mrb, value))
+        }
+    }
+
+    #[inline]
+    fn sym(&self, name: &str) -> u32 {
+        unsafe {
+            mrb_intern(self.borrow().mrb, name.as_ptr(), name.len())
+        }
+    }
+
+    fn time(&self, value: SystemTime) -> Value {
+        let (secs, usecs) = match value.duration_since(UNIX_EPOCH) {
+            Ok(duration) => {
+                (duration.as_secs() as f64, (duration.subsec_nanos() / 1000) as f64)
+            },
+            Err(err) => {
+                let duration = err.duration();
+
+                (-(duration.as_secs() as f64), -((duration.subsec_nanos() / 1000) as f64))
+            }
+        };
+
+        let class = unsafe {
+            let mrb = self.borrow().mrb;
+
+            mrb_ext_class_value(mrb_class_get(mrb, CString::new("Time").unwrap().as_ptr()))
+        };
+
+        Value::new(self.clone(), class).call("at", vec![self.float(secs), self.float(usecs)])
+                                       .unwrap()
+    }
+
+    #[inline]
+    fn obj<T: Any>(&self, obj: T) -> Value {
+        let borrow = self.borrow();
+
+        let class = match borrow.classes.get(&TypeId::of::<T>()) {
+            Some(class) => class,
+            None       => panic!("Class not found.")
+        };
+
+        unsafe {
+            Value::new(self.clone(), MrValue::obj(borrow.mrb, class.0 as *const MrClass, obj,
+                                                  &class.1))
+        }
+    }
+
+    fn obj_dyn<T: Any>(&self, key: &str, obj: T) -> Value {
+        let borrow = self.borrow();
+
+        let class = match borrow.dyn_classes.get(key) {
+            Some(class) => class,
+            None        => panic!("Class not found.")
+        };
+
+        let obj: Rc<Any> = Rc::new(obj);
+
+        unsafe {
+            Value::new(self.clone(), MrValue::obj(borrow.mrb, class.0 as *const MrClass, obj,
+                                                  &class.1))
+        }
+    }
+
+    #[inline]
+    fn option<T: Any>(&self, obj: Option<T>) -> Value {
+        match obj {
+            Some(obj) => self.obj(obj),
+            None      => self.nil()
+        }
+    }
+
+    #[inline]
+    fn string_or_nil(&self, value: Option<&str>) -> Value {
+        value.to_value(self)
+    }
+
+    #[inline]
+    fn array(&self, value: Vec<Value>) -> Value {
+        let array: Vec<MrValue> = value.iter().map(|value| {
+            value.value
+        }).collect();
+
+        unsafe {
+            Value::new(self.clone(), MrValue::array(self.borrow().mrb, array))
+        }
+    }
+
+    fn array_of<T: ToValue, I: IntoIterator<Item = T>>(&self, items: I) -> Value {
+        let items = items.into_iter();
+
+        unsafe {
+            let mrb = self.borrow().mrb;
+
+            let array = mrb_ary_new_capa(mrb, items.size_hint().0 as i32);
+
+            for item in items {
+                mrb_ary_push(mrb, array, item.to_value(self).value);
+            }
+
+            Value::new(self.clone(), array)
+        }
+    }
+
+    #[inline]
+    fn hash(&self, value: Vec<(Value, Value)>) -> Value {
+        let hash: Vec<(MrValue, MrValue)> = value.iter().map(|&(ref key, ref value)| {
+            (key.value, value.value)
+        }).collect();
+
+        unsafe {
+            Value::new(self.clone(), MrValue::hash(self.borrow().mrb, hash))
+        }
+    }
+
+    #[inline]
+    fn range(&self, from: Value, to: Value, exclusive: bool) -> Value {
+        unsafe {
+            let range = mrb_ext_range_new(self.borrow().mrb, from.value, to.value, exclusive);
+
+            Value::new(self.clone(), range)
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    fn from_json(&self, json: &serde_json::Value) -> Value {
+        json::from_json(self, json)
+    }
+
+    fn proc_from<F>(&self, f: F) -> Value where F: Fn(MrubyType, Vec<Value>) -> Value + 'static {
+        extern "C" fn call_proc(mrb: *const MrState, _slf: MrValue) -> MrValue {
+            unsafe {
+                let ptr = mrb_ext_get_ud(mrb);
+                let mruby = mem::transmute::<*const u8, MrubyType>(ptr);
+
+                let result = {
+                    let ptr = mrb_ext_data_ptr(mrb_proc_cfunc_env_get(mrb, 0));
+                    let rc = mem::transmute::<*const u8, Rc<ProcFn>>(ptr);
+
+                    let f = rc.clone();
+
+                    mem::forget(rc);
+
+                    let args = mem::uninitialized::<*mut MrValue>();
+                    let count = mem::uninitialized::<i32>();
+
+                    mrb_get_args(mrb, CString::new("*").unwrap().as_ptr(),
+                                 &args as *const *mut MrValue, &count as *const i32);
+
+                    let args = slice::from_raw_parts(args, count as usize);
+                    let args = args.iter().map(|arg| {
+                        Value::new(mruby.clone(), *arg)
+                    }).collect::<Vec<_>>();
+
+                    recover_panic(&mruby, || f(mruby.clone(), args).value)
+                };
+
+                mem::forget(mruby);
+
+                result
+            }
+        }
+
+        unsafe {
+            let (mrb, proc_ty) = {
+                let borrow = self.borrow();
+
+                (borrow.mrb, &borrow.proc_ty as *const MrDataType)
+            };
+
+            // The closure lives in a data object kept in the proc's environment. It is dropped
+            // by free_proc once mruby collects the proc.
+            let f: ProcFn = Box::new(f);
+            let class = mrb_class_get(mrb, CString::new("Object").unwrap().as_ptr());
+
+            let env = [MrValue::obj(mrb, class, f, &*proc_ty)];
+            let prc = mrb_proc_new_cfunc_with_env(mrb, call_proc, 1, env.as_ptr());
+
+            Value::new(self.clone(), mrb_ext_proc_to_value(mrb, prc))
+        }
+    }
+}
+
+fn exception_class(err: &MrubyError) -> &'static str {
+    match *err {
+        MrubyError::Cast(_)                     => "TypeError",
+        MrubyError::Undef                       => "NameError",
+        MrubyError::Syntax { .. }               => "SyntaxError",
+        MrubyError::Runtime(_)                  => "RuntimeError",
+        MrubyError::Frozen(_)                   => "FrozenError",
+        MrubyError::Panic(_)                    => "RustPanic",
+        MrubyError::Resource(_)                 => "ResourceError",
+        MrubyError::RuntimeWithBacktrace { .. } => "RuntimeError",
+        MrubyError::Filetype                    => "ArgumentError",
+        MrubyError::Io(_)                       => "RuntimeError"
+    }
+}
+
+fn panic_class(mrb: *const MrState) -> String {
+    unsafe {
+        let ptr = mrb_ext_get_ud(mrb);
+
+        // The user data is null while the state is owned by an MrubyOwned.
+        if ptr.is_null() {
+            return "RustPanic".to_owned();
+        }
+
+        let mruby = mem::transmute::<*const u8, MrubyType>(ptr);
+        let class = mruby.borrow().panic_class.clone();
+
+        mem::forget(mruby);
+
+        class
+    }
+}
+
+fn get_exc(mrb: *const MrState) -> Option<MrubyError> {
+    unsafe {
+        let mut line = 0;
+        let mut column = 0;
+
+        let message = mrb_ext_get_syntax_error(mrb, &mut line, &mut column);
+
+        if let Ok(message) = message.to_str(mrb) {
+            return Some(MrubyError::Syntax {
+                message: message.to_owned(),
+                line:    line as u32,
+                column:  column as u32
+            })
+        }
+
+        let panic = mrb_ext_get_exc_of(mrb, CString::new(panic_class(mrb)).unwrap().as_ptr());
+
+        if let Ok(message) = panic.to_str(mrb) {
+            mrb_ext_get_exc(mrb);
+
+            return Some(MrubyError::Panic(message.to_owned()))
+        }
+
+        let resource = mrb_ext_get_exc_of(mrb, CString::new("ResourceError").unwrap().as_ptr());
+
+        if let Ok(message) = resource.to_str(mrb) {
+            mrb_ext_get_exc(mrb);
+
+            return Some(MrubyError::Resource(message.to_owned()))
+        }
+
+        let exc = mrb_ext_get_exc_with_backtrace(mrb);
+
+        match exc.typ {
+            MrType::MRB_TT_FALSE => None,
+            _ => {
+                let mut lines: Vec<String> = exc.to_vec(mrb).unwrap().iter().map(|line| {
+                    line.to_str(mrb).unwrap().to_owned()
+                }).collect();
+
+                let message = lines.remove(0);
+
+                // A single frame is the raise location, which the message already contains.
+                if lines.len() > 1 {
+                    Some(MrubyError::RuntimeWithBacktrace {
+                        message:   message,
+                        backtrace: lines
+                    })
+                } else {
+                    Some(MrubyError::Runtime(message))
+                }
+            }
+        }
+    }
+}
+
+fn global_name(name: &str) -> String {
+    if name.starts_with('$') {
+        name.to_owned()
+    } else {
+        "$".to_owned() + name
+    }
+}
+
+// Starts a new instruction count when mruby is entered from Rust rather than from a method called
+// by a running script, so that each run gets the whole instruction limit.
+fn reset_instructions(mruby: &MrubyType) {
+    let borrow = mruby.borrow();
+
+    if unsafe { mrb_ext_vm_idle(borrow.mrb) } {
+        borrow.executed.set(0);
+    }
+}
+
+// Installs the code fetch hook only while a timeout or an instruction limit is active, since it
+// runs before every VM instruction.
+fn set_code_fetch_hook(mruby: &MrubyType) {
+    extern "C" fn code_fetch(mrb: *const MrState, _irep: *const u8, _pc: *const u32,
+                             _regs: *const MrValue) {
+        unsafe {
+            let ptr = mrb_ext_get_ud(mrb);
+            let mruby = mem::transmute::<*const u8, MrubyType>(ptr);
+
+            // Clearing the flag raises the timeout only once, so that scripts can rescue it.
+            let interrupted = match mruby.borrow().interrupt {
+                Some(ref flag) => flag.swap(false, Ordering::Relaxed),
+                None           => false
+            };
+
+            // Only borrows immutably, since methods like Value::call keep a borrow while running.
+            let exhausted = {
+                let borrow = mruby.borrow();
+
+                match borrow.instructions {
+                    Some(max) if borrow.executed.get() >= max => true,
+                    Some(_)                                   => {
+                        borrow.executed.set(borrow.executed.get() + 1);
+
+                        false
+                    },
+                    None => false
+                }
+            };
+
+            mem::forget(mruby);
+
+            if interrupted {
+                mrb_ext_raise(mrb, CString::new("RuntimeError").unwrap().as_ptr(),
+                              CString::new("execution timed out").unwrap().as_ptr());
+            }
+
+            if exhausted {
+                mrb_ext_raise(mrb, CString::new("ResourceError").unwrap().as_ptr(),
+                              CString::new("instruction limit exceeded").unwrap().as_ptr());
+            }
+        }
+    }
+
+    let borrow = mruby.borrow();
+
+    let hook = if borrow.interrupt.is_some() || borrow.instructions.is_some() {
+        Some(code_fetch as extern "C" fn(*const MrState, *const u8, *const u32, *const MrValue))
+    } else {
+        None
+    };
+
+    unsafe {
+        mrb_ext_set_code_fetch_hook(borrow.mrb, hook);
+    }
+}
+
+fn find_file(dirs: &[PathBuf], name: &str) -> Result<PathBuf, String> {
+    let mut tried = Vec::new();
+
+    for dir in dirs {
+        for file in &[name.to_owned() + ".rb", name.to_owned() + ".mrb", name.to_owned()] {
+            let path = dir.join(file);
+
+            if path.is_file() {
+                return Ok(path);
+            }
+
+            tried.push(path.display().to_string());
+        }
+    }
+
+    Err(format!("cannot load {}; tried {}", name, tried.join(", ")))
+}
+
+fn require_file(mruby: &MrubyType, path: &Path, name: String) -> Value {
+    let filename = mruby.borrow().filename.clone();
+
+    { mruby.borrow_mut().required.insert(name); }
+
+    let result = mruby.execute(path);
+
+    match filename {
+        Some(filename) => mruby.filename(&filename),
+        None           => mruby.borrow_mut().filename = None
+    }
+
+    match result {
+        Err(err) => {
+            mruby.raise("RuntimeError", &format!("{}", err));
+        }
+        _ => ()
+    }
+
+    mruby.bool(true)
+}
+
+fn module_path(mruby: &MrubyType, path: &str) -> *const MrClass {
+    unsafe {
+        let mrb = mruby.borrow().mrb;
+
+        let mut outer = mrb_class_get(mrb, CString::new("Object").unwrap().as_ptr());
+        let mut current = String::new();
+
+        for part in path.split("::") {
+            current = if current.is_empty() {
+                part.to_owned()
+            } else {
+                current + "::" + part
+            };
+
+            let existing = mrb_ext_class_path(mrb, CString::new(current.clone()).unwrap()
+                                                                                .as_ptr());
+
+            outer = if existing.is_null() {
+                mrb_define_module_under(mrb, outer, CString::new(part).unwrap().as_ptr())
+            } else {
+                existing
+            };
+        }
+
+        outer
+    }
+}
+
+fn method_names(methods: Value) -> Vec<String> {
+    match methods.to_vec() {
+        Ok(methods) => {
+            methods.iter().filter_map(|method| {
+                method.call("to_s", vec![]).ok().and_then(|name| {
+                    name.to_str().ok().map(|name| name.to_owned())
+                })
+            }).collect()
+        },
+        Err(_) => vec![]
+    }
+}
+
+extern "C" fn free<T: Any>(mrb: *const MrState, ptr: *const u8) {
+    unsafe {
+        let mut rc = mem::transmute::<*const u8, Rc<T>>(ptr);
+
+        // The user data is null while the state is owned by an MrubyOwned.
+        let ud = mrb_ext_get_ud(mrb);
+
+        if ud.is_null() {
+            return;
+        }
+
+        let mruby = mem::transmute::<*const u8, MrubyType>(ud);
+        let on_free = mruby.borrow().drops.get(&TypeId::of::<T>()).map(|f| f.clone());
+
+        mem::forget(mruby);
+
+        if let (Some(on_free), Some(obj)) = (on_free, Rc::get_mut(&mut rc)) {
+            on_free.downcast_ref::<Box<Fn(&mut T)>>().unwrap()(obj);
+        }
+    }
+}
+
+type ProcFn = Box<Fn(MrubyType, Vec<Value>) -> Value>;
+type SingletonMethods = HashMap<u32, Rc<Fn(MrubyType, Value) -> Value>>;
+
+extern "C" fn free_rc<T>(_mrb: *const MrState, ptr: *const u8) {
+    unsafe {
+        mem::transmute::<*const u8, Rc<T>>(ptr);
+    }
+}
+
+fn recover_panic<F>(mruby: &MrubyType, f: F) -> MrValue where F: FnOnce() -> MrValue {
+    match panic::recover(AssertRecoverSafe::new(f)) {
+        Ok(value)  => value,
+        Err(error) => {
+            let message = match error.downcast_ref::<&'static str>() {
+                Some(s) => *s,
+                None    => match error.downcast_ref::<String>() {
+                    Some(s) => &s[..],
+                    None    => ""
+                }
+            };
+
+            let class = mruby.borrow().panic_class.clone();
+
+            mruby.raise(&class, message).value
+        }
+    }
+}
+
+fn register_class<T: Any>(mruby: &MrubyType, class: *const MrClass, name: String) {
+    unsafe {
+        let c_name = CString::new(name.clone()).unwrap();
+
+        mrb_ext_set_instance_tt(class, MrType::MRB_TT_DATA);
+
+        let data_type = MrDataType { name: c_name.as_ptr(), free: free::<T> };
+
+        mruby.borrow_mut().classes.insert(TypeId::of::<T>(), (class, data_type, name));
+        mruby.borrow_mut().methods.insert(TypeId::of::<T>(), HashMap::new());
+        mruby.borrow_mut().class_methods.insert(TypeId::of::<T>(), HashMap::new());
+    }
+
+    mruby.def_method::<T, _>("dup", |_mruby, slf| {
+        slf.clone()
+    });
+}
+
+impl Drop for Mruby {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// A `struct` that wraps around any mruby variable.
+///
+/// `Values` are created from the `Mruby` instance:
+///
+/// * [`nil`](../mrusty/trait.MrubyImpl.html#tymethod.nil)
+/// * [`bool`](../mrusty/trait.MrubyImpl.html#tymethod.bool)
+/// * [`fixnum`](../mrusty/trait.MrubyImpl.html#tymethod.fixnum)
+/// * [`float`](../mrusty/trait.MrubyImpl.html#tymethod.float)
+/// * [`string`](../mrusty/trait.MrubyImpl.html#tymethod.string)
+/// * [`obj`](../mrusty/trait.MrubyImpl.html#tymethod.obj)
+/// * [`option`](../mrusty/trait.MrubyImpl.html#tymethod.option)
+/// * [`array`](../mrusty/trait.MrubyImpl.html#tymethod.array)
+/// * [`hash`](../mrusty/trait.MrubyImpl.html#tymethod.hash)
+///
+/// # Examples
+///
+/// ```
+/// # use mrusty::Mruby;
+/// # use mrusty::MrubyImpl;
+/// let mruby = Mruby::new();
+/// let result = mruby.run("true").unwrap(); // Value
+///
+/// // Values need to be unwrapped in order to make sure they have the right mruby type.
+/// assert_eq!(result.to_bool().unwrap(), true);
+/// ```
+pub struct Value {
+    mruby: MrubyType,
+    value: MrValue
+}
+
+impl Value {
+    /// Not meant to be called directly.
+    #[doc(hidden)]
+    pub fn new(mruby: MrubyType, value: MrValue) -> Value {
+        Value {
+            mruby: mruby,
+            value: value
+        }
+    }
+
+    /// Initializes the `self` mruby object passed to `initialize` with a Rust object of type `T`.
+    ///
+    /// *Note:* `T` must be defined on the current `Mruby` with `def_class`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::*;
+    ///
+    /// # fn main() {
+    /// let mruby = Mruby::new();
+    ///
+    /// struct Cont {
+    ///     value: i32
+    /// };
+    ///
+    /// mruby.def_class::<Cont>("Container");
+    /// mruby.def_method::<Cont, _>("initialize", mrfn!(|_mruby, slf: Value, v: i32| {
+    ///     let cont = Cont { value: v };
+    ///
+    ///     slf.init(cont) // Return the same slf value.
+    /// }));
+    ///
+    /// let result = mruby.run("Container.new 3").unwrap();
+    ///
+    /// assert_eq!(result.to_obj::<Cont>().unwrap().value, 3);
+    /// # }
+    /// ```
+    pub fn init<T: Any>(self, obj: T) -> Value {
+        unsafe {
+            let rc = Rc::new(obj);
+            let ptr = mem::transmute::<Rc<T>, *const u8>(rc);
+
+            let borrow = self.mruby.borrow();
+
+            let class = match borrow.classes.get(&TypeId::of::<T>()) {
+                Some(class) => class,
+                None       => panic!("Class not found.")
+            };
+
+            let data_type = &class.1;
+
+            mrb_ext_data_init(&self.value as *const MrValue, ptr, data_type as *const MrDataType);
+        }
+
+        self
+    }
+
+    /// Initializes the `self` mruby object passed to `initialize` with a Rust object of type `T`,
+    /// stored as a `Rc<Any>`.
+    ///
+    /// *Note:* `key` must be defined on the current `Mruby` with `def_class_dyn`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::*;
+    ///
+    /// # fn main() {
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.def_class_dyn("plugin", "Plugin");
+    /// mruby.def_method_on("Plugin", "initialize", mrfn!(|_mruby, slf: Value, name: String| {
+    ///     slf.init_dyn("plugin", name)
+    /// })).unwrap();
+    ///
+    /// let result = mruby.run("Plugin.new 'logger'").unwrap();
+    ///
+    /// assert_eq!(*result.to_obj_dyn::<String>().unwrap(), "logger");
+    /// # }
+    /// ```
+    pub fn init_dyn<T: Any>(self, key: &str, obj: T) -> Value {
+        unsafe {
+            let rc: Rc<Any> = Rc::new(obj);
+            let ptr = mem::transmute::<Rc<Rc<Any>>, *const u8>(Rc::new(rc));
+
+            let borrow = self.mruby.borrow();
+
+            let class = match borrow.dyn_classes.get(key) {
+                Some(class) => class,
+                None        => panic!("Class not found.")
+            };
+
+            let data_type = &class.1;
+
+            mrb_ext_data_init(&self.value as *const MrValue, ptr, data_type as *const MrDataType);
+        }
+
+        self
+    }
+
+    /// Calls method `name` on a `Value` passing `args`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let one = mruby.fixnum(1);
+    /// let result = one.call("+", vec![mruby.fixnum(2)]).unwrap();
+    ///
+    /// assert_eq!(result.to_i32().unwrap(), 3);
+    /// ```
+    pub fn call(&self, name: &str, args: Vec<Value>) -> Result<Value, MrubyError> {
+        self.call_sym(self.mruby.sym(name), args)
+    }
+
+    /// Calls the method with the symbol `sym`, as returned by `sym`, on a `Value` passing `args`.
+    /// Caching `sym` avoids interning the method name on every call.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let plus = mruby.sym("+");
+    /// let one = mruby.fixnum(1);
+    ///
+    /// let result = one.call_sym(plus, vec![mruby.fixnum(2)]).unwrap();
+    ///
+    /// assert_eq!(result.to_i32().unwrap(), 3);
+    /// ```
+    pub fn call_sym(&self, sym: u32, args: Vec<Value>) -> Result<Value, MrubyError> {
+        let mrb = self.mruby.borrow().mrb;
+
+        reset_instructions(&self.mruby);
+
+        unsafe {
+            let args: Vec<MrValue> = args.iter().map(|value| value.value).collect();
+
+            let result = mrb_funcall_argv(mrb, self.value, sym, args.len() as i32, args.as_ptr());
+
+            match get_exc(mrb) {
+                Some(err) => Err(err),
+                None      => Ok(Value::new(self.mruby.clone(), result))
+            }
+        }
+    }
+
+    /// Calls method `name` on a `Value` passing `args` like `call`, returning `None` when the
+    /// `Value` does not respond to `name`, private methods and `respond_to_missing?` included.
+    /// Useful for optional duck-typed methods. A `NoMethodError` raised from inside `name` is
+    /// still returned as an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let one = mruby.fixnum(1);
+    ///
+    /// let result = one.try_call("+", vec![mruby.fixnum(2)]).unwrap().unwrap();
+    ///
+    /// assert_eq!(result.to_i32().unwrap(), 3);
+    /// assert!(one.try_call("nope", vec![]).unwrap().is_none());
+    /// assert!(one.try_call("/", vec![mruby.string("a")]).is_err());
+    /// ```
+    pub fn try_call(&self, name: &str, args: Vec<Value>) -> Result<Option<Value>, MrubyError> {
+        let responds = try!(self.call("respond_to?", vec![self.mruby.symbol(name),
+                                                          self.mruby.bool(true)]));
+
+        if try!(responds.to_bool()) {
+            self.call(name, args).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Calls the method named by the `Symbol` `name` on a `Value` passing `args`, like Ruby's
+    /// `send`. Returns a `Cast` error if `name` is not a `Symbol`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let name = mruby.run(":upcase").unwrap();
+    /// let result = mruby.string("hi").send(&name, vec![]).unwrap();
+    ///
+    /// assert_eq!(result.to_str().unwrap(), "HI");
+    /// assert!(mruby.string("hi").send(&mruby.string("upcase"), vec![]).is_err());
+    /// ```
+    pub fn send(&self, name: &Value, args: Vec<Value>) -> Result<Value, MrubyError> {
+        if !name.is_symbol() {
+            return Err(MrubyError::Cast("Symbol".to_owned()));
+        }
+
+        self.call_sym(unsafe { mrb_ext_symbol(name.value) }, args)
+    }
+
+    /// Calls method `name` on a `Value` passing `args` and `block` as the method's block. `block`
+    /// can be any `Proc` `Value`, including one created with `proc_from`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let array = mruby.array(vec![mruby.fixnum(1), mruby.fixnum(2), mruby.fixnum(3)]);
+    /// let double = mruby.proc_from(|mruby, args| {
+    ///     mruby.fixnum(args[0].to_i32().unwrap() * 2)
+    /// });
+    ///
+    /// let result = array.call_with_block("map", vec![], double).unwrap();
+    ///
+    /// assert_eq!(result.to_vec().unwrap(), vec![
+    ///     mruby.fixnum(2),
+    ///     mruby.fixnum(4),
+    ///     mruby.fixnum(6)
+    /// ]);
+    /// ```
+    pub fn call_with_block(&self, name: &str, args: Vec<Value>,
+                           block: Value) -> Result<Value, MrubyError> {
+        unsafe {
+            let sym = self.mruby.sym(name);
+
+            let args: Vec<MrValue> = args.iter().map(|value| value.value).collect();
+
+            let mrb = self.mruby.borrow().mrb;
+
+            reset_instructions(&self.mruby);
+
+            let result = mrb_funcall_with_block(mrb, self.value, sym, args.len() as i32,
+                                                args.as_ptr(), block.value);
+
+            match get_exc(mrb) {
+                Some(err) => Err(err),
+                None      => Ok(Value::new(self.mruby.clone(), result))
+            }
+        }
+    }
+
+    /// Runs mruby `script` with `self` bound to a `Value`, like Ruby's `instance_eval`. Methods
+    /// called without a receiver, including singleton methods, are looked up on the `Value` and
+    /// methods defined by `script` are defined on its singleton `Class`. Exceptions are returned
+    /// in an `Err` like with `run`. Syntax errors are raised by mruby-eval as `SyntaxError`s and
+    /// returned as `Runtime`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let config = mruby.run("
+    ///   class Config
+    ///     attr_reader :settings
+    ///
+    ///     def initialize
+    ///       @settings = {}
+    ///     end
+    ///
+    ///     def set(key, value)
+    ///       @settings[key] = value
+    ///     end
+    ///   end
+    ///
+    ///   Config.new
+    /// ").unwrap();
+    ///
+    /// config.instance_eval("
+    ///   set :workers, 4
+    ///   set :name, 'api'
+    /// ").unwrap();
+    ///
+    /// let workers = config.call("settings", vec![]).unwrap()
+    ///                     .call("[]", vec![mruby.symbol("workers")]).unwrap();
+    ///
+    /// assert_eq!(workers.to_i32().unwrap(), 4);
+    /// assert!(config.instance_eval("nope 1").is_err());
+    /// ```
+    pub fn instance_eval(&self, script: &str) -> Result<Value, MrubyError> {
+        self.call("instance_eval", vec![self.mruby.string(script)])
+    }
+
+    /// Calls method `name` on a `Value` passing `args`. If call fails, mruby will be left to
+    /// handle the exception.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let one = mruby.string("");
+    /// one.call("+", vec![mruby.fixnum(1)]);
+    /// ```
+    pub fn call_unchecked(&self, name: &str, args: Vec<Value>) -> Value {
+        unsafe {
+            let sym = self.mruby.sym(name);
+
+            let args: Vec<MrValue> = args.iter().map(|value| value.value).collect();
+
+            let mrb = self.mruby.borrow().mrb;
+
+            reset_instructions(&self.mruby);
+
+            let result = mrb_funcall_argv(mrb, self.value, sym, args.len() as i32, args.as_ptr());
+
+            Value::new(self.mruby.clone(), result)
+        }
+    }
+
+    /// Returns the value of the instance variable `name` in a `Some` or `None` if it is not
+    /// defined. `name` can be passed either with or without its leading `@`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("
+    ///   class Cont
+    ///     def initialize
+    ///       @value = 3
+    ///     end
+    ///   end
+    ///
+    ///   Cont.new
+    /// ").unwrap();
+    ///
+    /// assert_eq!(result.get_var("value").unwrap().to_i32().unwrap(), 3);
+    /// assert_eq!(result.get_var("@value").unwrap().to_i32().unwrap(), 3);
+    /// assert!(result.get_var("nope").is_none());
+    /// ```
+    pub fn get_var(&self, name: &str) -> Option<Value> {
+        let name = if name.starts_with('@') {
+            name.to_owned()
+        } else {
+            "@".to_owned() + name
+        };
+
+        unsafe {
+            let mrb = self.mruby.borrow().mrb;
+
+            let sym = mrb_intern(mrb, name.as_ptr(), name.len());
+
+            if mrb_iv_defined(mrb, self.value, sym) {
+                Some(Value::new(self.mruby.clone(), mrb_iv_get(mrb, self.value, sym)))
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Sets the instance variable `name` to `value`. `name` can be passed either with or without
+    /// its leading `@`.
+    ///
+    /// *Note:* Setting instance variables on immediate values like `Fixnum` raises an mruby
+    /// `ArgumentError`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::*;
+    ///
+    /// # fn main() {
+    /// let mruby = Mruby::new();
+    ///
+    /// struct Cont;
+    ///
+    /// mruby.def_class::<Cont>("Container");
+    /// mruby.def_method::<Cont, _>("initialize", mrfn!(|mruby, slf: Value, v: i32| {
+    ///     slf.set_var("value", mruby.fixnum(v));
+    ///     slf.set_var("@double", mruby.fixnum(v * 2));
+    ///
+    ///     slf
+    /// }));
+    ///
+    /// let result = mruby.run("
+    ///   class Container
+    ///     attr_reader :double
+    ///   end
+    ///
+    ///   Container.new(3).double
+    /// ").unwrap();
+    ///
+    /// assert_eq!(result.to_i32().unwrap(), 6);
+    /// # }
+    /// ```
+    pub fn set_var(&self, name: &str, value: Value) {
+        let name = if name.starts_with('@') {
+            name.to_owned()
+        } else {
+            "@".to_owned() + name
+        };
+
+        unsafe {
+            let mrb = self.mruby.borrow().mrb;
+
+            let sym = mrb_intern(mrb, name.as_ptr(), name.len());
+
+            mrb_iv_set(mrb, self.value, sym, value.value);
         }
     }
 
-    #[inline]
-    fn run_unchecked(&self, script: &str) -> Value {
+    /// Returns the names of the instance variables of a `Value`, each with its leading `@`, like
+    /// Ruby's `instance_variables`. Values without instance variables, like `Fixnum`s, return an
+    /// empty `Vec`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("
+    ///   class Point
+    ///     def initialize
+    ///       @x = 1
+    ///       @y = 2
+    ///     end
+    ///   end
+    ///
+    ///   Point.new
+    /// ").unwrap();
+    ///
+    /// let mut names = result.instance_variables();
+    /// names.sort();
+    ///
+    /// assert_eq!(names, vec!["@x", "@y"]);
+    /// assert_eq!(result.get_var(&names[1]).unwrap().to_i32().unwrap(), 2);
+    /// assert!(mruby.fixnum(1).instance_variables().is_empty());
+    /// ```
+    pub fn instance_variables(&self) -> Vec<String> {
+        match self.call("instance_variables", vec![]) {
+            Ok(names) => method_names(names),
+            Err(_)    => vec![]
+        }
+    }
+
+    /// Defines an mruby method named `name` on the singleton `Class` of a `Value`, so that only
+    /// this object responds to it. The closure to be run when the `name` method is called should
+    /// be passed through the `mrfn!` macro.
+    ///
+    /// Returns a `Cast` error for `nil`, `true`, `false`, `Fixnum`, `Float` and `Symbol`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::*;
+    ///
+    /// # fn main() {
+    /// let mruby = Mruby::new();
+    ///
+    /// let config = mruby.run("$config = Object.new").unwrap();
+    ///
+    /// config.def_singleton_method("port", mrfn!(|mruby, _slf: Value| {
+    ///     mruby.fixnum(8080)
+    /// })).unwrap();
+    ///
+    /// assert_eq!(mruby.run("$config.port").unwrap().to_i32().unwrap(), 8080);
+    /// assert!(mruby.run("Object.new.port").is_err());
+    /// # }
+    /// ```
+    pub fn def_singleton_method<F>(&self, name: &str, method: F) -> Result<(), MrubyError>
+        where F: Fn(MrubyType, Value) -> Value + 'static {
+        match self.value.typ {
+            MrType::MRB_TT_FALSE  |
+            MrType::MRB_TT_TRUE   |
+            MrType::MRB_TT_FIXNUM |
+            MrType::MRB_TT_FLOAT  |
+            MrType::MRB_TT_SYMBOL |
+            MrType::MRB_TT_CPTR   => return Err(MrubyError::Cast("Object".to_owned())),
+            _                     => ()
+        }
+
+        extern "C" fn call_singleton_method(mrb: *const MrState, slf: MrValue) -> MrValue {
+            unsafe {
+                let ptr = mrb_ext_get_ud(mrb);
+                let mruby = mem::transmute::<*const u8, MrubyType>(ptr);
+
+                let result = {
+                    let value = Value::new(mruby.clone(), slf);
+
+                    let method = {
+                        let class = mrb_ext_class_value(mrb_ext_get_target_class(mrb));
+                        let methods = mrb_iv_get(mrb, class, mruby.sym("__singleton__"));
+
+                        if methods.typ == MrType::MRB_TT_DATA {
+                            let ptr = mrb_ext_data_ptr(methods);
+                            let methods = mem::transmute::<*const u8, Rc<SingletonMethods>>(ptr);
+
+                            let sym = mrb_ext_get_mid(mrb);
+                            let method = methods.get(&sym).cloned().ok_or("Method not found.");
+
+                            mem::forget(methods);
+
+                            method
+                        } else {
+                            Err("Class not found.")
+                        }
+                    };
+
+                    let method = match method {
+                        Ok(method)   => method,
+                        Err(message) => return mruby.raise("TypeError", message).value
+                    };
+
+                    recover_panic(&mruby, || method(mruby.clone(), value).value)
+                };
+
+                mem::forget(mruby);
+
+                result
+            }
+        }
+
         unsafe {
-            let (mrb, ctx) = {
-                let borrow = self.borrow();
+            let (mrb, singleton_ty) = {
+                let borrow = self.mruby.borrow();
 
-                (borrow.mrb, borrow.ctx)
+                (borrow.mrb, &borrow.singleton_ty as *const MrDataType)
             };
 
-            let value = mrb_load_nstring_cxt(mrb, script.as_ptr(), script.len() as i32, ctx);
+            let class = mrb_ext_singleton_class(mrb, self.value);
 
-            Value::new(self.clone(), value)
+            // The singleton Class keeps its closures in a hidden data object which mruby frees
+            // along with the Class. Clones share the object, so it is replaced instead of being
+            // modified.
+            let class_value = mrb_ext_class_value(class);
+            let methods_sym = self.mruby.sym("__singleton__");
+            let current = mrb_iv_get(mrb, class_value, methods_sym);
+
+            let mut methods = if current.typ == MrType::MRB_TT_DATA {
+                let ptr = mrb_ext_data_ptr(current);
+                let current = mem::transmute::<*const u8, Rc<SingletonMethods>>(ptr);
+                let methods = (*current).clone();
+
+                mem::forget(current);
+
+                methods
+            } else {
+                SingletonMethods::new()
+            };
+
+            methods.insert(self.mruby.sym(name), Rc::new(method));
+
+            let object = mrb_class_get(mrb, CString::new("Object").unwrap().as_ptr());
+            let methods = MrValue::obj(mrb, object, methods, &*singleton_ty);
+
+            mrb_iv_set(mrb, class_value, methods_sym, methods);
+
+            mrb_define_method(mrb, class, CString::new(name).unwrap().as_ptr(),
+                              call_singleton_method, 1 << 12);
         }
+
+        Ok(())
     }
 
-    #[inline]
-    fn runb(&self, script: &[u8]) -> Result<Value, MrubyError> {
-        unsafe {
-            let (mrb, ctx) = {
-                let borrow = self.borrow();
+    /// Returns the constant `name` defined on a `Value` of `Class` `Class` or `Module` in an
+    /// `Ok` or an `Err` if the constant is not defined.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("
+    ///   module Config
+    ///     VERSION = '1.0'
+    ///   end
+    ///
+    ///   Config
+    /// ").unwrap();
+    ///
+    /// assert_eq!(result.get_const("VERSION").unwrap().to_str().unwrap(), "1.0");
+    /// assert!(result.get_const("NOPE").is_err());
+    /// ```
+    pub fn get_const(&self, name: &str) -> Result<Value, MrubyError> {
+        match self.value.typ {
+            MrType::MRB_TT_CLASS | MrType::MRB_TT_MODULE | MrType::MRB_TT_SCLASS => (),
+            _ => return Err(MrubyError::Cast("Class or Module".to_owned()))
+        }
 
-                (borrow.mrb, borrow.ctx)
-            };
+        unsafe {
+            let mrb = self.mruby.borrow().mrb;
 
-            let value = mrb_load_irep_cxt(mrb, script.as_ptr(), ctx);
-            let exc = mrb_ext_get_exc(self.borrow().mrb);
+            let sym = mrb_intern(mrb, name.as_ptr(), name.len());
 
-            match exc.typ {
-                MrType::MRB_TT_FALSE => {
-                    Ok(Value::new(self.clone(), value))
-                },
-                _ => Err(MrubyError::Runtime(exc.to_str(self.borrow().mrb).unwrap().to_owned()))
+            if mrb_const_defined(mrb, self.value, sym) {
+                Ok(Value::new(self.mruby.clone(), mrb_const_get(mrb, self.value, sym)))
+            } else {
+                Err(MrubyError::Undef)
             }
         }
     }
 
-    #[inline]
-    fn execute(&self, script: &Path) -> Result<Value, MrubyError> {
-        match script.extension() {
-            Some(ext) => {
-                self.filename(script.file_name().unwrap().to_str().unwrap());
+    /// Returns the name of the mruby `Class` as a `&str`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let one = mruby.run("1").unwrap();
+    /// assert_eq!(one.type_name(), "Fixnum");
+    /// ```
+    pub fn type_name(&self) -> &str {
+        let string = self.call_unchecked("class", vec![]).call_unchecked("to_s", vec![]);
 
-                let mut file = try!(File::open(script));
+        string.to_str().unwrap()
+    }
 
-                match ext.to_str().unwrap() {
-                    "rb" => {
-                        let mut script = String::new();
-                        try!(file.read_to_string(&mut script));
+    /// Returns the mruby `Class` of a `Value` as a `Value`. Singleton classes are skipped, like
+    /// Ruby's `class`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let class = mruby.fixnum(1).class();
+    ///
+    /// assert_eq!(class.to_string(), "Fixnum");
+    /// assert_eq!(class, mruby.run("Fixnum").unwrap());
+    /// ```
+    pub fn class(&self) -> Value {
+        unsafe {
+            let mrb = self.mruby.borrow().mrb;
 
-                        self.run(&script)
-                    },
-                    "mrb" => {
-                        let mut script = Vec::new();
-                        try!(file.read_to_end(&mut script));
+            Value::new(self.mruby.clone(), mrb_ext_class_value(mrb_obj_class(mrb, self.value)))
+        }
+    }
 
-                        self.runb(&script)
-                    },
-                    _ => {
-                        Err(MrubyError::Filetype)
-                    }
-                }
-            },
-            None => Err(MrubyError::Filetype)
+    /// Returns whether two `Value`s are instances of the same mruby `Class`, comparing the
+    /// `Class` objects themselves rather than their names.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert!(mruby.fixnum(1).class_eq(&mruby.fixnum(2)));
+    /// assert!(!mruby.fixnum(1).class_eq(&mruby.float(2.0)));
+    /// ```
+    pub fn class_eq(&self, other: &Value) -> bool {
+        unsafe {
+            let mrb = self.mruby.borrow().mrb;
+
+            mrb_obj_class(mrb, self.value) == mrb_obj_class(mrb, other.value)
         }
     }
 
+    /// Returns whether two `Value`s are the same mruby object, like Ruby's `equal?`. Unlike
+    /// `PartialEq`, which calls `==`, this compares identity and does not call into mruby.
+    /// Immediate values like `Fixnum`s and `Symbol`s are identical when they are equal.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let a = mruby.string("a");
+    ///
+    /// assert!(a.identical(&a.clone()));
+    /// assert!(!a.identical(&mruby.string("a")));
+    /// assert_eq!(a, mruby.string("a"));
+    /// assert!(mruby.fixnum(1).identical(&mruby.fixnum(1)));
+    /// ```
     #[inline]
-    fn raise(&self, eclass: &str, message: &str) -> Value {
+    pub fn identical(&self, other: &Value) -> bool {
         unsafe {
-            mrb_ext_raise(self.borrow().mrb, CString::new(eclass).unwrap().as_ptr(),
-                          CString::new(message).unwrap().as_ptr());
+            mrb_obj_eq(self.mruby.borrow().mrb, self.value, other.value)
+        }
+    }
 
-            self.nil()
+    /// Returns whether a `Value` is `nil`. Unlike calling `nil?`, this does not call into mruby
+    /// and it does not mistake `false` for `nil`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert!(mruby.nil().is_nil());
+    /// assert!(!mruby.bool(false).is_nil());
+    /// ```
+    #[inline]
+    pub fn is_nil(&self) -> bool {
+        unsafe {
+            mrb_ext_is_nil(self.value)
+        }
+    }
+
+    /// Returns whether a `Value` is an instance of `class` or of one of its subclasses, or
+    /// whether it includes the module `class`. Nested names like `"A::B"` are supported.
+    /// Undefined classes always return `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let one = mruby.fixnum(1);
+    ///
+    /// assert!(one.is_a("Fixnum"));
+    /// assert!(one.is_a("Comparable"));
+    /// assert!(!one.is_a("String"));
+    /// assert!(!one.is_a("Nope"));
+    /// ```
+    #[inline]
+    pub fn is_a(&self, class: &str) -> bool {
+        unsafe {
+            let mrb = self.mruby.borrow().mrb;
+
+            let class = mrb_ext_class_path(mrb, CString::new(class).unwrap().as_ptr());
+
+            !class.is_null() && mrb_obj_is_kind_of(mrb, self.value, class)
         }
     }
 
+    /// Returns whether a `Value` is of `Class` `Symbol`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert!(mruby.symbol("a").is_symbol());
+    /// assert!(!mruby.string("a").is_symbol());
+    /// ```
+    #[inline]
+    pub fn is_symbol(&self) -> bool {
+        self.value.typ == MrType::MRB_TT_SYMBOL
+    }
+
+    /// Returns whether a `Value` is of `Class` `Array`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert!(mruby.array(vec![]).is_array());
+    /// assert!(!mruby.nil().is_array());
+    /// ```
+    #[inline]
+    pub fn is_array(&self) -> bool {
+        self.value.typ == MrType::MRB_TT_ARRAY
+    }
+
+    /// Returns whether a `Value` is of `Class` `Hash`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert!(mruby.hash(vec![]).is_hash());
+    /// assert!(!mruby.array(vec![]).is_hash());
+    /// ```
+    #[inline]
+    pub fn is_hash(&self) -> bool {
+        self.value.typ == MrType::MRB_TT_HASH
+    }
+
+    /// Returns whether a `Value` is of `Class` `Proc`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert!(mruby.run("proc {}").unwrap().is_proc());
+    /// assert!(!mruby.nil().is_proc());
+    /// ```
     #[inline]
-    fn def_file<T: MrubyFile>(&self, name: &str) {
-        let mut borrow = self.borrow_mut();
-
-        if borrow.files.contains_key(name) {
-            let mut file = borrow.files.get_mut(name).unwrap();
+    pub fn is_proc(&self) -> bool {
+        self.value.typ == MrType::MRB_TT_PROC
+    }
 
-            file.push(T::require);
-        } else {
-            borrow.files.insert(name.to_owned(), vec![T::require]);
+    /// Calls a `Proc` `Value` passing `args`, like `Proc#call`, including `Proc`s created with
+    /// `proc_from`. Returns a `Cast` error if `self` is not a `Proc`. This makes it possible to
+    /// keep blocks passed from mruby and call them later on from Rust.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::*;
+    ///
+    /// # fn main() {
+    /// let mruby = Mruby::new();
+    ///
+    /// let handler = mruby.run("proc { |a, b| a + b }").unwrap();
+    ///
+    /// let result = handler.call_proc(vec![mruby.fixnum(1), mruby.fixnum(2)]).unwrap();
+    ///
+    /// assert_eq!(result.to_i32().unwrap(), 3);
+    /// assert!(mruby.fixnum(1).call_proc(vec![]).is_err());
+    /// # }
+    /// ```
+    pub fn call_proc(&self, args: Vec<Value>) -> Result<Value, MrubyError> {
+        if !self.is_proc() {
+            return Err(MrubyError::Cast("Proc".to_owned()));
         }
-    }
 
-    fn def_class<T: Any>(&self, name: &str) {
         unsafe {
-            let name = name.to_owned();
+            let mrb = self.mruby.borrow().mrb;
 
-            let c_name = CString::new(name.clone()).unwrap();
-            let object = CString::new("Object").unwrap();
-            let object = mrb_class_get(self.borrow().mrb, object.as_ptr());
+            let args: Vec<MrValue> = args.iter().map(|value| value.value).collect();
 
-            let class = mrb_define_class(self.borrow().mrb, c_name.as_ptr(), object);
+            reset_instructions(&self.mruby);
 
-            mrb_ext_set_instance_tt(class, MrType::MRB_TT_DATA);
+            let result = mrb_ext_yield_argv(mrb, self.value, args.len() as i32, args.as_ptr());
 
-            extern "C" fn free<T>(_mrb: *const MrState, ptr: *const u8) {
-                unsafe {
-                    mem::transmute::<*const u8, Rc<T>>(ptr);
-                }
+            match get_exc(mrb) {
+                Some(err) => Err(err),
+                None      => Ok(Value::new(self.mruby.clone(), result))
             }
-
-            let data_type = MrDataType { name: c_name.as_ptr(), free: free::<T> };
-
-            self.borrow_mut().classes.insert(TypeId::of::<T>(), (class, data_type, name));
-            self.borrow_mut().methods.insert(TypeId::of::<T>(), HashMap::new());
-            self.borrow_mut().class_methods.insert(TypeId::of::<T>(), HashMap::new());
         }
-
-        self.def_method::<T, _>("dup", |_mruby, slf| {
-            slf.clone()
-        });
     }
 
-    fn def_method<T: Any, F>(&self, name: &str,
-                             method: F) where F: Fn(MrubyType, Value) -> Value + 'static {
-        {
-            let sym = unsafe {
-                mrb_intern(self.borrow().mrb, name.as_ptr(), name.len())
-            };
-
-            let mut borrow = self.borrow_mut();
-
-            let methods = match borrow.methods.get_mut(&TypeId::of::<T>()) {
-                Some(methods) => methods,
-                None          => panic!("Class not found.")
-            };
-
-            methods.insert(sym, Rc::new(method));
+    /// Keeps a `Value` from being garbage collected until a matching `gc_release`.
+    ///
+    /// A `Value` only keeps its `Mruby` alive, not the mruby object itself. Objects that are
+    /// referenced from mruby, like the contents of a global, are safe, but a block passed to a Rust
+    /// method and stored in Rust is only referenced from Rust after the method returns and can be
+    /// collected by the next garbage collection. Protected `Value`s are added to mruby's GC root
+    /// table, which every collection marks. Protecting a `Value` multiple times needs as many
+    /// `gc_release`s, so independent owners can share it.
+    ///
+    /// Objects created from Rust are also kept in mruby's GC arena, but only until the arena is
+    /// restored, which mruby does after every method call. Use `gc_protect` for `Value`s kept in
+    /// long-lived Rust caches as well.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate mrusty;
+    /// use mrusty::*;
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    ///
+    /// # fn main() {
+    /// let mruby = Mruby::new();
+    ///
+    /// let handlers = Rc::new(RefCell::new(vec![]));
+    /// let registry = handlers.clone();
+    ///
+    /// mruby.def_method_on("Object", "on_event", mrfn!(|mruby, _slf: Value, &block| {
+    ///     block.gc_protect();
+    ///     registry.borrow_mut().push(block);
+    ///
+    ///     mruby.nil()
+    /// })).unwrap();
+    ///
+    /// mruby.run("on_event { |name| name.upcase }").unwrap();
+    /// mruby.full_gc();
+    ///
+    /// let result = handlers.borrow()[0].call_proc(vec![mruby.string("start")]).unwrap();
+    ///
+    /// assert_eq!(result.to_str().unwrap(), "START");
+    ///
+    /// for handler in handlers.borrow_mut().drain(..) {
+    ///     handler.gc_release();
+    /// }
+    /// # }
+    /// ```
+    pub fn gc_protect(&self) {
+        unsafe {
+            mrb_gc_register(self.mruby.borrow().mrb, self.value);
         }
+    }
 
-        extern "C" fn call_method<T: Any>(mrb: *const MrState, slf: MrValue) -> MrValue {
-            unsafe {
-                let ptr = mrb_ext_get_ud(mrb);
-                let mruby = mem::transmute::<*const u8, MrubyType>(ptr);
-
-                let result = {
-                    let value = Value::new(mruby.clone(), slf);
-
-                    let method = {
-                        let borrow = mruby.borrow();
-
-                        let methods = match borrow.methods.get(&TypeId::of::<T>()) {
-                            Some(methods) => methods,
-                            None          => {
-                                return mruby.raise("TypeError", "Class not found.").value
-                            }
-                        };
-
-                        let sym = mrb_ext_get_mid(mrb);
-
-                        match methods.get(&sym) {
-                            Some(method) => method.clone(),
-                            None         => {
-                                return mruby.raise("TypeError", "Method not found.").value
-                            }
-                        }
-                    };
-
-                    match panic::recover(AssertRecoverSafe::new(|| method(mruby.clone(), value).value)) {
-                        Ok(value)  => value,
-                        Err(error) => {
-                            let message = match error.downcast_ref::<&'static str>() {
-                                Some(s) => *s,
-                                None    => match error.downcast_ref::<String>() {
-                                    Some(s) => &s[..],
-                                    None    => ""
-                                }
-                            };
-
-                            mruby.raise("RustPanic", message).value
-                        }
-                    }
-                };
-
-                mem::forget(mruby);
-
-                result
-            }
+    /// Releases one `gc_protect` of a `Value`. Once released as many times as it was protected,
+    /// the `Value` can be garbage collected again unless it is referenced from mruby.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let string = mruby.string("kept");
+    ///
+    /// string.gc_protect();
+    /// mruby.full_gc();
+    ///
+    /// assert_eq!(string.to_str().unwrap(), "kept");
+    ///
+    /// string.gc_release();
+    /// ```
+    pub fn gc_release(&self) {
+        unsafe {
+            mrb_ext_gc_release(self.mruby.borrow().mrb, self.value);
         }
+    }
 
-        let borrow = self.borrow();
-
-        let class = match borrow.classes.get(&TypeId::of::<T>()) {
-            Some(class) => class,
-            None       => panic!("Class not found.")
-        };
+    /// Returns whether a `Value` has a method named `name`. Unlike calling `respond_to?`, this
+    /// never raises and does not consult `respond_to_missing?`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("
+    ///   class Cont
+    ///     def value; 3; end
+    ///   end
+    ///
+    ///   Cont.new
+    /// ").unwrap();
+    ///
+    /// assert!(result.respond_to("value"));
+    /// assert!(!result.respond_to("nope"));
+    /// assert!(mruby.fixnum(1).respond_to("+"));
+    /// ```
+    pub fn respond_to(&self, name: &str) -> bool {
+        let sym = self.mruby.sym(name);
 
         unsafe {
-            mrb_define_method(borrow.mrb, class.0, CString::new(name).unwrap().as_ptr(),
-                              call_method::<T>, 1 << 12);
+            mrb_respond_to(self.mruby.borrow().mrb, self.value, sym)
         }
     }
 
-    fn def_class_method<T: Any, F>(&self, name: &str, method: F)
-        where F: Fn(MrubyType, Value) -> Value + 'static {
-        {
-            let sym = unsafe {
-                mrb_intern(self.borrow().mrb, name.as_ptr(), name.len())
-            };
-
-            let mut borrow = self.borrow_mut();
-
-            let methods = match borrow.class_methods.get_mut(&TypeId::of::<T>()) {
-                Some(methods) => methods,
-                None          => panic!("Class not found.")
-            };
-
-            methods.insert(sym, Rc::new(method));
+    /// Returns the names of the methods a `Value` responds to, like Ruby's `methods`. Useful
+    /// for completing method names in a console.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let methods = mruby.string("hi").methods();
+    ///
+    /// assert!(methods.contains(&"upcase".to_owned()));
+    /// assert!(!methods.contains(&"nope".to_owned()));
+    /// ```
+    pub fn methods(&self) -> Vec<String> {
+        match self.call("methods", vec![]) {
+            Ok(methods) => method_names(methods),
+            Err(_)      => vec![]
         }
+    }
 
-        extern "C" fn call_class_method<T: Any>(mrb: *const MrState, slf: MrValue) -> MrValue {
-            unsafe {
-                let ptr = mrb_ext_get_ud(mrb);
-                let mruby = mem::transmute::<*const u8, MrubyType>(ptr);
-
-                let result = {
-                    let value = Value::new(mruby.clone(), slf);
+    /// Returns a `ValueIter` which lazily pulls the elements of a `Value` that responds to `each`
+    /// through an mruby `Enumerator`. Returns an `Err` if `self` does not respond to `each`.
+    ///
+    /// Iteration ends with `StopIteration` once all elements were yielded. Exceptions raised by
+    /// `each` itself are yielded as an `Err` and end the iteration.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let range = mruby.run("1..3").unwrap();
+    ///
+    /// let mut sum = 0;
+    ///
+    /// for value in range.enumerator().unwrap() {
+    ///     sum += value.unwrap().to_i32().unwrap();
+    /// }
+    ///
+    /// assert_eq!(sum, 6);
+    /// assert!(mruby.fixnum(1).enumerator().is_err());
+    /// ```
+    pub fn enumerator(&self) -> Result<ValueIter, MrubyError> {
+        if !self.respond_to("each") {
+            return Err(MrubyError::Cast("Enumerable".to_owned()));
+        }
 
-                    let method = {
-                        let borrow = mruby.borrow();
+        let enumerator = try!(self.call("to_enum", vec![]));
 
-                        let methods = match borrow.class_methods.get(&TypeId::of::<T>()) {
-                            Some(methods) => methods,
-                            None          => {
-                                return mruby.raise("TypeError", "Class not found.").value
-                            }
-                        };
+        Ok(ValueIter {
+            enumerator: enumerator,
+            done:       false
+        })
+    }
 
-                        let sym = mrb_ext_get_mid(mrb);
+    /// Freezes a `Value`, like Ruby's `freeze`.
+    ///
+    /// *Note:* mruby itself only refuses to modify frozen `String`s. Other frozen objects report
+    /// `frozen?` and cannot be borrowed with `to_obj_mut`, which returns a `MrubyError::Frozen`
+    /// that is raised as `FrozenError` from `def_method_result`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let string = mruby.string("cached");
+    /// string.freeze();
+    ///
+    /// assert!(string.is_frozen());
+    /// assert!(string.call("<<", vec![mruby.string("!")]).is_err());
+    /// ```
+    pub fn freeze(&self) {
+        unsafe {
+            mrb_ext_freeze(self.value);
+        }
+    }
 
-                        match methods.get(&sym) {
-                            Some(method) => method.clone(),
-                            None         => {
-                                return mruby.raise("TypeError", "Method not found.").value
-                            }
-                        }
-                    };
+    /// Returns whether a `Value` is frozen. Immediate values like `Fixnum`s, `Symbol`s and `nil`
+    /// are always frozen.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert!(!mruby.run("[1, 2]").unwrap().is_frozen());
+    /// assert!(mruby.run("[1, 2].freeze").unwrap().is_frozen());
+    /// assert!(mruby.fixnum(1).is_frozen());
+    /// ```
+    pub fn is_frozen(&self) -> bool {
+        unsafe {
+            mrb_ext_is_frozen(self.value)
+        }
+    }
 
-                    match panic::recover(AssertRecoverSafe::new(|| method(mruby.clone(), value).value)) {
-                        Ok(value)  => value,
-                        Err(error) => {
-                            let message = match error.downcast_ref::<&'static str>() {
-                                Some(s) => *s,
-                                None    => match error.downcast_ref::<String>() {
-                                    Some(s) => &s[..],
-                                    None    => ""
-                                }
-                            };
+    /// Returns a shallow copy of a `Value` made by Ruby's `dup`. The copy is a distinct mruby
+    /// object, so mutating it leaves the original untouched, and it is never frozen.
+    ///
+    /// There are three ways of copying a `Value`:
+    ///
+    /// * `Clone::clone` returns another handle to the *same* mruby object
+    /// * `ruby_dup` returns a new mruby object, like `dup`
+    /// * `ruby_clone` returns a new mruby object that also keeps the frozen state and singleton
+    ///   methods, like `clone`
+    ///
+    /// *Note:* Values that cannot be copied, such as `Fixnum`s, `Symbol`s and `nil`, are returned
+    /// as they are, just like objects of `Class`es defined with `def_class`, whose `dup` returns
+    /// the object itself since the Rust object cannot be copied.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let original = mruby.string("a");
+    ///
+    /// let same = original.clone();
+    /// let copy = original.ruby_dup();
+    ///
+    /// copy.call("<<", vec![mruby.string("b")]).unwrap();
+    /// same.call("<<", vec![mruby.string("c")]).unwrap();
+    ///
+    /// assert_eq!(original.to_str().unwrap(), "ac");
+    /// assert_eq!(copy.to_str().unwrap(), "ab");
+    /// assert_eq!(mruby.fixnum(1).ruby_dup().to_i32().unwrap(), 1);
+    /// ```
+    pub fn ruby_dup(&self) -> Value {
+        self.call("dup", vec![]).unwrap_or_else(|_| self.clone())
+    }
 
-                            mruby.raise("RustPanic", message).value
-                        }
-                    }
-                };
+    /// Returns a shallow copy of a `Value` made by Ruby's `clone`. Unlike `ruby_dup`, the copy
+    /// keeps the frozen state and singleton methods of the original. See `ruby_dup` for how this
+    /// differs from `Clone::clone`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// let original = mruby.run("
+    ///   list = [1, 2]
+    ///
+    ///   def list.total
+    ///     sum = 0
+    ///     each { |n| sum += n }
+    ///     sum
+    ///   end
+    ///
+    ///   list.freeze
+    /// ").unwrap();
+    ///
+    /// let copy = original.ruby_clone();
+    ///
+    /// assert!(copy.is_frozen());
+    /// assert_eq!(copy.call("total", vec![]).unwrap().to_i32().unwrap(), 3);
+    /// assert!(!original.ruby_dup().is_frozen());
+    /// ```
+    pub fn ruby_clone(&self) -> Value {
+        match self.call("clone", vec![]) {
+            Ok(copy) => {
+                // mruby's clone does not copy the frozen flag.
+                if self.is_frozen() {
+                    copy.freeze();
+                }
 
-                mem::forget(mruby);
+                copy
+            },
+            Err(_) => self.clone()
+        }
+    }
 
-                result
-            }
+    /// Returns the result of calling `inspect` on a `Value` as a `String`. This never fails: if
+    /// `inspect` raises an mruby `Exception`, or if it is called again while an `inspect` is
+    /// already running (e.g. from a Rust method called by a custom `inspect`), a `#<ClassName>`
+    /// placeholder is returned instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.string("hi").inspect(), "\"hi\"");
+    ///
+    /// let result = mruby.run("
+    ///   class Broken
+    ///     def inspect
+    ///       fail 'broken'
+    ///     end
+    ///   end
+    ///
+    ///   Broken.new
+    /// ").unwrap();
+    ///
+    /// assert_eq!(result.inspect(), "#<Broken>");
+    /// ```
+    pub fn inspect(&self) -> String {
+        if self.mruby.borrow().inspecting {
+            return self.placeholder()
         }
 
-        let borrow = self.borrow();
+        self.mruby.borrow_mut().inspecting = true;
 
-        let class = match borrow.classes.get(&TypeId::of::<T>()) {
-            Some(class) => class,
-            None       => panic!("Class not found.")
-        };
+        let result = self.call("inspect", vec![]);
 
-        unsafe {
-            mrb_define_class_method(borrow.mrb, class.0, CString::new(name).unwrap().as_ptr(),
-                                    call_class_method::<T>, 1 << 12);
+        self.mruby.borrow_mut().inspecting = false;
+
+        match result.as_ref().map(|result| result.to_str()) {
+            Ok(Ok(string)) => string.to_owned(),
+            _              => self.placeholder()
         }
     }
 
-    #[inline]
-    fn class_name<T: Any>(&self) -> Result<String, MrubyError> {
-        let borrow = self.borrow();
+    fn placeholder(&self) -> String {
+        unsafe {
+            let name = CStr::from_ptr(mrb_obj_classname(self.mruby.borrow().mrb, self.value));
 
-        match borrow.classes.get(&TypeId::of::<T>()) {
-            Some(class) => Ok(class.2.clone()),
-            None        => Err(MrubyError::Undef)
+            format!("#<{}>", name.to_string_lossy())
         }
     }
 
+    /// Casts a `Value` and returns a `bool` in an `Ok` or an `Err` if the types mismatch.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("
+    ///   def pos(n)
+    ///     n > 0
+    ///   end
+    ///
+    ///   pos 1
+    /// ").unwrap();
+    ///
+    /// assert_eq!(result.to_bool().unwrap(), true);
+    /// ```
     #[inline]
-    fn nil(&self) -> Value {
+    pub fn to_bool(&self) -> Result<bool, MrubyError> {
         unsafe {
-            Value::new(self.clone(), MrValue::nil())
+            self.value.to_bool()
         }
     }
 
+    /// Casts a `Value` and returns an `i32` in an `Ok` or an `Err` if the types mismatch.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("
+    ///   def fact(n)
+    ///     n > 1 ? fact(n - 1) * n : 1
+    ///   end
+    ///
+    ///   fact 5
+    /// ").unwrap();
+    ///
+    /// assert_eq!(result.to_i32().unwrap(), 120);
+    /// ```
     #[inline]
-    fn bool(&self, value: bool) -> Value {
+    pub fn to_i32(&self) -> Result<i32, MrubyError> {
         unsafe {
-            Value::new(self.clone(), MrValue::bool(value))
+            self.value.to_i32()
         }
     }
 
+    /// Casts a `Value` and returns an `i64` in an `Ok` or an `Err` if the types mismatch. Unlike
+    /// `to_i32`, the value is read at the full width of mruby's `Fixnum`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("
+    ///   -2 ** 30
+    /// ").unwrap();
+    ///
+    /// assert_eq!(result.to_i64().unwrap(), -1073741824);
+    /// ```
     #[inline]
-    fn fixnum(&self, value: i32) -> Value {
+    pub fn to_i64(&self) -> Result<i64, MrubyError> {
         unsafe {
-            Value::new(self.clone(), MrValue::fixnum(value))
+            self.value.to_i64()
         }
     }
 
-    #[inline]
-    fn float(&self, value: f64) -> Value {
-        unsafe {
-            Value::new(self.clone(), MrValue::float(self.borrow().mrb, value))
+    /// Returns the decimal digits of an integer `Value` as a `String`, including integers too
+    /// large for `to_i64`. Returns an `Err` for other `Value`s, including non-integral `Float`s.
+    ///
+    /// *Note:* mruby is built without `Bignum`, so integers overflowing `Fixnum` become `Float`s
+    /// and there is no native integer wider than `i64` to convert to, e.g. an `i128`. Their
+    /// digits are exact as far as the `Float` holds them: every integer up to `2 ** 53` and
+    /// powers of two such as `2 ** 100`. `Integer`s of other classes, e.g. from a bignum gem,
+    /// are converted with `to_s`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("2 ** 100").unwrap();
+    ///
+    /// assert!(result.to_i64().is_err());
+    /// assert_eq!(result.to_bignum_string().unwrap(), "1267650600228229401496703205376");
+    /// assert_eq!(mruby.fixnum(-12).to_bignum_string().unwrap(), "-12");
+    /// assert!(mruby.float(1.5).to_bignum_string().is_err());
+    /// ```
+    pub fn to_bignum_string(&self) -> Result<String, MrubyError> {
+        if self.is_a("Integer") {
+            let string = try!(self.call("to_s", vec![]));
+
+            return string.to_str().map(|s| s.to_owned());
+        }
+
+        match self.to_f64() {
+            Ok(float) if float.is_finite() && float.fract() == 0.0 => Ok(format!("{:.0}", float)),
+            _ => Err(MrubyError::Cast("Integer".to_owned()))
         }
     }
 
+    /// Casts a `Value` and returns an `f64` in an `Ok` or an `Err` if the types mismatch.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("
+    ///   3 / 2.0
+    /// ").unwrap();
+    ///
+    /// assert_eq!(result.to_f64().unwrap(), 1.5);
+    /// ```
     #[inline]
-    fn string(&self, value: &str) -> Value {
+    pub fn to_f64(&self) -> Result<f64, MrubyError> {
         unsafe {
-            Value::new(self.clone(), MrValue::string(self.borrow().mrb, value))
+            self.value.to_f64()
         }
     }
 
+    /// Casts a `Value` and returns an `f32` in an `Ok` or an `Err` if the types mismatch or if
+    /// the `Float` is too large for an `f32`. Precision beyond that of an `f32` is rounded away,
+    /// while infinities and `NaN` are kept.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.run("3 / 2.0").unwrap().to_f32().unwrap(), 1.5);
+    /// assert!(mruby.run("1e300").unwrap().to_f32().is_err());
+    /// ```
     #[inline]
-    fn symbol(&self, value: &str) -> Value {
-        unsafe {
-            Value::new(self.clone(), MrValue::symbol(self.borrow().mrb, value))
+    pub fn to_f32(&self) -> Result<f32, MrubyError> {
+        let value = try!(self.to_f64());
+        let narrowed = value as f32;
+
+        if narrowed.is_infinite() && value.is_finite() {
+            Err(MrubyError::Cast("Float in the range of f32".to_owned()))
+        } else {
+            Ok(narrowed)
         }
     }
 
-    #[inline]
-    fn obj<T: Any>(&self, obj: T) -> Value {
-        let borrow = self.borrow();
+    /// Casts a `Value` of `Class` `Time` and returns a `SystemTime` in an `Ok` or an `Err` if the
+    /// types mismatch. The microseconds of the `Time` are kept.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("Time.at(1_000_000_000, 500_000)").unwrap();
+    ///
+    /// assert_eq!(result.to_systemtime().unwrap(),
+    ///            UNIX_EPOCH + Duration::new(1_000_000_000, 500_000_000));
+    /// assert!(mruby.float(1.5).to_systemtime().is_err());
+    /// ```
+    pub fn to_systemtime(&self) -> Result<SystemTime, MrubyError> {
+        if !self.is_a("Time") {
+            return Err(MrubyError::Cast("Time".to_owned()))
+        }
 
-        let class = match borrow.classes.get(&TypeId::of::<T>()) {
-            Some(class) => class,
-            None       => panic!("Class not found.")
+        let secs = try!(try!(self.call("to_i", vec![])).to_i64());
+        let usecs = try!(try!(self.call("usec", vec![])).to_i64());
+
+        // mruby keeps usec positive, so times before the epoch have a negative to_i.
+        let time = if secs >= 0 {
+            UNIX_EPOCH + Duration::new(secs as u64, 0)
+        } else {
+            UNIX_EPOCH - Duration::new(secs.wrapping_neg() as u64, 0)
         };
 
-        unsafe {
-            Value::new(self.clone(), MrValue::obj(borrow.mrb, class.0 as *const MrClass, obj,
-                                                  &class.1))
-        }
+        Ok(time + Duration::new(0, usecs as u32 * 1000))
     }
 
+    /// Casts a `Value` and returns a `&str` in an `Ok` or an `Err` if the types mismatch or if
+    /// the `String` is not valid UTF-8. Use `to_bytes` for binary data.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("
+    ///   [1, 2, 3].map(&:to_s).join
+    /// ").unwrap();
+    ///
+    /// assert_eq!(result.to_str().unwrap(), "123");
+    /// ```
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run(":symbol").unwrap();
+    ///
+    /// assert_eq!(result.to_str().unwrap(), "symbol");
+    /// ```
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("\"\\xff\"").unwrap();
+    ///
+    /// assert!(result.to_str().is_err());
+    /// ```
     #[inline]
-    fn option<T: Any>(&self, obj: Option<T>) -> Value {
-        match obj {
-            Some(obj) => self.obj(obj),
-            None      => self.nil()
+    pub fn to_str<'a>(&self) -> Result<&'a str, MrubyError> {
+        unsafe {
+            self.value.to_str(self.mruby.borrow().mrb)
         }
     }
 
-    #[inline]
-    fn array(&self, value: Vec<Value>) -> Value {
-        let array: Vec<MrValue> = value.iter().map(|value| {
-            value.value
-        }).collect();
+    /// Casts mruby `Value` of `Class` `Symbol` and returns its name as a `&str`. Unlike `to_str`,
+    /// returns an `Err` for `String`s.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.run(":read").unwrap().to_sym_name().unwrap(), "read");
+    /// assert!(mruby.string("read").to_sym_name().is_err());
+    /// ```
+    pub fn to_sym_name<'a>(&self) -> Result<&'a str, MrubyError> {
+        if !self.is_symbol() {
+            return Err(MrubyError::Cast("Symbol".to_owned()));
+        }
 
-        unsafe {
-            Value::new(self.clone(), MrValue::array(self.borrow().mrb, array))
+        self.to_str()
+    }
+
+    /// Casts mruby `Value` of `Class` `String` and returns a `&str`. Unlike `to_str`, returns an
+    /// `Err` for `Symbol`s.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.string("read").to_string_only().unwrap(), "read");
+    /// assert!(mruby.run(":read").unwrap().to_string_only().is_err());
+    /// ```
+    pub fn to_string_only<'a>(&self) -> Result<&'a str, MrubyError> {
+        if self.is_symbol() {
+            return Err(MrubyError::Cast("String".to_owned()));
         }
-    }
-}
 
-impl Drop for Mruby {
-    fn drop(&mut self) {
-        self.close();
+        self.to_str()
     }
-}
 
-/// A `struct` that wraps around any mruby variable.
-///
-/// `Values` are created from the `Mruby` instance:
-///
-/// * [`nil`](../mrusty/trait.MrubyImpl.html#tymethod.nil)
-/// * [`bool`](../mrusty/trait.MrubyImpl.html#tymethod.bool)
-/// * [`fixnum`](../mrusty/trait.MrubyImpl.html#tymethod.fixnum)
-/// * [`float`](../mrusty/trait.MrubyImpl.html#tymethod.float)
-/// * [`string`](../mrusty/trait.MrubyImpl.html#tymethod.string)
-/// * [`obj`](../mrusty/trait.MrubyImpl.html#tymethod.obj)
-/// * [`option`](../mrusty/trait.MrubyImpl.html#tymethod.option)
-/// * [`array`](../mrusty/trait.MrubyImpl.html#tymethod.array)
-///
-/// # Examples
-///
-/// ```
-/// # use mrusty::Mruby;
-/// # use mrusty::MrubyImpl;
-/// let mruby = Mruby::new();
-/// let result = mruby.run("true").unwrap(); // Value
-///
-/// // Values need to be unwrapped in order to make sure they have the right mruby type.
-/// assert_eq!(result.to_bool().unwrap(), true);
-/// ```
-pub struct Value {
-    mruby: MrubyType,
-    value: MrValue
-}
+    /// Casts mruby `Value` of `Class` `String` containing exactly one Unicode scalar value and
+    /// returns it as a `char`. Empty or longer `String`s return an `Err`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.run("'ab'[1]").unwrap().to_char().unwrap(), 'b');
+    /// assert!(mruby.string("ab").to_char().is_err());
+    /// assert!(mruby.string("").to_char().is_err());
+    /// ```
+    pub fn to_char(&self) -> Result<char, MrubyError> {
+        let string = try!(self.to_str());
+        let mut chars = string.chars();
 
-impl Value {
-    /// Not meant to be called directly.
-    #[doc(hidden)]
-    pub fn new(mruby: MrubyType, value: MrValue) -> Value {
-        Value {
-            mruby: mruby,
-            value: value
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _               => Err(MrubyError::Cast("String of one character".to_owned()))
         }
     }
 
-    /// Initializes the `self` mruby object passed to `initialize` with a Rust object of type `T`.
+    /// Casts mruby `Value` of `Class` `String` and returns its raw bytes without copying them.
+    /// Unlike `to_str`, the bytes do not need to be valid UTF-8.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("\"a\\x00\\xff\"").unwrap();
+    ///
+    /// assert_eq!(result.to_bytes().unwrap(), &[b'a', 0, 0xff]);
+    /// ```
+    #[inline]
+    pub fn to_bytes(&self) -> Result<&[u8], MrubyError> {
+        unsafe {
+            self.value.to_bytes()
+        }
+    }
+
+    /// Casts mruby `Value` of `Class` `name` to Rust type `Rc<T>`.
     ///
     /// *Note:* `T` must be defined on the current `Mruby` with `def_class`.
     ///
     /// # Examples
     ///
     /// ```
-    /// # #[macro_use] extern crate mrusty;
-    /// use mrusty::*;
-    ///
-    /// # fn main() {
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
     /// let mruby = Mruby::new();
     ///
     /// struct Cont {
     ///     value: i32
-    /// };
+    /// }
     ///
     /// mruby.def_class::<Cont>("Container");
-    /// mruby.def_method::<Cont, _>("initialize", mrfn!(|_mruby, slf: Value, v: i32| {
-    ///     let cont = Cont { value: v };
     ///
-    ///     slf.init(cont) // Return the same slf value.
-    /// }));
-    ///
-    /// let result = mruby.run("Container.new 3").unwrap();
+    /// let value = mruby.obj(Cont { value: 3 });
+    /// let cont = value.to_obj::<Cont>().unwrap();
     ///
-    /// assert_eq!(result.to_obj::<Cont>().unwrap().value, 3);
-    /// # }
+    /// assert_eq!(cont.value, 3);
     /// ```
-    pub fn init<T: Any>(self, obj: T) -> Value {
+    #[inline]
+    pub fn to_obj<T: Any>(&self) -> Result<Rc<T>, MrubyError> {
         unsafe {
-            let rc = Rc::new(obj);
-            let ptr = mem::transmute::<Rc<T>, *const u8>(rc);
-
             let borrow = self.mruby.borrow();
 
             let class = match borrow.classes.get(&TypeId::of::<T>()) {
                 Some(class) => class,
-                None       => panic!("Class not found.")
+                None        => {
+                    return Err(MrubyError::Undef)
+                }
             };
 
-            let data_type = &class.1;
+            let class_name = self.type_name();
 
-            mrb_ext_data_init(&self.value as *const MrValue, ptr, data_type as *const MrDataType);
+            if class_name != class.2 {
+                return Err(MrubyError::Undef)
+            }
+
+            self.value.to_obj::<T>(borrow.mrb, &class.1)
         }
+    }
 
-        self
+    /// Casts mruby `Value` of a `Class` defined with `def_class_dyn` to Rust type `Rc<T>`.
+    /// Returns `Undef` if the `Value` is not an object of such a `Class` and `Cast` if the
+    /// object holds a Rust type other than `T`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::*;
+    /// let mruby = Mruby::new();
+    ///
+    /// mruby.def_class_dyn("plugin", "Plugin");
+    ///
+    /// let value = mruby.obj_dyn("plugin", vec![1, 2]);
+    ///
+    /// assert_eq!(*value.to_obj_dyn::<Vec<i32>>().unwrap(), vec![1, 2]);
+    ///
+    /// match value.to_obj_dyn::<String>() {
+    ///     Err(MrubyError::Cast(expected)) => assert_eq!(expected, "Plugin of another Rust type"),
+    ///     _ => assert!(false)
+    /// }
+    ///
+    /// match mruby.fixnum(1).to_obj_dyn::<String>() {
+    ///     Err(MrubyError::Undef) => (),
+    ///     _ => assert!(false)
+    /// }
+    /// ```
+    pub fn to_obj_dyn<T: Any>(&self) -> Result<Rc<T>, MrubyError> {
+        let obj = unsafe {
+            let borrow = self.mruby.borrow();
+
+            let class_name = self.type_name();
+
+            let class = match borrow.dyn_classes.values().find(|class| class.2 == class_name) {
+                Some(class) => class,
+                None        => return Err(MrubyError::Undef)
+            };
+
+            try!(self.value.to_obj::<Rc<Any>>(borrow.mrb, &class.1))
+        };
+
+        let class_name = self.type_name();
+
+        (*obj).clone().downcast::<T>().map_err(|_| {
+            MrubyError::Cast(format!("{} of another Rust type", class_name))
+        })
     }
 
-    /// Calls method `name` on a `Value` passing `args`.
+    /// Casts mruby `Value` of a `Class` defined with `def_class_cell` and mutably borrows its
+    /// Rust object `T`. Returns a `MrubyError::Frozen` if the object is frozen. The returned
+    /// `ObjMut` keeps `T` alive, even if mruby collects the object while it is borrowed.
+    ///
+    /// *Note:* Like `RefCell::borrow_mut`, this panics if the object is already borrowed, e.g. when
+    /// the same object is passed twice to a method.
     ///
     /// # Examples
     ///
     /// ```
     /// # use mrusty::Mruby;
     /// # use mrusty::MrubyImpl;
+    /// use std::cell::RefCell;
+    ///
     /// let mruby = Mruby::new();
     ///
-    /// let one = mruby.fixnum(1);
-    /// let result = one.call("+", vec![mruby.fixnum(2)]).unwrap();
+    /// struct Cont {
+    ///     value: i32
+    /// }
     ///
-    /// assert_eq!(result.to_i32().unwrap(), 3);
+    /// mruby.def_class_cell::<Cont>("Container");
+    ///
+    /// let value = mruby.obj(RefCell::new(Cont { value: 3 }));
+    /// value.to_obj_mut::<Cont>().unwrap().value = 4;
+    ///
+    /// assert_eq!(value.to_obj::<RefCell<Cont>>().unwrap().borrow().value, 4);
+    /// assert!(mruby.fixnum(1).to_obj_mut::<Cont>().is_err());
     /// ```
-    pub fn call(&self, name: &str, args: Vec<Value>) -> Result<Value, MrubyError> {
-        unsafe {
-            let sym = mrb_intern(self.mruby.borrow().mrb, name.as_ptr(), name.len());
+    pub fn to_obj_mut<T: Any>(&self) -> Result<ObjMut<T>, MrubyError> {
+        let rc = try!(self.to_obj::<RefCell<T>>());
 
-            let args: Vec<MrValue> = args.iter().map(|value| value.value).collect();
+        if self.is_frozen() {
+            return Err(MrubyError::Frozen(format!("can't modify frozen {}", self.type_name())))
+        }
 
-            let result = mrb_funcall_argv(self.mruby.borrow().mrb, self.value, sym,
-                                          args.len() as i32, args.as_ptr());
+        // The borrow points into the heap allocation owned by rc, which ObjMut drops after it.
+        let cell = unsafe { &*(&*rc as *const RefCell<T>) };
 
-            let exc = mrb_ext_get_exc(self.mruby.borrow().mrb);
+        Ok(ObjMut {
+            borrow: cell.borrow_mut(),
+            _rc:    rc
+        })
+    }
 
-            match exc.typ {
-                MrType::MRB_TT_FALSE => {
-                    Ok(Value::new(self.mruby.clone(), result))
-                },
-                _  => Err(MrubyError::Runtime(exc.to_str(self.mruby.borrow().mrb).unwrap()
-                                                 .to_owned()))
-            }
+    /// Casts mruby `Value` of `Class` `name` to Rust `Option` of `Rc<T>`.
+    ///
+    /// *Note:* `T` must be defined on the current `Mruby` with `def_class`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// struct Cont {
+    ///     value: i32
+    /// }
+    ///
+    /// mruby.def_class::<Cont>("Container");
+    ///
+    /// let value = mruby.obj(Cont { value: 3 });
+    /// let cont = value.to_option::<Cont>().unwrap();
+    ///
+    /// assert_eq!(cont.unwrap().value, 3);
+    /// assert!(mruby.nil().to_option::<Cont>().unwrap().is_none());
+    /// ```
+    #[inline]
+    pub fn to_option<T: Any>(&self) -> Result<Option<Rc<T>>, MrubyError> {
+        if self.value.typ == MrType::MRB_TT_DATA {
+            self.to_obj::<T>().map(|obj| Some(obj))
+        } else {
+            Ok(None)
         }
     }
 
-    /// Calls method `name` on a `Value` passing `args`. If call fails, mruby will be left to
-    /// handle the exception.
+    /// Returns the raw data pointer (`DATA_PTR`) of an mruby `Data` object, or a null pointer if
+    /// the `Value` is not a `Data` object. Useful when bridging with C libraries that wrap their
+    /// own structs in mruby objects.
+    ///
+    /// *Note:* This is `unsafe` because nothing checks what the pointer points to. It is only
+    /// valid while the object is alive, i.e. while a `Value` referencing it lives, and must only
+    /// be cast to the type the object was created with. For `Class`es defined with `def_class`
+    /// the pointer is the one a `Rc<T>` was turned into, which is still owned by the object.
     ///
     /// # Examples
     ///
     /// ```
     /// # use mrusty::Mruby;
     /// # use mrusty::MrubyImpl;
+    /// use std::mem;
+    /// use std::os::raw::c_void;
+    /// use std::rc::Rc;
+    ///
     /// let mruby = Mruby::new();
     ///
-    /// let one = mruby.string("");
-    /// one.call("+", vec![mruby.fixnum(1)]);
+    /// struct Cont {
+    ///     value: i32
+    /// }
+    ///
+    /// mruby.def_class::<Cont>("Container");
+    ///
+    /// let value = mruby.obj(Cont { value: 3 });
+    ///
+    /// unsafe {
+    ///     let rc = mem::transmute::<*const c_void, Rc<Cont>>(value.data_ptr());
+    ///
+    ///     assert_eq!(rc.value, 3);
+    ///
+    ///     mem::forget(rc);
+    ///
+    ///     assert!(mruby.fixnum(1).data_ptr().is_null());
+    /// }
     /// ```
-    pub fn call_unchecked(&self, name: &str, args: Vec<Value>) -> Value {
-        unsafe {
-            let sym = mrb_intern(self.mruby.borrow().mrb, name.as_ptr(), name.len());
-
-            let args: Vec<MrValue> = args.iter().map(|value| value.value).collect();
+    pub unsafe fn data_ptr(&self) -> *const c_void {
+        match self.value.typ {
+            MrType::MRB_TT_DATA => mrb_ext_data_ptr(self.value) as *const c_void,
+            _                   => ptr::null()
+        }
+    }
 
-            let result = mrb_funcall_argv(self.mruby.borrow().mrb, self.value, sym,
-                                          args.len() as i32, args.as_ptr());
+    /// Casts mruby `Value` of `Class` `String` to Rust `Option<String>`, with `nil` becoming
+    /// `None`. This is the same as `FromValue` for `Option<String>`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    ///
+    /// assert_eq!(mruby.string("Ann").to_string_option().unwrap(), Some("Ann".to_owned()));
+    /// assert_eq!(mruby.nil().to_string_option().unwrap(), None);
+    /// assert!(mruby.fixnum(1).to_string_option().is_err());
+    /// ```
+    #[inline]
+    pub fn to_string_option(&self) -> Result<Option<String>, MrubyError> {
+        Option::<String>::from_value(self)
+    }
 
-            Value::new(self.mruby.clone(), result)
+    /// Casts mruby `Value` of `Class` `Array` to Rust type `Vec<Value>`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyImpl;
+    /// let mruby = Mruby::new();
+    /// let result = mruby.run("
+    ///   [1, 2, 3].map(&:to_s)
+    /// ").unwrap();
+    ///
+    /// assert_eq!(result.to_vec().unwrap(), vec![
+    ///     mruby.string("1"),
+    ///     mruby.string("2"),
+    ///     mruby.string("3")
+    /// ]);
+    /// ```
+    #[inline]
+    pub fn to_vec(&self) -> Result<Vec<Value>, MrubyError> {
+        unsafe {
+            self.value.to_vec(self.mruby.borrow().mrb).map(|vec| {
+                vec.iter().map(|mrvalue| {
+                    Value::new(self.mruby.clone(), *mrvalue)
+                }).collect()
+            })
         }
     }
 
-    /// Returns the name of the mruby `Class` as a `&str`.
+    /// Casts mruby `Value` of `Class` `Array` to Rust type `Vec<T>`, converting each element with
+    /// `FromValue`. Stops at the first element that does not convert and returns a `Cast` error
+    /// naming its index.
     ///
     /// # Examples
     ///
     /// ```
     /// # use mrusty::Mruby;
+    /// # use mrusty::MrubyError;
     /// # use mrusty::MrubyImpl;
     /// let mruby = Mruby::new();
     ///
-    /// let one = mruby.run("1").unwrap();
-    /// assert_eq!(one.type_name(), "Fixnum");
+    /// let numbers = mruby.run("[1, 2, 3]").unwrap().to_vec_of::<i32>().unwrap();
+    ///
+    /// assert_eq!(numbers, vec![1, 2, 3]);
+    ///
+    /// match mruby.run("[1, 'two', 3]").unwrap().to_vec_of::<i32>() {
+    ///     Err(MrubyError::Cast(expected)) => assert_eq!(expected, "Fixnum at index 1"),
+    ///     _ => assert!(false)
+    /// }
     /// ```
-    pub fn type_name(&self) -> &str {
-        let string = self.call_unchecked("class", vec![]).call_unchecked("to_s", vec![]);
-
-        string.to_str().unwrap()
+    pub fn to_vec_of<T: FromValue>(&self) -> Result<Vec<T>, MrubyError> {
+        let vec = try!(self.to_vec());
+
+        vec.iter().enumerate().map(|(i, value)| {
+            T::from_value(value).map_err(|err| {
+                match err {
+                    MrubyError::Cast(expected) => {
+                        MrubyError::Cast(format!("{} at index {}", expected, i))
+                    },
+                    err => err
+                }
+            })
+        }).collect()
     }
 
-    /// Casts a `Value` and returns a `bool` in an `Ok` or an `Err` if the types mismatch.
+    /// Calls `f` with every element of an mruby `Value` of `Class` `Array` without collecting
+    /// them in a `Vec`. Returns an `Err` if `self` is not an `Array`.
     ///
-    /// # Example
+    /// # Examples
     ///
     /// ```
     /// # use mrusty::Mruby;
     /// # use mrusty::MrubyImpl;
     /// let mruby = Mruby::new();
-    /// let result = mruby.run("
-    ///   def pos(n)
-    ///     n > 0
-    ///   end
+    /// let result = mruby.run("[1, 2, 3]").unwrap();
     ///
-    ///   pos 1
-    /// ").unwrap();
+    /// let mut sum = 0;
     ///
-    /// assert_eq!(result.to_bool().unwrap(), true);
+    /// result.each(|value| sum += value.to_i32().unwrap()).unwrap();
+    ///
+    /// assert_eq!(sum, 6);
+    /// assert!(mruby.fixnum(1).each(|_| ()).is_err());
     /// ```
-    #[inline]
-    pub fn to_bool(&self) -> Result<bool, MrubyError> {
-        unsafe {
-            self.value.to_bool()
+    pub fn each<F: FnMut(Value)>(&self, mut f: F) -> Result<(), MrubyError> {
+        if self.value.typ != MrType::MRB_TT_ARRAY {
+            return Err(MrubyError::Cast("Array".to_owned()));
+        }
+
+        let mut i = 0;
+
+        loop {
+            // The length is read again since f can change the Array.
+            let value = unsafe {
+                let mrb = self.mruby.borrow().mrb;
+
+                if i >= mrb_ext_ary_len(mrb, self.value) {
+                    break;
+                }
+
+                mrb_ary_ref(mrb, self.value, i)
+            };
+
+            f(Value::new(self.mruby.clone(), value));
+
+            i += 1;
         }
+
+        Ok(())
     }
 
-    /// Casts a `Value` and returns an `i32` in an `Ok` or an `Err` if the types mismatch.
+    /// Returns the length of an mruby `Value` of `Class` `Array`, `String` (in bytes) or `Hash`.
     ///
-    /// # Example
+    /// # Examples
     ///
     /// ```
     /// # use mrusty::Mruby;
     /// # use mrusty::MrubyImpl;
     /// let mruby = Mruby::new();
-    /// let result = mruby.run("
-    ///   def fact(n)
-    ///     n > 1 ? fact(n - 1) * n : 1
-    ///   end
-    ///
-    ///   fact 5
-    /// ").unwrap();
     ///
-    /// assert_eq!(result.to_i32().unwrap(), 120);
+    /// assert_eq!(mruby.run("[1, 2, 3]").unwrap().len().unwrap(), 3);
+    /// assert_eq!(mruby.string("hello").len().unwrap(), 5);
+    /// assert_eq!(mruby.run("{ a: 1 }").unwrap().len().unwrap(), 1);
+    /// assert!(mruby.nil().len().is_err());
     /// ```
     #[inline]
-    pub fn to_i32(&self) -> Result<i32, MrubyError> {
+    pub fn len(&self) -> Result<usize, MrubyError> {
         unsafe {
-            self.value.to_i32()
+            let mrb = self.mruby.borrow().mrb;
+
+            match self.value.typ {
+                MrType::MRB_TT_ARRAY  => Ok(mrb_ext_ary_len(mrb, self.value) as usize),
+                MrType::MRB_TT_STRING => Ok(mrb_ext_str_len(self.value) as usize),
+                MrType::MRB_TT_HASH   => Ok(mrb_ext_hash_len(mrb, self.value) as usize),
+                _ => Err(MrubyError::Cast("Array, String or Hash".to_owned()))
+            }
         }
     }
 
-    /// Casts a `Value` and returns an `f64` in an `Ok` or an `Err` if the types mismatch.
+    /// Returns whether an mruby `Value` of `Class` `Array`, `String` or `Hash` is empty.
     ///
-    /// # Example
+    /// # Examples
     ///
     /// ```
     /// # use mrusty::Mruby;
     /// # use mrusty::MrubyImpl;
     /// let mruby = Mruby::new();
-    /// let result = mruby.run("
-    ///   3 / 2.0
-    /// ").unwrap();
     ///
-    /// assert_eq!(result.to_f64().unwrap(), 1.5);
+    /// assert!(mruby.run("[]").unwrap().is_empty().unwrap());
+    /// assert!(!mruby.string("hello").is_empty().unwrap());
+    /// assert!(mruby.nil().is_empty().is_err());
     /// ```
     #[inline]
-    pub fn to_f64(&self) -> Result<f64, MrubyError> {
-        unsafe {
-            self.value.to_f64()
-        }
+    pub fn is_empty(&self) -> Result<bool, MrubyError> {
+        self.len().map(|len| len == 0)
     }
 
-    /// Casts a `Value` and returns a `&str` in an `Ok` or an `Err` if the types mismatch.
+    /// Casts mruby `Value` of `Class` `Hash` to Rust type `Vec<(Value, Value)>`.
     ///
-    /// # Example
+    /// # Examples
     ///
     /// ```
     /// # use mrusty::Mruby;
     /// # use mrusty::MrubyImpl;
     /// let mruby = Mruby::new();
     /// let result = mruby.run("
-    ///   [1, 2, 3].map(&:to_s).join
+    ///   { a: 1, 'b' => 2.0 }
     /// ").unwrap();
     ///
-    /// assert_eq!(result.to_str().unwrap(), "123");
-    /// ```
-    ///
-    /// ```
-    /// # use mrusty::Mruby;
-    /// # use mrusty::MrubyImpl;
-    /// let mruby = Mruby::new();
-    /// let result = mruby.run(":symbol").unwrap();
-    ///
-    /// assert_eq!(result.to_str().unwrap(), "symbol");
+    /// assert_eq!(result.to_hash().unwrap(), vec![
+    ///     (mruby.symbol("a"), mruby.fixnum(1)),
+    ///     (mruby.string("b"), mruby.float(2.0))
+    /// ]);
     /// ```
     #[inline]
-    pub fn to_str<'a>(&self) -> Result<&'a str, MrubyError> {
+    pub fn to_hash(&self) -> Result<Vec<(Value, Value)>, MrubyError> {
         unsafe {
-            self.value.to_str(self.mruby.borrow().mrb)
+            self.value.to_hash(self.mruby.borrow().mrb).map(|vec| {
+                vec.iter().map(|&(key, value)| {
+                    (Value::new(self.mruby.clone(), key), Value::new(self.mruby.clone(), value))
+                }).collect()
+            })
         }
     }
 
-    /// Casts mruby `Value` of `Class` `name` to Rust type `Rc<T>`.
-    ///
-    /// *Note:* `T` must be defined on the current `Mruby` with `def_class`.
+    /// Returns a `WeakValue` pointing to the same mruby object without keeping the `Mruby`
+    /// alive. See `WeakValue` for its lifetime implications.
     ///
     /// # Examples
     ///
@@ -1423,42 +6669,19 @@ impl Value {
     /// # use mrusty::MrubyImpl;
     /// let mruby = Mruby::new();
     ///
-    /// struct Cont {
-    ///     value: i32
-    /// }
-    ///
-    /// mruby.def_class::<Cont>("Container");
-    ///
-    /// let value = mruby.obj(Cont { value: 3 });
-    /// let cont = value.to_obj::<Cont>().unwrap();
+    /// let weak = mruby.string("cached").downgrade();
     ///
-    /// assert_eq!(cont.value, 3);
+    /// assert_eq!(weak.upgrade().unwrap().to_str().unwrap(), "cached");
     /// ```
-    #[inline]
-    pub fn to_obj<T: Any>(&self) -> Result<Rc<T>, MrubyError> {
-        unsafe {
-            let borrow = self.mruby.borrow();
-
-            let class = match borrow.classes.get(&TypeId::of::<T>()) {
-                Some(class) => class,
-                None        => {
-                    return Err(MrubyError::Undef)
-                }
-            };
-
-            let class_name = self.type_name();
-
-            if class_name != class.2 {
-                return Err(MrubyError::Undef)
-            }
-
-            self.value.to_obj::<T>(borrow.mrb, &class.1)
+    pub fn downgrade(&self) -> WeakValue {
+        WeakValue {
+            mruby: Rc::downgrade(&self.mruby),
+            value: self.value
         }
     }
 
-    /// Casts mruby `Value` of `Class` `name` to Rust `Option` of `Rc<T>`.
-    ///
-    /// *Note:* `T` must be defined on the current `Mruby` with `def_class`.
+    /// Casts mruby `Value` of `Class` `Range` to its beginning, its end and whether the end is
+    /// excluded.
     ///
     /// # Examples
     ///
@@ -1466,29 +6689,69 @@ impl Value {
     /// # use mrusty::Mruby;
     /// # use mrusty::MrubyImpl;
     /// let mruby = Mruby::new();
+    /// let result = mruby.run("1...3").unwrap();
     ///
-    /// struct Cont {
-    ///     value: i32
-    /// }
-    ///
-    /// mruby.def_class::<Cont>("Container");
-    ///
-    /// let value = mruby.obj(Cont { value: 3 });
-    /// let cont = value.to_option::<Cont>().unwrap();
-    ///
-    /// assert_eq!(cont.unwrap().value, 3);
-    /// assert!(mruby.nil().to_option::<Cont>().unwrap().is_none());
+    /// assert_eq!(result.to_range().unwrap(), (mruby.fixnum(1), mruby.fixnum(3), true));
+    /// assert!(mruby.fixnum(1).to_range().is_err());
     /// ```
-    #[inline]
-    pub fn to_option<T: Any>(&self) -> Result<Option<Rc<T>>, MrubyError> {
-        if self.value.typ == MrType::MRB_TT_DATA {
-            self.to_obj::<T>().map(|obj| Some(obj))
-        } else {
-            Ok(None)
+    pub fn to_range(&self) -> Result<(Value, Value, bool), MrubyError> {
+        if self.value.typ != MrType::MRB_TT_RANGE {
+            return Err(MrubyError::Cast("Range".to_owned()));
+        }
+
+        unsafe {
+            let mut from = MrValue::nil();
+            let mut to = MrValue::nil();
+            let mut exclusive = false;
+
+            mrb_ext_range_edges(self.value, &mut from, &mut to, &mut exclusive);
+
+            Ok((Value::new(self.mruby.clone(), from), Value::new(self.mruby.clone(), to),
+                exclusive))
         }
     }
+}
 
-    /// Casts mruby `Value` of `Class` `Array` to Rust type `Vec<Value>`.
+/// A weak reference to a `Value`, holding a `Weak<RefCell<Mruby>>` instead of a `MrubyType`.
+/// Created with `Value::downgrade`.
+///
+/// Every `Value` keeps its `Mruby` alive, so storing `Value`s inside the `Mruby` itself, e.g. in
+/// its user data or in a closure defined as a method, creates a reference cycle and the `Mruby`
+/// is never dropped. `WeakValue`s break such cycles: `upgrade` returns `None` once every
+/// `MrubyType` and `Value` of the `Mruby` has been dropped.
+///
+/// *Note:* Like a `Value`, a `WeakValue` does not keep its mruby object from being garbage
+/// collected. Objects that are not referenced from mruby should be kept with `gc_protect`.
+///
+/// # Examples
+///
+/// ```
+/// # use mrusty::Mruby;
+/// # use mrusty::MrubyImpl;
+/// let mruby = Mruby::new();
+///
+/// let config = mruby.run("{ debug: true }").unwrap();
+/// config.gc_protect();
+///
+/// let weak = config.downgrade();
+///
+/// mruby.set_user_data(weak.clone());
+///
+/// assert!(weak.upgrade().is_some());
+///
+/// drop(config);
+/// drop(mruby);
+///
+/// assert!(weak.upgrade().is_none());
+/// ```
+#[derive(Clone)]
+pub struct WeakValue {
+    mruby: Weak<RefCell<Mruby>>,
+    value: MrValue
+}
+
+impl WeakValue {
+    /// Returns the `Value` if its `Mruby` is still alive, or `None` otherwise.
     ///
     /// # Examples
     ///
@@ -1496,24 +6759,77 @@ impl Value {
     /// # use mrusty::Mruby;
     /// # use mrusty::MrubyImpl;
     /// let mruby = Mruby::new();
-    /// let result = mruby.run("
-    ///   [1, 2, 3].map(&:to_s)
-    /// ").unwrap();
     ///
-    /// assert_eq!(result.to_vec().unwrap(), vec![
-    ///     mruby.string("1"),
-    ///     mruby.string("2"),
-    ///     mruby.string("3")
-    /// ]);
+    /// let weak = mruby.fixnum(3).downgrade();
+    ///
+    /// assert_eq!(weak.upgrade().unwrap().to_i32().unwrap(), 3);
     /// ```
-    #[inline]
-    pub fn to_vec(&self) -> Result<Vec<Value>, MrubyError> {
+    pub fn upgrade(&self) -> Option<Value> {
+        self.mruby.upgrade().map(|mruby| Value::new(mruby, self.value))
+    }
+}
+
+/// A mutable borrow of the Rust object of an mruby `Value`. Created with `Value::to_obj_mut`.
+///
+/// It holds its own reference to the object, so `T` stays alive while borrowed.
+pub struct ObjMut<T: 'static> {
+    borrow: RefMut<'static, T>,
+    _rc:    Rc<RefCell<T>>
+}
+
+impl<T> Deref for ObjMut<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.borrow
+    }
+}
+
+impl<T> DerefMut for ObjMut<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.borrow
+    }
+}
+
+/// An `Iterator` over the elements of an mruby `Enumerable`. Created with `Value::enumerator`.
+///
+/// Iteration ends when `Enumerator#next` raises `StopIteration`. Any other exception is yielded
+/// once as an `Err`, after which the iterator is exhausted.
+pub struct ValueIter {
+    enumerator: Value,
+    done:       bool
+}
+
+impl Iterator for ValueIter {
+    type Item = Result<Value, MrubyError>;
+
+    fn next(&mut self) -> Option<Result<Value, MrubyError>> {
+        if self.done {
+            return None;
+        }
+
+        let mrb = self.enumerator.mruby.borrow().mrb;
+
+        let value = self.enumerator.call_unchecked("next", vec![]);
+
         unsafe {
-            self.value.to_vec(self.mruby.borrow().mrb).map(|vec| {
-                vec.iter().map(|mrvalue| {
-                    Value::new(self.mruby.clone(), *mrvalue)
-                }).collect()
-            })
+            let stop = mrb_ext_get_exc_of(mrb, CString::new("StopIteration").unwrap().as_ptr());
+
+            if !mrb_ext_is_nil(stop) {
+                mrb_ext_get_exc(mrb);
+                self.done = true;
+
+                return None;
+            }
+
+            match get_exc(mrb) {
+                Some(err) => {
+                    self.done = true;
+
+                    Some(Err(err))
+                },
+                None      => Some(Ok(value))
+            }
         }
     }
 }
@@ -1545,8 +6861,171 @@ impl PartialEq<Value> for Value {
     }
 }
 
+/// A `struct` wrapping a `Value` so that it can key a Rust `HashMap` or `HashSet`.
+///
+/// Unlike `Value`'s `PartialEq`, which is Ruby's `==`, keys are only equal if they also have the
+/// same type, like with `eql?`, so `1` and `1.0` or `[1]` and `[1.0]` are different keys.
+/// (`eql?` itself cannot be used since mruby's `Float#eql?` is broken.) Keys are hashed with
+/// Ruby's `hash` method, or by type only if it raises or does not return a `Fixnum`.
+///
+/// `Float`s are compared by value, except that `NaN` keys are equal to each other. Recursive
+/// `Array`s can be used as keys.
+///
+/// *Note:* Like with Ruby's own `Hash`, mutating an mruby object (e.g. pushing to an `Array`)
+/// changes its hash, so it should not be mutated while used as a key.
+///
+/// # Examples
+///
+/// ```
+/// # use mrusty::Mruby;
+/// # use mrusty::MrubyImpl;
+/// # use mrusty::ValueKey;
+/// use std::collections::HashMap;
+///
+/// let mruby = Mruby::new();
+/// let mut memo = HashMap::new();
+///
+/// memo.insert(ValueKey(mruby.run("[1, 'a']").unwrap()), 1);
+/// memo.insert(ValueKey(mruby.fixnum(1)), 2);
+///
+/// assert_eq!(memo.get(&ValueKey(mruby.run("[1, 'a']").unwrap())), Some(&1));
+/// assert_eq!(memo.get(&ValueKey(mruby.fixnum(1))), Some(&2));
+/// assert_eq!(memo.get(&ValueKey(mruby.float(1.0))), None);
+/// assert_eq!(memo.get(&ValueKey(mruby.run("[1.0, 'a']").unwrap())), None);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ValueKey(pub Value);
+
+impl PartialEq<ValueKey> for ValueKey {
+    fn eq(&self, other: &ValueKey) -> bool {
+        key_eq(&self.0, &other.0, &mut vec![])
+    }
+}
+
+impl Eq for ValueKey {}
+
+impl Hash for ValueKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        key_hash(&self.0, state, &mut vec![]);
+    }
+}
+
+// Arrays that (indirectly) contain themselves are compared and hashed like Ruby does: a pair of
+// Arrays already being compared further up counts as equal and an Array already being hashed
+// only contributes its type.
+fn key_eq(a: &Value, b: &Value, path: &mut Vec<(u64, u64)>) -> bool {
+    if a.value.typ != b.value.typ {
+        return false;
+    }
+
+    match a.value.typ {
+        MrType::MRB_TT_ARRAY => {
+            let pair = (a.value.value, b.value.value);
+
+            if path.contains(&pair) {
+                return true;
+            }
+
+            match (a.to_vec(), b.to_vec()) {
+                (Ok(a), Ok(b)) => {
+                    path.push(pair);
+
+                    let equal = a.len() == b.len() &&
+                                a.iter().zip(&b).all(|(a, b)| key_eq(a, b, path));
+
+                    path.pop();
+
+                    equal
+                },
+                _              => false
+            }
+        },
+        MrType::MRB_TT_FLOAT => match (a.to_f64(), b.to_f64()) {
+            (Ok(a), Ok(b)) => float_bits(a) == float_bits(b),
+            _              => false
+        },
+        _ => match a.call("==", vec![b.clone()]) {
+            Ok(result) => result.to_bool().unwrap_or(false),
+            Err(_)     => false
+        }
+    }
+}
+
+// Hashes consistently with key_eq: Arrays by their elements, Floats by value and everything else
+// with Ruby's hash. Hashes only compare equal with ==, so only their size is hashed.
+fn key_hash<H: Hasher>(value: &Value, state: &mut H, path: &mut Vec<u64>) {
+    (value.value.typ as u32).hash(state);
+
+    match value.value.typ {
+        MrType::MRB_TT_ARRAY => {
+            if path.contains(&value.value.value) {
+                return;
+            }
+
+            if let Ok(values) = value.to_vec() {
+                path.push(value.value.value);
+
+                for value in &values {
+                    key_hash(value, state, path);
+                }
+
+                path.pop();
+            }
+        },
+        MrType::MRB_TT_FLOAT => {
+            if let Ok(float) = value.to_f64() {
+                float_bits(float).hash(state);
+            }
+        },
+        MrType::MRB_TT_HASH => {
+            if let Ok(size) = value.call("size", vec![]).and_then(|size| size.to_i32()) {
+                size.hash(state);
+            }
+        },
+        _ => {
+            if let Ok(hash) = value.call("hash", vec![]).and_then(|hash| hash.to_i32()) {
+                hash.hash(state);
+            }
+        }
+    }
+}
+
+// 0.0 and -0.0 are the same key and so is every NaN, which keeps ValueKey's Eq reflexive.
+fn float_bits(float: f64) -> u64 {
+    if float == 0.0 {
+        0.0f64.to_bits()
+    } else if float.is_nan() {
+        std::f64::NAN.to_bits()
+    } else {
+        float.to_bits()
+    }
+}
+
 impl fmt::Debug for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Value {{ {:?} }}", self.value)
+        write!(f, "{}", self.inspect())
+    }
+}
+
+/// Formats a `Value` with `to_s`. Like `inspect`, this never fails: if `to_s` raises an mruby
+/// `Exception` or does not return a `String`, a `#<ClassName>` placeholder is written instead.
+///
+/// # Examples
+///
+/// ```
+/// # use mrusty::Mruby;
+/// # use mrusty::MrubyImpl;
+/// let mruby = Mruby::new();
+///
+/// assert_eq!(format!("{}", mruby.string("hi")), "hi");
+/// assert_eq!(format!("{:?}", mruby.string("hi")), "\"hi\"");
+/// assert_eq!(format!("{}", mruby.run("[1, :a, nil]").unwrap()), "[1, :a, nil]");
+/// ```
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.call("to_s", vec![]).as_ref().map(|result| result.to_str()) {
+            Ok(Ok(string)) => write!(f, "{}", string),
+            _              => write!(f, "{}", self.placeholder())
+        }
     }
 }
@@ -19,6 +19,8 @@ use std::ffi::CStr;
 use std::mem;
 use std::os::raw::c_char;
 use std::rc::Rc;
+use std::slice;
+use std::str;
 
 use super::MrubyError;
 
@@ -27,6 +29,7 @@ pub enum MrContext {}
 
 pub enum MrClass {}
 pub enum MrData {}
+pub enum MrProc {}
 
 pub type MrFunc = extern "C" fn(*const MrState, MrValue) -> MrValue;
 
@@ -65,6 +68,15 @@ impl MrValue {
         mrb_ext_cint_to_fixnum(value)
     }
 
+    #[inline]
+    pub unsafe fn int64(value: i64) -> Result<MrValue, MrubyError> {
+        if value < mrb_ext_int_min() || value > mrb_ext_int_max() {
+            Err(MrubyError::Cast(format!("Fixnum of {} bits", mrb_ext_int_bit())))
+        } else {
+            Ok(mrb_ext_cint64_to_fixnum(value))
+        }
+    }
+
     #[inline]
     pub unsafe fn float(mrb: *const MrState, value: f64) -> MrValue {
         mrb_ext_cdouble_to_float(mrb, value)
@@ -75,6 +87,11 @@ impl MrValue {
         mrb_str_new(mrb, value.as_ptr(), value.len())
     }
 
+    #[inline]
+    pub unsafe fn string_bytes(mrb: *const MrState, value: &[u8]) -> MrValue {
+        mrb_str_new(mrb, value.as_ptr(), value.len())
+    }
+
     #[inline]
     pub unsafe fn symbol(mrb: *const MrState, value: &str) -> MrValue {
         mrb_ext_sym_new(mrb, value.as_ptr(), value.len())
@@ -101,6 +118,17 @@ impl MrValue {
         array
     }
 
+    #[inline]
+    pub unsafe fn hash(mrb: *const MrState, value: Vec<(MrValue, MrValue)>) -> MrValue {
+        let hash = mrb_hash_new(mrb);
+
+        for &(key, value) in value.iter() {
+            mrb_hash_set(mrb, hash, key, value);
+        }
+
+        hash
+    }
+
     #[inline]
     pub unsafe fn to_bool<'a>(&self) -> Result<bool, MrubyError> {
         match self.typ {
@@ -120,6 +148,16 @@ impl MrValue {
         }
     }
 
+    #[inline]
+    pub unsafe fn to_i64(&self) -> Result<i64, MrubyError> {
+        match self.typ {
+            MrType::MRB_TT_FIXNUM => {
+                Ok(mrb_ext_fixnum_to_cint64(*self))
+            },
+            _ => Err(MrubyError::Cast("Fixnum".to_owned()))
+        }
+    }
+
     #[inline]
     pub unsafe fn to_f64(&self) -> Result<f64, MrubyError> {
         match self.typ {
@@ -134,9 +172,9 @@ impl MrValue {
     pub unsafe fn to_str<'a>(&self, mrb: *const MrState) -> Result<&'a str, MrubyError> {
         match self.typ {
             MrType::MRB_TT_STRING => {
-                let s = mrb_str_to_cstr(mrb, *self) as *const i8;
+                let bytes = try!(self.to_bytes());
 
-                Ok(CStr::from_ptr(s).to_str().unwrap().clone())
+                str::from_utf8(bytes).map_err(|_| MrubyError::Cast("UTF-8 String".to_owned()))
             },
             MrType::MRB_TT_SYMBOL => {
                 let s = mrb_ext_sym2name(mrb, *self) as *const i8;
@@ -147,6 +185,19 @@ impl MrValue {
         }
     }
 
+    #[inline]
+    pub unsafe fn to_bytes<'a>(&self) -> Result<&'a [u8], MrubyError> {
+        match self.typ {
+            MrType::MRB_TT_STRING => {
+                let ptr = mrb_ext_str_ptr(*self);
+                let len = mrb_ext_str_len(*self) as usize;
+
+                Ok(slice::from_raw_parts(ptr, len))
+            },
+            _ => Err(MrubyError::Cast("String".to_owned()))
+        }
+    }
+
     #[inline]
     pub unsafe fn to_obj<T: Any>(&self, mrb: *const MrState,
                                  typ: &MrDataType) -> Result<Rc<T>, MrubyError> {
@@ -181,6 +232,27 @@ impl MrValue {
             _ => Err(MrubyError::Cast("Array".to_owned()))
         }
     }
+
+    #[inline]
+    pub unsafe fn to_hash(&self, mrb: *const MrState) -> Result<Vec<(MrValue, MrValue)>,
+                                                               MrubyError> {
+        match self.typ {
+            MrType::MRB_TT_HASH => {
+                let keys = mrb_hash_keys(mrb, *self);
+                let len = mrb_ext_ary_len(mrb, keys) as usize;
+                let mut vec = Vec::with_capacity(len);
+
+                for i in 0..len {
+                    let key = mrb_ary_ref(mrb, keys, i as i32);
+
+                    vec.push((key, mrb_hash_get(mrb, *self, key)));
+                }
+
+                Ok(vec)
+            },
+            _ => Err(MrubyError::Cast("Hash".to_owned()))
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -217,6 +289,8 @@ extern "C" {
     pub fn mrb_open() -> *const MrState;
     pub fn mrb_close(mrb: *const MrState);
 
+    pub fn mrb_top_self(mrb: *const MrState) -> MrValue;
+
     #[inline]
     pub fn mrb_ext_get_ud(mrb: *const MrState) -> *const u8;
     #[inline]
@@ -226,7 +300,12 @@ extern "C" {
 
     pub fn mrbc_filename(mrb: *const MrState, context: *const MrContext,
                          filename: *const c_char) -> *const c_char;
+    pub fn mrb_ext_clear_filename(context: *const MrContext);
 
+    pub fn mrb_ext_load_nstring_cxt(mrb: *const MrState, code: *const u8, len: usize,
+                                    context: *const MrContext) -> MrValue;
+    pub fn mrb_ext_load_file_cxt(mrb: *const MrState, path: *const c_char,
+                                 context: *const MrContext) -> MrValue;
     pub fn mrb_load_nstring_cxt(mrb: *const MrState, code: *const u8, len: i32,
                                 context: *const MrContext) -> MrValue;
     pub fn mrb_load_irep_cxt(mrb: *const MrState, code: *const u8,
@@ -234,9 +313,25 @@ extern "C" {
 
     pub fn mrb_class_get(mrb: *const MrState, name: *const c_char) -> *const MrClass;
     pub fn mrb_module_get(mrb: *const MrState, name: *const c_char) -> *const MrClass;
-
+    pub fn mrb_ext_class_path(mrb: *const MrState, path: *const c_char) -> *const MrClass;
+    pub fn mrb_ext_class_value(class: *const MrClass) -> MrValue;
+    pub fn mrb_ext_singleton_class(mrb: *const MrState, value: MrValue) -> *const MrClass;
+    pub fn mrb_obj_classname(mrb: *const MrState, object: MrValue) -> *const c_char;
+    pub fn mrb_obj_class(mrb: *const MrState, object: MrValue) -> *const MrClass;
+    pub fn mrb_ext_class_name(mrb: *const MrState, class: MrValue) -> *const c_char;
+    pub fn mrb_obj_is_kind_of(mrb: *const MrState, object: MrValue, class: *const MrClass) -> bool;
+    pub fn mrb_obj_eq(mrb: *const MrState, a: MrValue, b: MrValue) -> bool;
+
+    pub fn mrb_class_defined(mrb: *const MrState, name: *const c_char) -> bool;
+    pub fn mrb_ext_is_exception_class(mrb: *const MrState, class: *const MrClass) -> bool;
     pub fn mrb_define_class(mrb: *const MrState, name: *const c_char,
                             sup: *const MrClass) -> *const MrClass;
+    pub fn mrb_define_class_under(mrb: *const MrState, outer: *const MrClass,
+                                  name: *const c_char, sup: *const MrClass) -> *const MrClass;
+    pub fn mrb_include_module(mrb: *const MrState, class: *const MrClass,
+                              module: *const MrClass);
+    pub fn mrb_define_module_under(mrb: *const MrState, outer: *const MrClass,
+                                   name: *const c_char) -> *const MrClass;
     pub fn mrb_define_module_function(mrb: *const MrState, module: *const MrClass,
                                       name: *const c_char, fun: MrFunc, aspec: u32);
 
@@ -244,20 +339,58 @@ extern "C" {
                              fun: MrFunc, aspec: u32);
     pub fn mrb_define_class_method(mrb: *const MrState, class: *const MrClass, name: *const c_char,
                                    fun: MrFunc, aspec: u32);
+    pub fn mrb_undef_method(mrb: *const MrState, class: *const MrClass, name: *const c_char);
+    pub fn mrb_define_alias(mrb: *const MrState, class: *const MrClass, name1: *const c_char,
+                            name2: *const c_char);
+    pub fn mrb_undef_class_method(mrb: *const MrState, class: *const MrClass,
+                                  name: *const c_char);
+    pub fn mrb_obj_respond_to(mrb: *const MrState, class: *const MrClass, sym: u32) -> bool;
+    pub fn mrb_respond_to(mrb: *const MrState, object: MrValue, sym: u32) -> bool;
 
     pub fn mrb_get_args(mrb: *const MrState, format: *const c_char, ...);
+    pub fn mrb_ext_check_arity(mrb: *const MrState, format: *const c_char);
     pub fn mrb_ext_get_mid(mrb: *const MrState) -> u32;
+    pub fn mrb_ext_get_target_class(mrb: *const MrState) -> *const MrClass;
+    pub fn mrb_ext_get_caller_self(mrb: *const MrState) -> MrValue;
+    pub fn mrb_ext_yield_argv(mrb: *const MrState, block: MrValue, argc: i32,
+                              argv: *const MrValue) -> MrValue;
 
     pub fn mrb_intern(mrb: *const MrState, string: *const u8, len: usize) -> u32;
 
+    pub fn mrb_define_const(mrb: *const MrState, class: *const MrClass, name: *const c_char,
+                            value: MrValue);
+    pub fn mrb_const_get(mrb: *const MrState, class: MrValue, sym: u32) -> MrValue;
+    pub fn mrb_const_defined(mrb: *const MrState, class: MrValue, sym: u32) -> bool;
+    pub fn mrb_const_remove(mrb: *const MrState, class: MrValue, sym: u32);
+
+    pub fn mrb_iv_get(mrb: *const MrState, object: MrValue, sym: u32) -> MrValue;
+    pub fn mrb_iv_set(mrb: *const MrState, object: MrValue, sym: u32, value: MrValue);
+    pub fn mrb_gv_get(mrb: *const MrState, sym: u32) -> MrValue;
+    pub fn mrb_gv_set(mrb: *const MrState, sym: u32, value: MrValue);
+
+    pub fn mrb_iv_defined(mrb: *const MrState, object: MrValue, sym: u32) -> bool;
+
     pub fn mrb_funcall_argv(mrb: *const MrState, object: MrValue, sym: u32, argc: i32,
                             argv: *const MrValue) -> MrValue;
+    pub fn mrb_funcall_with_block(mrb: *const MrState, object: MrValue, sym: u32, argc: i32,
+                                  argv: *const MrValue, block: MrValue) -> MrValue;
+
+    pub fn mrb_proc_new_cfunc_with_env(mrb: *const MrState, fun: MrFunc, argc: i32,
+                                       argv: *const MrValue) -> *const MrProc;
+    pub fn mrb_proc_cfunc_env_get(mrb: *const MrState, i: i32) -> MrValue;
+    #[inline]
+    pub fn mrb_ext_proc_to_value(mrb: *const MrState, prc: *const MrProc) -> MrValue;
 
     #[inline]
     pub fn mrb_ext_fixnum_to_cint(value: MrValue) -> i32;
     #[inline]
+    pub fn mrb_ext_fixnum_to_cint64(value: MrValue) -> i64;
+    #[inline]
     pub fn mrb_ext_float_to_cdouble(value: MrValue) -> f64;
 
+    #[inline]
+    pub fn mrb_ext_is_nil(value: MrValue) -> bool;
+
     #[inline]
     pub fn mrb_ext_nil() -> MrValue;
     #[inline]
@@ -267,16 +400,24 @@ extern "C" {
     #[inline]
     pub fn mrb_ext_cint_to_fixnum(value: i32) -> MrValue;
     #[inline]
+    pub fn mrb_ext_cint64_to_fixnum(value: i64) -> MrValue;
+    #[inline]
+    pub fn mrb_ext_int_bit() -> i32;
+    #[inline]
+    pub fn mrb_ext_int_min() -> i64;
+    #[inline]
+    pub fn mrb_ext_int_max() -> i64;
+    #[inline]
     pub fn mrb_ext_cdouble_to_float(mrb: *const MrState, value: f64) -> MrValue;
     #[inline]
     pub fn mrb_str_new(mrb: *const MrState, value: *const u8, len: usize) -> MrValue;
     #[inline]
     pub fn mrb_ext_sym2name(mrb: *const MrState, value: MrValue) -> *const u8;
     #[inline]
+    pub fn mrb_ext_symbol(value: MrValue) -> u32;
+    #[inline]
     pub fn mrb_ext_sym_new(mrb: *const MrState, value: *const u8, len: usize) -> MrValue;
 
-    #[inline]
-    pub fn mrb_str_to_cstr(mrb: *const MrState, value: MrValue) -> *const c_char;
 
     #[inline]
     pub fn mrb_data_object_alloc(mrb: *const MrState, class: *const MrClass, ptr: *const u8,
@@ -300,12 +441,70 @@ extern "C" {
     #[inline]
     pub fn mrb_ary_set(mrb: *const MrState, array: MrValue, i: i32, value: MrValue);
     #[inline]
+    pub fn mrb_ary_push(mrb: *const MrState, array: MrValue, value: MrValue);
+    #[inline]
     pub fn mrb_ext_ary_len(mrb: *const MrState, array: MrValue) -> i32;
+    #[inline]
+    pub fn mrb_ext_str_ptr(string: MrValue) -> *const u8;
+    #[inline]
+    pub fn mrb_ext_str_len(string: MrValue) -> i32;
+    #[inline]
+    pub fn mrb_ext_hash_len(mrb: *const MrState, hash: MrValue) -> i32;
+
+    pub fn mrb_ext_freeze(value: MrValue);
+    pub fn mrb_ext_is_frozen(value: MrValue) -> bool;
+    pub fn mrb_ext_define_freeze(mrb: *const MrState);
+
+    pub fn mrb_mruby_eval_gem_init(mrb: *const MrState);
+
+    pub fn mrb_ext_range_new(mrb: *const MrState, from: MrValue, to: MrValue,
+                             exclusive: bool) -> MrValue;
+    pub fn mrb_ext_range_edges(range: MrValue, from: *mut MrValue, to: *mut MrValue,
+                               exclusive: *mut bool);
+
+    pub fn mrb_hash_new(mrb: *const MrState) -> MrValue;
+    #[inline]
+    pub fn mrb_hash_set(mrb: *const MrState, hash: MrValue, key: MrValue, value: MrValue);
+    #[inline]
+    pub fn mrb_hash_get(mrb: *const MrState, hash: MrValue, key: MrValue) -> MrValue;
+    #[inline]
+    pub fn mrb_hash_keys(mrb: *const MrState, hash: MrValue) -> MrValue;
+
+    pub fn mrb_full_gc(mrb: *const MrState);
+    pub fn mrb_incremental_gc(mrb: *const MrState);
+    pub fn mrb_ext_gc_disable(mrb: *const MrState);
+    pub fn mrb_ext_gc_enable(mrb: *const MrState);
+    pub fn mrb_ext_gc_stats(mrb: *const MrState, live: *mut usize, total: *mut usize,
+                            arena: *mut i32);
+    pub fn mrb_gc_arena_save(mrb: *const MrState) -> i32;
+    pub fn mrb_gc_arena_restore(mrb: *const MrState, idx: i32);
+
+    pub fn mrb_ext_set_code_fetch_hook(mrb: *const MrState,
+                                       hook: Option<extern "C" fn(*const MrState, *const u8,
+                                                                  *const u32, *const MrValue)>);
+    pub fn mrb_ext_vm_idle(mrb: *const MrState) -> bool;
+
+    pub fn mrb_ext_compile(mrb: *const MrState, context: *const MrContext, code: *const u8,
+                           len: usize, bin: *mut *mut u8, bin_size: *mut usize) -> i32;
+    pub fn mrb_free(mrb: *const MrState, ptr: *mut u8);
+    pub fn mrb_ext_compile_proc(mrb: *const MrState, filename: *const c_char, code: *const u8,
+                                len: usize) -> MrValue;
+    pub fn mrb_ext_run_proc(mrb: *const MrState, compiled: MrValue) -> MrValue;
+    pub fn mrb_gc_register(mrb: *const MrState, value: MrValue);
+    pub fn mrb_gc_unregister(mrb: *const MrState, value: MrValue);
+    pub fn mrb_ext_gc_release(mrb: *const MrState, value: MrValue);
 
     #[inline]
     pub fn mrb_ext_raise(mrb: *const MrState, eclass: *const c_char, msg: *const c_char);
     #[inline]
     pub fn mrb_ext_get_exc(mrb: *const MrState) -> MrValue;
+    pub fn mrb_ext_get_exc_of(mrb: *const MrState, name: *const c_char) -> MrValue;
+    pub fn mrb_ext_parse_state(mrb: *const MrState, code: *const u8, len: usize,
+                               message: *mut MrValue, line: *mut i32) -> i32;
+    pub fn mrb_ext_get_syntax_error(mrb: *const MrState, line: *mut i32,
+                                    column: *mut i32) -> MrValue;
+    #[inline]
+    pub fn mrb_ext_get_exc_with_backtrace(mrb: *const MrState) -> MrValue;
 }
 
 
@@ -70,7 +70,8 @@ impl Repl {
 
     /// Starts a `Repl`.
     ///
-    /// Use `'\'` for multiline statements.
+    /// Incomplete statements, like an open `def`, continue on the next line. Use `'\'` to
+    /// continue complete ones.
     ///
     /// # Examples
     ///
@@ -122,6 +123,12 @@ impl Repl {
                 command = command + &input;
             }
 
+            if self.mruby.parse_state(&command) == ParseStatus::Incomplete {
+                command = command + "\n";
+
+                continue
+            }
+
             match self.mruby.run(&command) {
                 Ok(value) => {
                     let result = value.call("to_s", vec![]).unwrap().to_str().unwrap();
@@ -98,6 +98,27 @@ fn symbol_to_string() {
     }
 }
 
+#[test]
+fn class_path() {
+    unsafe {
+        let mrb = mrb_open();
+        let context = mrbc_context_new(mrb);
+
+        let code = "module A; class B; end; C = 1; end";
+
+        mrb_load_nstring_cxt(mrb, code.as_ptr(), code.len() as i32, context);
+
+        let a = mrb_ext_class_path(mrb, CString::new("A").unwrap().as_ptr());
+
+        assert_eq!(a, mrb_module_get(mrb, CString::new("A").unwrap().as_ptr()));
+        assert!(!mrb_ext_class_path(mrb, CString::new("A::B").unwrap().as_ptr()).is_null());
+        assert!(mrb_ext_class_path(mrb, CString::new("A::C").unwrap().as_ptr()).is_null());
+        assert!(mrb_ext_class_path(mrb, CString::new("A::D").unwrap().as_ptr()).is_null());
+
+        mrb_close(mrb);
+    }
+}
+
 #[test]
 fn define_method() {
     unsafe {
@@ -384,6 +405,21 @@ fn fixnum() {
     }
 }
 
+#[test]
+fn int64() {
+    unsafe {
+        let number = MrValue::int64(-1291657).unwrap();
+        assert_eq!(number.to_i64().unwrap(), -1291657);
+
+        if mrb_ext_int_bit() < 64 {
+            assert!(MrValue::int64(i64::max_value()).is_err());
+        } else {
+            let number = MrValue::int64(1 << 40).unwrap();
+            assert_eq!(number.to_i64().unwrap(), 1 << 40);
+        }
+    }
+}
+
 #[test]
 fn float() {
     unsafe {
@@ -396,6 +432,26 @@ fn float() {
     }
 }
 
+#[test]
+fn float_non_finite() {
+    use std::f64;
+
+    unsafe {
+        let mrb = mrb_open();
+
+        let number = MrValue::float(mrb, f64::INFINITY);
+        assert_eq!(number.to_f64().unwrap(), f64::INFINITY);
+
+        let number = MrValue::float(mrb, f64::NEG_INFINITY);
+        assert_eq!(number.to_f64().unwrap(), f64::NEG_INFINITY);
+
+        let number = MrValue::float(mrb, f64::NAN);
+        assert!(number.to_f64().unwrap().is_nan());
+
+        mrb_close(mrb);
+    }
+}
+
 #[test]
 fn string() {
     unsafe {
@@ -583,3 +639,20 @@ fn array() {
         mrb_close(mrb);
     }
 }
+
+#[test]
+fn hash() {
+    unsafe {
+        let mrb = mrb_open();
+
+        let vec: Vec<(MrValue, MrValue)> = [1, 2, 3].iter().map(|v| {
+            (MrValue::fixnum(*v), MrValue::fixnum(*v * 2))
+        }).collect();
+
+        let hash = MrValue::hash(mrb, vec.clone());
+
+        assert_eq!(hash.to_hash(mrb).unwrap(), vec);
+
+        mrb_close(mrb);
+    }
+}
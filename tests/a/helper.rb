@@ -0,0 +1,3 @@
+def helper
+  "helper"
+end
@@ -37,10 +37,10 @@ mrclass!(Scalar, {
     });
 
     def!("value", |mruby, slf: Scalar| {
-        mruby.float(slf.value as f64)
+        mruby.float32(slf.value)
     });
 
-    def!("*", |mruby, slf: Scalar, vector: Vector| {
+    defop!("*", |mruby, slf: Scalar, vector: Vector| {
         mruby.obj((*slf).clone() * (*vector).clone())
     });
 
@@ -44,23 +44,23 @@ mrclass!(Vector, {
         slf.call_unchecked("new", array)
     });
 
-    def!("x", |mruby, slf: Vector| {
-        mruby.float(slf.x as f64)
-    });
+    getters!(x: f32, y: f32, z: f32);
 
-    def!("y", |mruby, slf: Vector| {
-        mruby.float(slf.y as f64)
+    defop!("+", |mruby, slf: Vector, other: Vector| {
+        mruby.obj(Vector::new(slf.x + other.x, slf.y + other.y, slf.z + other.z))
     });
 
-    def!("z", |mruby, slf: Vector| {
-        mruby.float(slf.z as f64)
+    def!("*", |mruby, slf: Vector, scalar: f64| {
+        mruby.obj(Scalar::new(scalar as f32) * (*slf).clone())
     });
 
+    coerce!();
+
     def!("to_a", |mruby, slf: Vector| {
         mruby.array(vec![
-            mruby.float(slf.x as f64),
-            mruby.float(slf.y as f64),
-            mruby.float(slf.z as f64)
+            mruby.float32(slf.x),
+            mruby.float32(slf.y),
+            mruby.float32(slf.z)
         ])
     });
 });
@@ -54,6 +54,10 @@ fn api_getters() {
     assert_eq!(vector.to_obj::<Vector>().unwrap().x, 1.0);
     assert_eq!(vector.to_obj::<Vector>().unwrap().y, 2.0);
     assert_eq!(vector.to_obj::<Vector>().unwrap().z, 3.0);
+
+    let result = mruby.run("v = Vector.new 1.0, 2.0, 3.0; [v.x, v.y, v.z]").unwrap();
+
+    assert_eq!(result, mruby.run("[1.0, 2.0, 3.0]").unwrap());
 }
 
 #[test]
@@ -68,6 +72,59 @@ fn api_mul() {
     assert_eq!(*vector.to_obj::<Vector>().unwrap(), Vector::new(2.0, 4.0, 6.0));
 }
 
+#[test]
+fn api_defop() {
+    let mruby = Mruby::new();
+
+    Scalar::require(mruby.clone());
+    Vector::require(mruby.clone());
+
+    let vector = mruby.run("Vector.new(1.0, 2.0, 3.0) + Vector.new(1.0, 1.0, 1.0)").unwrap();
+
+    assert_eq!(*vector.to_obj::<Vector>().unwrap(), Vector::new(2.0, 3.0, 4.0));
+
+    let result = mruby.run("
+      begin
+        Vector.new(1.0, 2.0, 3.0) + 1
+      rescue TypeError => e
+        e.message
+      end
+    ").unwrap();
+
+    assert_eq!(result.to_str().unwrap(), "Fixnum can't be coerced into Vector");
+
+    let result = mruby.run("Scalar.new(2.0) * Scalar.new(2.0)");
+
+    match result {
+        Err(MrubyError::Runtime(err)) => {
+            assert_eq!(err, "TypeError: Scalar can't be coerced into Scalar");
+        },
+        _ => panic!("Scalar * Scalar should raise")
+    }
+}
+
+#[test]
+fn api_coerce() {
+    let mruby = Mruby::new();
+
+    Scalar::require(mruby.clone());
+    Vector::require(mruby.clone());
+
+    let vector = mruby.run("3 * Vector.new(1, 2, 3)").unwrap();
+
+    assert_eq!(*vector.to_obj::<Vector>().unwrap(), Vector::new(3.0, 6.0, 9.0));
+
+    let vector = mruby.run("0.5 * Vector.new(1, 2, 3)").unwrap();
+
+    assert_eq!(*vector.to_obj::<Vector>().unwrap(), Vector::new(0.5, 1.0, 1.5));
+
+    assert_eq!(mruby.run("[3.__send__(:*, 2), 3.__send__(:-, 2.5), 6.__send__(:/, 4)]").unwrap(),
+               mruby.run("[6, 0.5, 1.5]").unwrap());
+    assert!(mruby.run("3 * 'a'").is_err());
+    assert!(mruby.run("3 - Vector.new(1, 2, 3)").is_err());
+    assert!(mruby.run("3.0 / Vector.new(1, 2, 3)").is_err());
+}
+
 #[test]
 fn api_array() {
     let mruby = Mruby::new();
@@ -180,6 +237,2625 @@ fn api_execute_binary() {
     assert_eq!(*result.to_obj::<Scalar>().unwrap(), Scalar::new(2.0));
 }
 
+#[test]
+fn api_load_path() {
+    let mruby = Mruby::new();
+
+    mruby.add_load_path(Path::new("tests/lib"));
+
+    let result = mruby.run("
+        require 'greeting'
+
+        greeting
+    ").unwrap();
+
+    assert_eq!(result.to_str().unwrap(), "hello");
+}
+
+#[test]
+fn api_new_with_opts() {
+    let mruby = Mruby::new_with_opts(MrubyOptions {
+        load_paths: vec![Path::new("tests/lib").to_path_buf()],
+        ..MrubyOptions::default()
+    });
+
+    assert_eq!(mruby.run("require 'greeting'; greeting").unwrap().to_str().unwrap(), "hello");
+
+    let mruby = Mruby::new_with_opts(MrubyOptions {
+        allow_require: false,
+        load_paths: vec![Path::new("tests/lib").to_path_buf()],
+        ..MrubyOptions::default()
+    });
+
+    assert!(mruby.run("require 'greeting'").is_err());
+    assert!(mruby.run("require_relative 'greeting'").is_err());
+
+    let mruby = Mruby::new_with_opts(MrubyOptions {
+        gc_enabled: false,
+        instruction_limit: Some(10000),
+        ..MrubyOptions::default()
+    });
+
+    assert_eq!(mruby.run("(1..10).reduce(:+)").unwrap().to_i32().unwrap(), 55);
+    assert!(mruby.run("100000.times {}").is_err());
+    assert_eq!(mruby.run("1").unwrap().to_i32().unwrap(), 1);
+
+    let looper = mruby.run("
+      class Looper
+        def go
+          100000.times {}
+        end
+      end
+
+      Looper.new
+    ").unwrap();
+
+    assert!(looper.call("go", vec![]).is_err());
+    assert!(mruby.run("proc { 2 }").unwrap().call("call", vec![]).is_ok());
+}
+
+#[test]
+fn api_sandboxed() {
+    let mruby = Mruby::sandboxed();
+
+    match mruby.run("system 'ls'") {
+        Err(MrubyError::Runtime(err)) => {
+            assert_eq!(err, "SecurityError: system is not allowed in a sandbox")
+        },
+        _ => panic!("system should raise a SecurityError")
+    }
+
+    match mruby.run("require 'greeting'") {
+        Err(MrubyError::Runtime(err)) => {
+            assert_eq!(err, "SecurityError: require is not allowed in a sandbox")
+        },
+        _ => panic!("require should raise a SecurityError")
+    }
+
+    assert!(mruby.run("exit").is_err());
+    assert!(mruby.run("Kernel.exit").is_err());
+    assert!(mruby.run("begin; `ls`; rescue; end").is_err());
+    assert!(!mruby.run("Object.const_defined?(:File)").unwrap().to_bool().unwrap());
+
+    assert_eq!(mruby.run("1 + 1").unwrap().to_i32().unwrap(), 2);
+}
+
+#[test]
+fn api_undef_method() {
+    let mruby = Mruby::new();
+
+    mruby.run("
+      module Outer
+        class Inner
+          def self.build; new; end
+          def value; 1; end
+        end
+      end
+    ").unwrap();
+
+    mruby.undef_method("Outer::Inner", "value").unwrap();
+    mruby.undef_class_method("Outer::Inner", "build").unwrap();
+
+    assert!(mruby.run("Outer::Inner.new.value").is_err());
+    assert!(mruby.run("Outer::Inner.build").is_err());
+
+    match mruby.undef_method("Outer::Missing", "value") {
+        Err(MrubyError::Undef) => (),
+        _ => panic!("undefining a method of a missing class should fail")
+    }
+
+    match mruby.undef_class_method("Outer::Inner", "build") {
+        Err(MrubyError::Undef) => (),
+        _ => panic!("undefining a method twice should fail")
+    }
+}
+
+#[test]
+fn api_def_method_on() {
+    let mruby = Mruby::new();
+
+    mruby.def_method_on("Integer", "double", mrfn!(|mruby, slf: Value| {
+        mruby.fixnum(slf.to_i32().unwrap() * 2)
+    })).unwrap();
+    mruby.def_method_on("Comparable", "positive?", mrfn!(|mruby, slf: Value| {
+        slf.call(">", vec![mruby.fixnum(0)]).unwrap()
+    })).unwrap();
+    mruby.def_method_on("Kernel", "fail_from_rust", mrfn!(|_mruby, _slf: Value| {
+        panic!("from Rust")
+    })).unwrap();
+
+    assert_eq!(mruby.run("21.double").unwrap().to_i32().unwrap(), 42);
+    assert_eq!(mruby.run("3.positive?").unwrap().to_bool().unwrap(), true);
+    assert_eq!(mruby.run("'a'.positive? rescue :err").unwrap().to_str().unwrap(), "err");
+    match mruby.run("fail_from_rust") {
+        Err(MrubyError::Panic(message)) => assert_eq!(message, "from Rust"),
+        _ => panic!("fail_from_rust should panic")
+    }
+
+    match mruby.def_method_on("Missing", "m", mrfn!(|mruby, _slf: Value| mruby.nil())) {
+        Err(MrubyError::Undef) => (),
+        _ => panic!("defining a method on a missing class should fail")
+    }
+}
+
+#[test]
+fn api_float_non_finite() {
+    use std::f64;
+
+    let mruby = Mruby::new();
+
+    let infinity = mruby.run("Float::INFINITY").unwrap();
+    let nan = mruby.run("Float::NAN").unwrap();
+
+    assert_eq!(infinity.to_f64().unwrap(), f64::INFINITY);
+    assert_eq!(mruby.run("-Float::INFINITY").unwrap().to_f64().unwrap(), f64::NEG_INFINITY);
+    assert!(nan.to_f64().unwrap().is_nan());
+    assert!(mruby.run("0.0 / 0.0").unwrap().to_f64().unwrap().is_nan());
+
+    let value = mruby.float(f64::INFINITY);
+
+    assert!(value.call("infinite?", vec![]).unwrap().to_i32().unwrap() > 0);
+    assert!(mruby.float(f64::NAN).call("nan?", vec![]).unwrap().to_bool().unwrap());
+    assert!(mruby.float(f64::NAN).to_f64().unwrap().is_nan());
+}
+
+#[test]
+fn api_range() {
+    let mruby = Mruby::new();
+
+    let range = mruby.range(mruby.fixnum(1), mruby.fixnum(3), false);
+
+    assert_eq!(range.call("to_a", vec![]).unwrap().to_vec().unwrap(),
+               vec![mruby.fixnum(1), mruby.fixnum(2), mruby.fixnum(3)]);
+    assert_eq!(range.to_range().unwrap(), (mruby.fixnum(1), mruby.fixnum(3), false));
+
+    let endless = mruby.range(mruby.fixnum(1), mruby.nil(), false);
+
+    assert_eq!(endless.call("first", vec![]).unwrap().to_i32().unwrap(), 1);
+    assert_eq!(endless.to_range().unwrap(), (mruby.fixnum(1), mruby.nil(), false));
+
+    let beginless = mruby.range(mruby.nil(), mruby.string("z"), true);
+
+    assert_eq!(beginless.to_range().unwrap(), (mruby.nil(), mruby.string("z"), true));
+    assert_eq!(mruby.run("('a'..'c')").unwrap().to_range().unwrap(),
+               (mruby.string("a"), mruby.string("c"), false));
+}
+
+#[test]
+fn api_display() {
+    let mruby = Mruby::new();
+
+    struct Cont;
+
+    mruby.def_class::<Cont>("Container");
+
+    let cont = mruby.obj(Cont);
+
+    assert!(format!("{}", cont).starts_with("#<Container"));
+    assert_eq!(format!("{}", mruby.fixnum(3)), "3");
+    assert_eq!(format!("{}", mruby.nil()), "");
+
+    let broken = mruby.run("
+      class Broken
+        def to_s
+          fail 'broken'
+        end
+      end
+
+      Broken.new
+    ").unwrap();
+
+    assert_eq!(format!("{}", broken), "#<Broken>");
+    assert_eq!(format!("{}", mruby.run("class Num; def to_s; 1; end; end; Num.new").unwrap()),
+               "#<Num>");
+}
+
+#[test]
+fn api_top_self() {
+    let mruby = Mruby::new();
+
+    mruby.run("@x = 5").unwrap();
+
+    let top_self = mruby.top_self();
+
+    assert_eq!(top_self.get_var("x").unwrap().to_i32().unwrap(), 5);
+
+    top_self.set_var("y", mruby.fixnum(2));
+
+    assert_eq!(mruby.run("@x + @y").unwrap().to_i32().unwrap(), 7);
+    assert_eq!(mruby.run("self").unwrap(), top_self);
+}
+
+#[test]
+fn api_globals() {
+    let mruby = Mruby::new();
+
+    mruby.set_global("$input", mruby.array(vec![mruby.fixnum(1), mruby.fixnum(2)]));
+    mruby.run("$result = $input.map { |x| x * 10 }").unwrap();
+
+    assert_eq!(mruby.get_global("result").to_vec().unwrap(),
+               vec![mruby.fixnum(10), mruby.fixnum(20)]);
+    assert!(mruby.get_global("$missing").is_nil());
+}
+
+#[test]
+fn api_syntax_error() {
+    let mruby = Mruby::new();
+
+    match mruby.run("x = 1\ny = (x +\n]") {
+        Err(MrubyError::Syntax { message, line, column }) => {
+            assert_eq!(message, "syntax error, unexpected ']'");
+            assert_eq!(line, 3);
+            assert!(column > 0);
+        },
+        _ => panic!("expected a syntax error")
+    }
+
+    // Nothing from a script that fails to parse is run.
+    assert!(mruby.run("$ran = true\n)").is_err());
+    assert!(mruby.get_global("ran").is_nil());
+
+    match mruby.run("fail 'runtime'") {
+        Err(MrubyError::Runtime(err)) => assert_eq!(err, "RuntimeError: runtime"),
+        _ => panic!("expected a runtime error")
+    }
+
+    match mruby.compile_proc(")") {
+        Err(MrubyError::Syntax { line, .. }) => assert_eq!(line, 1),
+        _ => panic!("expected a syntax error")
+    }
+
+    // Local variables are still kept between runs.
+    mruby.run("a = 2").unwrap();
+
+    assert_eq!(mruby.run("a * 2").unwrap().to_i32().unwrap(), 4);
+}
+
+#[test]
+fn api_parse_state() {
+    let mruby = Mruby::new();
+
+    for script in &["def foo", "class A\n  def b", "[1,", "'open", "x = <<EOS\nhi", "foo.",
+                    "{ a: 1", "if true\n1\nelse"] {
+        assert_eq!(mruby.parse_state(script), ParseStatus::Incomplete, "{:?}", script);
+    }
+
+    for script in &["", "def foo; end", "1 + 1", "x = <<EOS\nhi\nEOS\n", "$ran = true"] {
+        assert_eq!(mruby.parse_state(script), ParseStatus::Complete, "{:?}", script);
+    }
+
+    match mruby.parse_state("x = 1\nend") {
+        ParseStatus::Error { line, .. } => assert_eq!(line, 2),
+        status => panic!("expected an error, got {:?}", status)
+    }
+
+    assert!(mruby.get_global("ran").is_nil());
+}
+
+#[test]
+fn api_respond_to() {
+    let mruby = Mruby::new();
+
+    struct Cont;
+
+    mruby.def_class::<Cont>("Container");
+    mruby.def_method::<Cont, _>("value", mrfn!(|mruby, _slf: Value| mruby.fixnum(1)));
+
+    let cont = mruby.obj(Cont);
+
+    assert!(cont.respond_to("value"));
+    assert!(cont.respond_to("to_s"));
+    assert!(!cont.respond_to("missing"));
+
+    mruby.run("class Container; def extra; end; end").unwrap();
+
+    assert!(cont.respond_to("extra"));
+    assert!(mruby.nil().respond_to("nil?"));
+}
+
+#[test]
+fn api_char() {
+    let mruby = Mruby::new();
+
+    for c in &['a', 'ß', '日', '🦀'] {
+        let value = mruby.char(*c);
+
+        assert_eq!(value.to_str().unwrap(), c.to_string());
+        assert_eq!(value.to_char().unwrap(), *c);
+    }
+
+    assert_eq!(mruby.run("'xyz'.chars.last").unwrap().to_char().unwrap(), 'z');
+
+    assert!(mruby.string("").to_char().is_err());
+    assert!(mruby.string("ab").to_char().is_err());
+    assert!(mruby.fixnum(1).to_char().is_err());
+}
+
+#[test]
+fn api_def_class_with_drop() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    struct Conn {
+        id: i32
+    }
+
+    let closed = Rc::new(Cell::new(0));
+    let mruby = Mruby::new();
+
+    {
+        let closed = closed.clone();
+
+        mruby.def_class_with_drop::<Conn, _>("Conn", move |conn| {
+            closed.set(closed.get() + conn.id);
+        });
+    }
+
+    mruby.def_method::<Conn, _>("initialize", mrfn!(|_mruby, slf: Value, id: i32| {
+        slf.init(Conn { id: id })
+    }));
+
+    mruby.run("Conn.new(1); Conn.new(10); nil").unwrap();
+    mruby.full_gc();
+
+    assert_eq!(closed.get(), 11);
+
+    let kept = mruby.run("Conn.new(100)").unwrap().to_obj::<Conn>().unwrap();
+
+    mruby.run("nil").unwrap();
+    mruby.full_gc();
+
+    assert_eq!(closed.get(), 11);
+    assert_eq!(kept.id, 100);
+
+    mruby.run("$conn = Conn.new(1000)").unwrap();
+
+    drop(mruby);
+
+    assert_eq!(closed.get(), 1011);
+}
+
+#[test]
+fn api_def_class_cell() {
+    use std::cell::RefCell;
+
+    struct Vector {
+        x: f64,
+        y: f64
+    }
+
+    let mruby = Mruby::new();
+
+    mruby.def_class_cell::<Vector>("Vector");
+    mruby.def_method::<RefCell<Vector>, _>("initialize", mrfn!(|_mruby, slf: Value, x: f64,
+                                                                y: f64| {
+        slf.init(RefCell::new(Vector { x: x, y: y }))
+    }));
+    mruby.def_method::<RefCell<Vector>, _>("normalize!", mrfn!(|_mruby, slf: Value| {
+        {
+            let mut vector = slf.to_obj_mut::<Vector>().unwrap();
+            let length = (vector.x * vector.x + vector.y * vector.y).sqrt();
+
+            vector.x /= length;
+            vector.y /= length;
+        }
+
+        slf
+    }));
+    mruby.def_method::<RefCell<Vector>, _>("x", mrfn!(|mruby, slf: Value| {
+        mruby.float(slf.to_obj_mut::<Vector>().unwrap().x)
+    }));
+
+    let result = mruby.run("v = Vector.new 3.0, 4.0; v.normalize!; v.x").unwrap();
+
+    assert_eq!(result.to_f64().unwrap(), 0.6);
+
+    let vector = mruby.run("v").unwrap();
+
+    assert_eq!(vector.to_obj_mut::<Vector>().unwrap().y, 0.8);
+    assert!(vector.to_obj::<Vector>().is_err());
+}
+
+#[test]
+fn api_freeze() {
+    use std::cell::RefCell;
+
+    struct Cont {
+        value: i32
+    }
+
+    let mruby = Mruby::new();
+
+    mruby.def_class_cell::<Cont>("Container");
+    mruby.def_method::<RefCell<Cont>, _>("initialize", mrfn!(|_mruby, slf: Value| {
+        slf.init(RefCell::new(Cont { value: 0 }))
+    }));
+    mruby.def_method_result::<RefCell<Cont>, _>("increment!", |_mruby, slf| {
+        try!(slf.to_obj_mut::<Cont>()).value += 1;
+
+        Ok(slf)
+    });
+
+    let cont = mruby.run("$cont = Container.new.increment!").unwrap();
+
+    assert!(!cont.is_frozen());
+
+    cont.freeze();
+
+    assert!(cont.is_frozen());
+    assert!(mruby.run("$cont.frozen?").unwrap().to_bool().unwrap());
+
+    let result = mruby.run("
+      begin
+        $cont.increment!
+      rescue FrozenError => e
+        e.message
+      end
+    ").unwrap();
+
+    assert_eq!(result.to_str().unwrap(), "can't modify frozen Container");
+
+    match cont.to_obj_mut::<Cont>() {
+        Err(MrubyError::Frozen(_)) => (),
+        _ => panic!("frozen objects should not be mutably borrowed")
+    }
+
+    assert_eq!(cont.to_obj::<RefCell<Cont>>().unwrap().borrow().value, 1);
+    assert!(mruby.run("'a'.freeze.frozen? && :a.frozen? && !Object.new.frozen?").unwrap()
+                 .to_bool().unwrap());
+}
+
+#[test]
+fn api_to_obj_mut_gc() {
+    use std::cell::RefCell;
+
+    struct Cont {
+        values: Vec<i32>
+    }
+
+    let mruby = Mruby::new();
+
+    mruby.def_class_cell::<Cont>("Container");
+
+    let mut cont = {
+        let value = mruby.obj(RefCell::new(Cont { values: vec![1, 2] }));
+
+        value.to_obj_mut::<Cont>().unwrap()
+    };
+
+    mruby.run("nil").unwrap();
+    mruby.full_gc();
+
+    cont.values.push(3);
+
+    assert_eq!(cont.values, vec![1, 2, 3]);
+}
+
+#[test]
+fn api_enumerator() {
+    let mruby = Mruby::new();
+
+    let fib = mruby.run("
+      class Fib
+        include Enumerable
+
+        def each
+          a, b = 0, 1
+
+          loop do
+            yield a
+            a, b = b, a + b
+          end
+        end
+      end
+
+      Fib.new
+    ").unwrap();
+
+    let values: Vec<i32> = fib.enumerator().unwrap().take(8).map(|value| {
+        value.unwrap().to_i32().unwrap()
+    }).collect();
+
+    assert_eq!(values, vec![0, 1, 1, 2, 3, 5, 8, 13]);
+
+    let hash = mruby.run("{ a: 1, b: 2 }").unwrap();
+    let pairs: Vec<Value> = hash.enumerator().unwrap().map(|pair| pair.unwrap()).collect();
+
+    assert_eq!(pairs, vec![mruby.run("[:a, 1]").unwrap(), mruby.run("[:b, 2]").unwrap()]);
+    assert_eq!(mruby.run("[]").unwrap().enumerator().unwrap().count(), 0);
+
+    let failing = mruby.run("
+      class Failing
+        def each
+          yield 1
+          raise ArgumentError, 'broken'
+        end
+      end
+
+      Failing.new
+    ").unwrap();
+
+    let mut iter = failing.enumerator().unwrap();
+
+    assert_eq!(iter.next().unwrap().unwrap().to_i32().unwrap(), 1);
+
+    match iter.next() {
+        Some(Err(err)) => assert!(err.to_string().contains("broken")),
+        _ => panic!("exception was not returned")
+    }
+
+    assert!(iter.next().is_none());
+
+    match mruby.nil().enumerator() {
+        Err(MrubyError::Cast(_)) => (),
+        _ => panic!("nil is not enumerable")
+    }
+}
+
+#[test]
+fn api_accessors() {
+    use std::cell::RefCell;
+
+    struct Config {
+        timeout: i32,
+        name: String
+    }
+
+    mrclass!(RefCell<Config>, {
+        def!("initialize", || {
+            RefCell::new(Config { timeout: 10, name: "default".to_owned() })
+        });
+
+        accessors!(timeout: i32, name: String);
+    });
+
+    let mruby = Mruby::new();
+
+    Config::require(mruby.clone());
+
+    let config = mruby.run("$config = Config.new").unwrap();
+
+    assert_eq!(mruby.run("$config.timeout").unwrap().to_i32().unwrap(), 10);
+    assert_eq!(mruby.run("$config.timeout = 30").unwrap().to_i32().unwrap(), 30);
+    assert_eq!(mruby.run("$config.timeout").unwrap().to_i32().unwrap(), 30);
+
+    mruby.run("$config.name = 'fast'").unwrap();
+
+    assert_eq!(config.to_obj_mut::<Config>().unwrap().name, "fast");
+
+    let result = mruby.run("
+      begin
+        $config.timeout = 'slow'
+      rescue TypeError => e
+        e.message
+      end
+    ").unwrap();
+
+    assert_eq!(result.to_str().unwrap(), "Cast error: expected Fixnum");
+    assert_eq!(config.to_obj_mut::<Config>().unwrap().timeout, 30);
+}
+
+#[test]
+fn api_private_method() {
+    let mruby = Mruby::new();
+
+    struct Cont;
+
+    mruby.def_class::<Cont>("Container");
+    mruby.def_private_method::<Cont, _>("secret", mrfn!(|mruby, _slf: Value| {
+        mruby.fixnum(42)
+    }));
+    mruby.def_protected_method::<Cont, _>("shared", mrfn!(|mruby, _slf: Value| {
+        mruby.fixnum(7)
+    }));
+
+    let result = mruby.run("
+      class Container
+        def reveal
+          [1].map { secret }.first + shared
+        end
+
+        def peek(other)
+          other.shared
+        end
+      end
+
+      c = Container.new
+      [c.reveal, c.peek(Container.new)]
+    ").unwrap();
+
+    assert_eq!(result, mruby.run("[49, 7]").unwrap());
+
+    let result = mruby.run("
+      begin
+        Container.new.secret
+      rescue NoMethodError => e
+        e.message
+      end
+    ").unwrap();
+
+    assert_eq!(result.to_str().unwrap(), "private method 'secret' called for Container");
+
+    assert!(mruby.run("Container.new.peek(Container.new) && Container.new.shared").is_err());
+
+    let cont = mruby.run("Container.new").unwrap();
+
+    assert!(cont.call("secret", vec![]).is_err());
+}
+
+#[test]
+fn api_alias_method() {
+    let mruby = Mruby::new();
+
+    mruby.run("
+      module Greeter
+        class Person
+          def greet
+            'hi'
+          end
+        end
+      end
+    ").unwrap();
+
+    mruby.alias_method("Greeter::Person", "plain_greet", "greet").unwrap();
+
+    mruby.def_method_on("Greeter::Person", "greet", mrfn!(|mruby, slf: Value| {
+        let plain = slf.call("plain_greet", vec![]).unwrap();
+
+        mruby.string(&(plain.to_str().unwrap().to_owned() + " from Rust"))
+    })).unwrap();
+
+    let result = mruby.run("p = Greeter::Person.new; [p.greet, p.plain_greet]").unwrap();
+
+    assert_eq!(result, mruby.run("['hi from Rust', 'hi']").unwrap());
+
+    match mruby.alias_method("Greeter::Nobody", "a", "greet") {
+        Err(MrubyError::Undef) => (),
+        _ => panic!("Greeter::Nobody is not defined")
+    }
+
+    match mruby.alias_method("Greeter::Person", "a", "wave") {
+        Err(MrubyError::Undef) => (),
+        _ => panic!("Greeter::Person#wave is not defined")
+    }
+
+    mruby.alias_method("Greeter::Person", "hello", "greet").unwrap();
+
+    let result = mruby.run("Greeter::Person.new.hello").unwrap();
+
+    assert_eq!(result.to_str().unwrap(), "hi from Rust");
+
+    struct Cont;
+
+    mruby.def_class::<Cont>("Container");
+    mruby.def_method::<Cont, _>("size", mrfn!(|mruby, _slf: Value| {
+        mruby.fixnum(3)
+    }));
+
+    mruby.alias_method("Container", "length", "size").unwrap();
+
+    assert_eq!(mruby.run("Container.new.length").unwrap().to_i32().unwrap(), 3);
+
+    mruby.run("class Sub < Container; end").unwrap();
+    mruby.alias_method("Sub", "count", "size").unwrap();
+
+    assert_eq!(mruby.run("Sub.new.count").unwrap().to_i32().unwrap(), 3);
+
+    mruby.def_method_on("Sub", "count", mrfn!(|mruby, _slf: Value| {
+        mruby.fixnum(4)
+    })).unwrap();
+
+    assert_eq!(mruby.run("Sub.new.count").unwrap().to_i32().unwrap(), 4);
+}
+
+#[test]
+fn api_call_sym() {
+    let mruby = Mruby::new();
+
+    let array = mruby.run("[3, 1, 2]").unwrap();
+    let sort = mruby.sym("sort");
+
+    assert_eq!(array.call_sym(sort, vec![]).unwrap(), mruby.run("[1, 2, 3]").unwrap());
+    assert!(array.call_sym(mruby.sym("nope"), vec![]).is_err());
+
+    let names = mruby.run("[:first, :last, :size]").unwrap().to_vec().unwrap();
+    let results: Vec<Value> = names.iter().map(|name| array.send(name, vec![]).unwrap()).collect();
+
+    assert_eq!(results, vec![mruby.fixnum(3), mruby.fixnum(2), mruby.fixnum(3)]);
+
+    match array.send(&mruby.string("first"), vec![]) {
+        Err(MrubyError::Cast(expected)) => assert_eq!(expected, "Symbol"),
+        _ => panic!("String selectors are not Symbols")
+    }
+}
+
+#[test]
+fn api_def_singleton_method() {
+    let mruby = Mruby::new();
+
+    let first = mruby.run("$first = 'first'").unwrap();
+
+    first.def_singleton_method("shout", mrfn!(|mruby, slf: Value| {
+        mruby.string(&(slf.to_str().unwrap().to_uppercase() + "!"))
+    })).unwrap();
+    first.def_singleton_method("id", mrfn!(|mruby, _slf: Value| {
+        mruby.fixnum(1)
+    })).unwrap();
+
+    let second = mruby.run("$second = Object.new").unwrap();
+
+    second.def_singleton_method("id", mrfn!(|mruby, _slf: Value| {
+        mruby.fixnum(2)
+    })).unwrap();
+
+    assert_eq!(mruby.run("$first.shout").unwrap().to_str().unwrap(), "FIRST!");
+    assert_eq!(mruby.run("[$first.id, $second.id, $first.clone.id]").unwrap(),
+               mruby.run("[1, 2, 1]").unwrap());
+    assert!(mruby.run("'other'.shout").is_err());
+    assert!(mruby.run("Object.new.id").is_err());
+
+    match mruby.fixnum(1).def_singleton_method("id", mrfn!(|_mruby, slf: Value| slf)) {
+        Err(MrubyError::Cast(_)) => (),
+        _ => panic!("Fixnum cannot have singleton methods")
+    }
+
+    let copy = mruby.run("$copy = $first.clone").unwrap();
+
+    copy.def_singleton_method("id", mrfn!(|mruby, _slf: Value| {
+        mruby.fixnum(3)
+    })).unwrap();
+
+    assert_eq!(mruby.run("[$first.id, $copy.id, $copy.shout]").unwrap(),
+               mruby.run("[1, 3, 'FIRST!']").unwrap());
+}
+
+#[test]
+fn api_def_singleton_method_free() {
+    use std::rc::Rc;
+
+    let mruby = Mruby::new();
+    let captured = Rc::new(());
+
+    let arena = mruby.gc_arena_save();
+
+    {
+        let captured = captured.clone();
+        let object = mruby.run("Object").unwrap().call("new", vec![]).unwrap();
+
+        object.def_singleton_method("count", move |mruby, _slf| {
+            mruby.fixnum(Rc::strong_count(&captured) as i32)
+        }).unwrap();
+    }
+
+    assert_eq!(Rc::strong_count(&captured), 2);
+
+    mruby.gc_arena_restore(arena);
+    mruby.full_gc();
+
+    assert_eq!(Rc::strong_count(&captured), 1);
+}
+
+#[test]
+fn api_def_spaceship() {
+    let mruby = Mruby::new();
+
+    #[derive(PartialEq, Eq, PartialOrd, Ord)]
+    struct Priority {
+        level: i32
+    }
+
+    mruby.def_class::<Priority>("Priority");
+    mruby.def_method::<Priority, _>("initialize", mrfn!(|_mruby, slf: Value, level: i32| {
+        slf.init(Priority { level: level })
+    }));
+    mruby.def_method::<Priority, _>("level", mrfn!(|mruby, slf: Priority| {
+        mruby.fixnum(slf.level)
+    }));
+
+    mruby.def_spaceship::<Priority>();
+    mruby.include_module::<Priority>("Comparable").unwrap();
+
+    let result = mruby.run("
+      [3, 1, 2].map { |level| Priority.new(level) }.sort.map(&:level)
+    ").unwrap();
+
+    assert_eq!(result, mruby.run("[1, 2, 3]").unwrap());
+
+    let result = mruby.run("
+      a = Priority.new(1)
+      b = Priority.new(2)
+
+      [a <=> b, b <=> a, a <=> Priority.new(1), a <=> 1, a < b, a == Priority.new(1)]
+    ").unwrap();
+
+    assert_eq!(result, mruby.run("[-1, 1, 0, nil, true, true]").unwrap());
+    assert!(mruby.run("Priority.new(1) < 2").is_err());
+}
+
+#[test]
+fn api_run_named() {
+    let mruby = Mruby::new();
+
+    match mruby.run_named("first.rb", "\n1.nope") {
+        Err(MrubyError::Runtime(err)) => {
+            assert_eq!(err, "first.rb:2: undefined method 'nope' for 1 (NoMethodError)");
+        },
+        _ => panic!("first.rb should fail")
+    }
+
+    match mruby.run("1.nope") {
+        Err(MrubyError::Runtime(err)) => {
+            assert_eq!(err, "NoMethodError: undefined method 'nope' for 1");
+        },
+        _ => panic!("unnamed run should fail")
+    }
+
+    mruby.filename("main.rb");
+
+    assert!(mruby.run_named("second.rb", "1 + 1").is_ok());
+
+    match mruby.run("1.nope") {
+        Err(MrubyError::Runtime(err)) => {
+            assert_eq!(err, "main.rb:1: undefined method 'nope' for 1 (NoMethodError)");
+        },
+        _ => panic!("main.rb should fail")
+    }
+}
+
+#[test]
+fn api_call_proc() {
+    let mruby = Mruby::new();
+
+    let handlers = mruby.run("
+      $log = []
+
+      $handlers = [
+        proc { |name| $log << name },
+        proc { |name| raise 'nope' if name == :fail }
+      ]
+    ").unwrap().to_vec().unwrap();
+
+    for name in &["start", "stop"] {
+        for handler in &handlers {
+            handler.call_proc(vec![mruby.symbol(name)]).unwrap();
+        }
+    }
+
+    assert_eq!(mruby.run("$log").unwrap(), mruby.run("[:start, :stop]").unwrap());
+    assert!(handlers[1].call_proc(vec![mruby.symbol("fail")]).is_err());
+
+    let double = mruby.proc_from(|mruby, args| mruby.fixnum(args[0].to_i32().unwrap() * 2));
+
+    assert_eq!(double.call_proc(vec![mruby.fixnum(4)]).unwrap().to_i32().unwrap(), 8);
+
+    match mruby.nil().call_proc(vec![]) {
+        Err(MrubyError::Cast(expected)) => assert_eq!(expected, "Proc"),
+        _ => panic!("nil is not a Proc")
+    }
+}
+
+#[test]
+fn api_gc_protect() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let mruby = Mruby::new();
+
+    let handlers = Rc::new(RefCell::new(vec![]));
+    let registry = handlers.clone();
+
+    mruby.def_method_on("Object", "on_event", mrfn!(|mruby, _slf: Value, &block| {
+        block.gc_protect();
+        registry.borrow_mut().push(block);
+
+        mruby.nil()
+    })).unwrap();
+
+    mruby.run("
+      $fired = []
+
+      on_event { |event| $fired << [:first, event] }
+      on_event { |event| $fired << [:second, event.to_s.size] }
+    ").unwrap();
+
+    mruby.run("1000.times { |i| i.to_s * 10 }").unwrap();
+    mruby.full_gc();
+
+    for handler in handlers.borrow().iter() {
+        handler.call_proc(vec![mruby.symbol("save")]).unwrap();
+    }
+
+    assert_eq!(mruby.run("$fired").unwrap(),
+               mruby.run("[[:first, :save], [:second, 4]]").unwrap());
+
+    let shared = handlers.borrow()[0].clone();
+
+    shared.gc_protect();
+    shared.gc_release();
+
+    mruby.run("1000.times { |i| i.to_s * 10 }").unwrap();
+    mruby.full_gc();
+
+    assert!(shared.call_proc(vec![mruby.symbol("load")]).is_ok());
+
+    for handler in handlers.borrow_mut().drain(..) {
+        handler.gc_release();
+    }
+}
+
+#[test]
+fn api_gc_protect_cache() {
+    let mruby = Mruby::new();
+
+    let cache: Vec<Value> = (0..10).map(|i| {
+        mruby.run(&format!("'value {}' * 2", i)).unwrap()
+    }).collect();
+
+    for value in &cache {
+        value.gc_protect();
+    }
+
+    mruby.run("$garbage = (0..1000).map { |i| i.to_s * 10 }; $garbage = nil").unwrap();
+    mruby.full_gc();
+    mruby.run("(0..1000).map { |i| i.to_s * 10 }").unwrap();
+
+    for (i, value) in cache.iter().enumerate() {
+        assert_eq!(value.to_str().unwrap(), format!("value {}value {}", i, i));
+    }
+
+    for value in &cache {
+        value.gc_release();
+    }
+}
+
+#[test]
+fn api_gc_stats() {
+    let mruby = Mruby::new();
+
+    mruby.full_gc();
+
+    let before = mruby.gc_stats();
+
+    mruby.run("$kept = (0..500).map { |i| i.to_s }").unwrap();
+    mruby.full_gc();
+
+    let after = mruby.gc_stats();
+
+    assert!(after.live >= before.live + 500);
+    assert!(after.live <= after.total);
+
+    let idx = mruby.gc_arena_save();
+
+    let _values: Vec<Value> = (0..5).map(|i| mruby.string(&i.to_string())).collect();
+
+    assert_eq!(mruby.gc_stats().arena_index, idx as usize + 5);
+
+    mruby.gc_arena_restore(idx);
+
+    assert_eq!(mruby.gc_stats().arena_index, idx as usize);
+}
+
+#[test]
+fn api_def_class_reopen() {
+    let mruby = Mruby::new();
+
+    struct Counter;
+
+    mruby.def_class::<Counter>("Counter");
+    mruby.def_method::<Counter, _>("initialize", mrfn!(|_mruby, slf: Value| {
+        slf.init(Counter)
+    }));
+    mruby.def_method::<Counter, _>("one", mrfn!(|mruby, _slf: Value| {
+        mruby.fixnum(1)
+    }));
+
+    mruby.def_class::<Counter>("Counter");
+    mruby.def_method::<Counter, _>("two", mrfn!(|mruby, _slf: Value| {
+        mruby.fixnum(2)
+    }));
+
+    let result = mruby.run("c = Counter.new; c.one + c.two").unwrap();
+
+    assert_eq!(result.to_i32().unwrap(), 3);
+    assert_eq!(mruby.class_name::<Counter>().unwrap(), "Counter");
+}
+
+#[test]
+fn api_user_data() {
+    use std::cell::Cell;
+
+    struct Host {
+        calls: Cell<i32>
+    }
+
+    let mruby = Mruby::new();
+
+    mruby.set_user_data(Host { calls: Cell::new(0) });
+    mruby.def_method_on("Object", "track", mrfn!(|mruby, _slf: Value| {
+        let host = mruby.user_data::<Host>().unwrap();
+
+        host.calls.set(host.calls.get() + 1);
+
+        mruby.fixnum(host.calls.get())
+    })).unwrap();
+
+    let result = mruby.run("3.times { track }; track").unwrap();
+
+    assert_eq!(result.to_i32().unwrap(), 4);
+    assert_eq!(mruby.user_data::<Host>().unwrap().calls.get(), 4);
+    assert!(mruby.user_data::<String>().is_none());
+}
+
+#[test]
+fn api_load_file() {
+    let mruby = Mruby::new();
+
+    let result = mruby.load_file(Path::new("tests/load/sum.rb")).unwrap();
+
+    assert_eq!(result.to_i32().unwrap(), 6);
+
+    match mruby.load_file(Path::new("tests/load/failing.rb")) {
+        Err(MrubyError::Runtime(err)) => {
+            assert_eq!(err, "failing.rb:4: undefined method 'nope' for 3 (NoMethodError)");
+        },
+        _ => assert!(false)
+    }
+
+    match mruby.load_file(Path::new("tests/load/broken.rb")) {
+        Err(MrubyError::Syntax { line, .. }) => assert_eq!(line, 3),
+        _ => assert!(false)
+    }
+
+    assert!(mruby.load_file(Path::new("tests/load/missing.rb")).is_err());
+
+    Scalar::require(mruby.clone());
+
+    let result = mruby.load_file(Path::new("tests/compiled.mrb")).unwrap();
+
+    assert_eq!(*result.to_obj::<Scalar>().unwrap(), Scalar::new(2.0));
+}
+
+#[test]
+fn api_methods() {
+    let mruby = Mruby::new();
+
+    struct Cont;
+
+    mruby.def_class::<Cont>("Container");
+    mruby.def_method::<Cont, _>("initialize", mrfn!(|_mruby, slf: Value| {
+        slf.init(Cont)
+    }));
+    mruby.def_method::<Cont, _>("value", mrfn!(|mruby, _slf: Value| {
+        mruby.fixnum(3)
+    }));
+
+    let methods = mruby.class_methods("Container");
+
+    assert!(methods.contains(&"value".to_owned()));
+    assert!(methods.contains(&"dup".to_owned()));
+
+    let cont = mruby.run("c = Container.new; def c.extra; end; c").unwrap();
+    let methods = cont.methods();
+
+    assert!(methods.contains(&"value".to_owned()));
+    assert!(methods.contains(&"extra".to_owned()));
+    assert!(!mruby.class_methods("Container").contains(&"extra".to_owned()));
+}
+
+#[test]
+fn api_symbol_enum() {
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Mode {
+        Read,
+        Write,
+        ReadWrite
+    }
+
+    symbol_enum!(Mode {
+        Read      => "read",
+        Write     => "write",
+        ReadWrite => "read_write",
+    });
+
+    let mruby = Mruby::new();
+
+    mruby.def_method_on("Object", "reopen", mrfn!(|mruby, _slf: Value, mode: Value| {
+        match Mode::from_value(&mode) {
+            Ok(Mode::Read) => Mode::ReadWrite.to_value(&mruby),
+            Ok(mode)       => mode.to_value(&mruby),
+            Err(err)       => mruby.raise("ArgumentError", &err.to_string())
+        }
+    })).unwrap();
+
+    let result = mruby.run("reopen :read").unwrap();
+
+    assert_eq!(Mode::from_value(&result).unwrap(), Mode::ReadWrite);
+    assert_eq!(mruby.run("reopen :write").unwrap(), mruby.symbol("write"));
+
+    match mruby.run("reopen 'read'") {
+        Err(MrubyError::Runtime(err)) => {
+            assert_eq!(err, "ArgumentError: Cast error: expected one of :read, :write, \
+                             :read_write");
+        },
+        _ => assert!(false)
+    }
+
+    assert!(Mode::from_value(&mruby.symbol("append")).is_err());
+}
+
+#[test]
+fn api_to_sym_name() {
+    let mruby = Mruby::new();
+
+    let values = mruby.run("[:open, 'open', 1]").unwrap().to_vec().unwrap();
+
+    assert_eq!(values[0].to_sym_name().unwrap(), "open");
+    assert_eq!(values[1].to_string_only().unwrap(), "open");
+
+    match values[1].to_sym_name() {
+        Err(MrubyError::Cast(expected)) => assert_eq!(expected, "Symbol"),
+        _ => assert!(false)
+    }
+
+    match values[0].to_string_only() {
+        Err(MrubyError::Cast(expected)) => assert_eq!(expected, "String"),
+        _ => assert!(false)
+    }
+
+    assert!(values[2].to_sym_name().is_err());
+    assert!(values[2].to_string_only().is_err());
+}
+
+#[test]
+fn api_pretty_error() {
+    let mruby = Mruby::new();
+
+    let source = "a = 1\nb = (a +\n  2))\n";
+    let err = mruby.run(source).unwrap_err();
+
+    assert_eq!(err.pretty(Some(source)),
+               "Syntax error: line 3, column 5: syntax error, unexpected ')', expecting $end\n\
+                \x20 |\n\
+                3 |   2))\n\
+                \x20 |     ^");
+
+    let source = (1..12).map(|i| format!("x{} = {}", i, i)).collect::<Vec<_>>().join("\n") +
+                 "\n\tx11.nope";
+    let err = mruby.run_named("long.rb", &source).unwrap_err();
+
+    assert_eq!(err.pretty(Some(&source)),
+               "Runtime error: long.rb:12: undefined method 'nope' for 11 (NoMethodError)\n\
+                \x20  |\n\
+                12 | \tx11.nope\n\
+                \x20  | \t^^^^^^^^");
+
+    let err = mruby.run("1.nope").unwrap_err();
+
+    assert_eq!(err.pretty(Some("1.nope")), err.to_string());
+    assert_eq!(MrubyError::Undef.pretty(Some("1.nope")), MrubyError::Undef.to_string());
+
+    let err = mruby.run("a = (").unwrap_err();
+
+    assert_eq!(err.pretty(Some("")), err.to_string());
+}
+
+#[test]
+fn api_array_of() {
+    let mruby = Mruby::new();
+
+    let array = mruby.array_of((1..4).map(|i| i * 10));
+
+    mruby.set_global("numbers", array);
+
+    assert_eq!(mruby.run("$numbers.inject(:+)").unwrap().to_i32().unwrap(), 60);
+
+    let nested = mruby.array_of(vec![vec![Some("a")], vec![None]]);
+
+    assert_eq!(nested.to_string(), "[[\"a\"], [nil]]");
+    assert_eq!(mruby.array_of(Vec::<i32>::new()).to_vec().unwrap(), vec![]);
+}
+
+#[test]
+fn api_to_vec_of() {
+    let mruby = Mruby::new();
+
+    let numbers = mruby.run("(1..4).map { |i| i * i }").unwrap();
+
+    assert_eq!(numbers.to_vec_of::<i32>().unwrap(), vec![1, 4, 9, 16]);
+
+    let names = mruby.run("%w(a b c)").unwrap();
+
+    assert_eq!(names.to_vec_of::<String>().unwrap(), vec!["a", "b", "c"]);
+
+    let mixed = mruby.run("[1, 2, nil, 'four']").unwrap();
+
+    match mixed.to_vec_of::<i32>() {
+        Err(MrubyError::Cast(expected)) => assert_eq!(expected, "Fixnum at index 2"),
+        _ => assert!(false)
+    }
+
+    assert_eq!(mixed.to_vec_of::<Option<i32>>().unwrap_err().to_string(),
+               "Cast error: expected Fixnum at index 3");
+
+    match mruby.fixnum(1).to_vec_of::<i32>() {
+        Err(MrubyError::Cast(expected)) => assert_eq!(expected, "Array"),
+        _ => assert!(false)
+    }
+}
+
+#[test]
+fn api_to_bignum_string() {
+    let mruby = Mruby::new();
+
+    let values = mruby.run("[2 ** 62, -(2 ** 53), 3 * 10 ** 2, 'big', 1.0 / 0]").unwrap()
+                      .to_vec().unwrap();
+
+    assert_eq!(values[0].to_bignum_string().unwrap(), "4611686018427387904");
+    assert_eq!(values[1].to_bignum_string().unwrap(), "-9007199254740992");
+    assert_eq!(values[2].to_bignum_string().unwrap(), "300");
+
+    match values[3].to_bignum_string() {
+        Err(MrubyError::Cast(expected)) => assert_eq!(expected, "Integer"),
+        _ => assert!(false)
+    }
+
+    assert!(values[4].to_bignum_string().is_err());
+}
+
+#[test]
+fn api_run_capturing() {
+    let mruby = Mruby::new();
+
+    mruby.def_method_on("Object", "inner", mrfn!(|mruby, _slf: Value| {
+        let (_, output) = mruby.run_capturing("puts 'inner'").unwrap();
+
+        mruby.string(&output)
+    })).unwrap();
+
+    let (value, output) = mruby.run_capturing("
+      puts 'before'
+      captured = inner
+      puts captured.upcase
+      p [1, nil]
+
+      captured
+    ").unwrap();
+
+    assert_eq!(value.to_str().unwrap(), "inner\n");
+    assert_eq!(output, "before\nINNER\n[1, nil]\n");
+
+    assert!(mruby.run_capturing("puts 1; raise 'failed'").is_err());
+
+    let hooked = mruby.run("Kernel.instance_methods.include?(:__mrusty_printstr__)").unwrap();
+
+    assert!(!hooked.to_bool().unwrap());
+
+    let (_, output) = mruby.run_capturing("print 'again'").unwrap();
+
+    assert_eq!(output, "again");
+}
+
+#[test]
+fn api_def_alias() {
+    let mruby = Mruby::new();
+
+    struct Stack {
+        items: Vec<i32>
+    }
+
+    mruby.def_class::<Stack>("Stack");
+    mruby.def_method::<Stack, _>("initialize", mrfn!(|_mruby, slf: Value| {
+        slf.init(Stack { items: vec![4, 5] })
+    }));
+    mruby.def_method::<Stack, _>("length", mrfn!(|mruby, slf: Stack| {
+        mruby.fixnum(slf.items.len() as i32)
+    }));
+
+    mruby.def_alias::<Stack>("size", "length").unwrap();
+    mruby.def_alias::<Stack>("describe", "inspect").unwrap();
+
+    let result = mruby.run("s = Stack.new; [s.length, s.size]").unwrap();
+
+    assert_eq!(result.to_vec_of::<i32>().unwrap(), vec![2, 2]);
+    assert!(mruby.run("Stack.new.describe").unwrap().to_str().unwrap().starts_with("#<Stack"));
+
+    assert!(mruby.def_alias::<Stack>("count", "missing").is_err());
+    assert!(mruby.def_alias::<String>("count", "length").is_err());
+}
+
+#[test]
+fn api_mrfn_type_error() {
+    let mruby = Mruby::new();
+
+    struct Cont;
+
+    mruby.def_class::<Cont>("Container");
+    mruby.def_method::<Cont, _>("initialize", mrfn!(|_mruby, slf: Value| {
+        slf.init(Cont)
+    }));
+    mruby.def_method::<Cont, _>("pair", mrfn!(|mruby, _slf: Value, _a: i32, _b: Cont| {
+        mruby.nil()
+    }));
+    mruby.def_method::<Cont, _>("name", mrfn!(|mruby, _slf: Value, a: String, _b: i64| {
+        mruby.string(&a)
+    }));
+    mruby.def_method::<Cont, _>("opt", mrfn!(|mruby, _slf: Value, _a: i32,
+                                            _b: Option<String>| {
+        mruby.nil()
+    }));
+    mruby.def_method::<Cont, _>("kw", mrfn!(|mruby, _slf: Value; width: i32| {
+        mruby.fixnum(width)
+    }));
+    mruby.def_method::<Cont, _>("join", mrfn!(|mruby, _slf: Value, a: str, b: Vec| {
+        mruby.string(&format!("{}{}", a, b.len()))
+    }));
+
+    let errors = vec![
+        ("Container.new.pair 1, 2",
+         "TypeError: wrong argument type Fixnum for argument 2 (expected Container)"),
+        ("Container.new.name 1, 2",
+         "TypeError: wrong argument type Fixnum for argument 1 (expected String)"),
+        ("Container.new.name 'a', 'b'",
+         "TypeError: wrong argument type String for argument 2 (expected Fixnum)"),
+        ("Container.new.opt 1, 2",
+         "TypeError: wrong argument type Fixnum for argument 2 (expected String)"),
+        ("Container.new.kw width: 'a'",
+         "TypeError: wrong argument type String for keyword width (expected Fixnum)"),
+        ("Container.new.join 1, []",
+         "TypeError: wrong argument type Fixnum for argument 1 (expected String)"),
+        ("Container.new.join 'a', 'b'",
+         "TypeError: wrong argument type String for argument 2 (expected Array)")
+    ];
+
+    for (script, message) in errors {
+        match mruby.run(script) {
+            Err(MrubyError::Runtime(err)) => assert_eq!(err, message),
+            _ => assert!(false)
+        }
+    }
+
+    assert!(mruby.run("Container.new.pair 1, Container.new").unwrap().is_nil());
+    assert_eq!(mruby.run("Container.new.name 'a', 2").unwrap().to_str().unwrap(), "a");
+    assert!(mruby.run("Container.new.opt 1").unwrap().is_nil());
+    assert_eq!(mruby.run("Container.new.join 'a', [1, 2]").unwrap().to_str().unwrap(), "a2");
+}
+
+#[test]
+fn api_class() {
+    let mruby = Mruby::new();
+
+    let values = mruby.run("
+      module Outer
+        class Item; end
+      end
+
+      class Item; end
+
+      a = Outer::Item.new
+      def a.extra; end
+
+      [a, Outer::Item.new, Item.new]
+    ").unwrap().to_vec().unwrap();
+
+    let class = values[0].class();
+
+    assert_eq!(class.to_string(), "Outer::Item");
+    assert_eq!(class, mruby.run("Outer::Item").unwrap());
+    assert!(class.call("new", vec![]).unwrap().class_eq(&values[1]));
+
+    assert!(values[0].class_eq(&values[1]));
+    assert!(!values[0].class_eq(&values[2]));
+
+    assert_eq!(class.class().to_string(), "Class");
+    assert!(mruby.nil().class_eq(&mruby.run("nil").unwrap()));
+}
+
+#[test]
+fn api_def_methods() {
+    use std::rc::Rc;
+
+    let mruby = Mruby::new();
+
+    struct Schema;
+
+    mruby.def_class::<Schema>("Schema");
+
+    let names = vec!["first", "second", "third"];
+
+    let methods = names.iter().enumerate().map(|(i, name)| {
+        let label = format!("{}:{}", name, i);
+        let method: Rc<Fn(MrubyType, Value) -> Value> = Rc::new(move |mruby, _slf| {
+            mruby.string(&label)
+        });
+
+        (name.to_string(), method)
+    }).collect();
+
+    mruby.def_methods::<Schema>(methods);
+
+    let result = mruby.run("s = Schema.new; [s.first, s.second, s.third]").unwrap();
+
+    assert_eq!(result.to_vec_of::<String>().unwrap(), vec!["first:0", "second:1", "third:2"]);
+    assert!(mruby.class_methods("Schema").contains(&"second".to_owned()));
+}
+
+#[test]
+fn api_panic_error() {
+    let mruby = Mruby::new();
+
+    mruby.def_method_on("Object", "explode", mrfn!(|_mruby, _slf: Value, reason: str| {
+        panic!("exploded: {}", reason)
+    })).unwrap();
+
+    match mruby.run("explode 'fuse'") {
+        Err(MrubyError::Panic(message)) => assert_eq!(message, "exploded: fuse"),
+        _ => panic!("explode should panic")
+    }
+
+    match mruby.top_self().call("explode", vec![mruby.string("wire")]) {
+        Err(err @ MrubyError::Panic(_)) => {
+            assert_eq!(err.to_string(), "Rust panic: exploded: wire");
+        },
+        _ => panic!("explode should panic")
+    }
+
+    match mruby.run("raise 'from Ruby'") {
+        Err(MrubyError::Runtime(message)) => assert_eq!(message, "RuntimeError: from Ruby"),
+        _ => panic!("raise should be a runtime error")
+    }
+
+    let rescued = mruby.run("begin; explode 'x'; rescue RustPanic => e; e.message; end").unwrap();
+
+    assert_eq!(rescued.to_str().unwrap(), "exploded: x");
+
+    let handler = mruby.proc_from(|_mruby, args| panic!("in a proc: {}", args.len()));
+
+    match handler.call_proc(vec![mruby.nil()]) {
+        Err(MrubyError::Panic(message)) => assert_eq!(message, "in a proc: 1"),
+        _ => panic!("the proc should panic")
+    }
+}
+
+#[test]
+fn api_const_get() {
+    let mruby = Mruby::new();
+
+    mruby.run("
+      module Outer
+        LIMIT = 3
+
+        module Inner
+          class Settings
+            def self.limit
+              Outer::LIMIT * 2
+            end
+          end
+        end
+      end
+    ").unwrap();
+
+    let settings = mruby.const_get("Outer::Inner::Settings").unwrap();
+
+    assert_eq!(settings.call("limit", vec![]).unwrap().to_i32().unwrap(), 6);
+    assert_eq!(mruby.const_get("Outer::LIMIT").unwrap().to_i32().unwrap(), 3);
+    assert_eq!(mruby.const_get("Outer").unwrap(), mruby.run("Outer").unwrap());
+
+    match mruby.const_get("Outer::Missing::Settings") {
+        Err(MrubyError::Undef) => (),
+        _ => panic!("expected Undef")
+    }
+
+    match mruby.const_get("Outer::LIMIT::Nope") {
+        Err(MrubyError::Undef) => (),
+        _ => panic!("expected Undef")
+    }
+
+    assert!(mruby.const_get("Outer::Inner::Nope").is_err());
+}
+
+#[test]
+fn api_def_class_dyn() {
+    trait Greeter {
+        fn greet(&self, name: &str) -> String;
+    }
+
+    struct English;
+    struct French {
+        polite: bool
+    }
+
+    impl Greeter for English {
+        fn greet(&self, name: &str) -> String {
+            format!("Hello, {}!", name)
+        }
+    }
+
+    impl Greeter for French {
+        fn greet(&self, name: &str) -> String {
+            if self.polite {
+                format!("Bonjour, {}.", name)
+            } else {
+                format!("Salut, {}!", name)
+            }
+        }
+    }
+
+    let mruby = Mruby::new();
+
+    mruby.def_class_dyn("greeter", "Greeter");
+    mruby.def_class_dyn("greeter", "Greeter");
+
+    mruby.def_method_on("Greeter", "initialize", mrfn!(|_mruby, slf: Value, lang: String| {
+        let greeter: Box<Greeter> = match lang.as_ref() {
+            "fr" => Box::new(French { polite: true }),
+            _    => Box::new(English)
+        };
+
+        slf.init_dyn("greeter", greeter)
+    })).unwrap();
+    mruby.def_method_on("Greeter", "greet", mrfn!(|mruby, slf: Value, name: String| {
+        let greeter = slf.to_obj_dyn::<Box<Greeter>>().unwrap();
+
+        mruby.string(&greeter.greet(&name))
+    })).unwrap();
+
+    let result = mruby.run("[Greeter.new('en'), Greeter.new('fr')].map { |g| g.greet 'Ann' }")
+                      .unwrap();
+
+    assert_eq!(result.to_vec_of::<String>().unwrap(),
+               vec!["Hello, Ann!".to_owned(), "Bonjour, Ann.".to_owned()]);
+
+    let casual: Box<Greeter> = Box::new(French { polite: false });
+
+    mruby.top_self().set_var("casual", mruby.obj_dyn("greeter", casual));
+
+    let result = mruby.run("@casual.greet 'Bob'").unwrap();
+
+    assert_eq!(result.to_str().unwrap(), "Salut, Bob!");
+
+    let result = mruby.run("Greeter.new('en')").unwrap();
+
+    match result.to_obj_dyn::<English>() {
+        Err(MrubyError::Cast(expected)) => assert_eq!(expected, "Greeter of another Rust type"),
+        _ => panic!("expected Cast")
+    }
+
+    match mruby.string("Greeter").to_obj_dyn::<Box<Greeter>>() {
+        Err(MrubyError::Undef) => (),
+        _ => panic!("expected Undef")
+    }
+}
+
+#[test]
+fn api_ruby_dup() {
+    let mruby = Mruby::new();
+
+    let original = mruby.run("{ name: 'config' }").unwrap();
+    let copy = original.ruby_dup();
+
+    copy.call("[]=", vec![mruby.symbol("name"), mruby.string("copy")]).unwrap();
+
+    let name = original.call("[]", vec![mruby.symbol("name")]).unwrap();
+
+    assert_eq!(name.to_str().unwrap(), "config");
+
+    let handle = original.clone();
+
+    handle.call("[]=", vec![mruby.symbol("name"), mruby.string("handle")]).unwrap();
+
+    let name = original.call("[]", vec![mruby.symbol("name")]).unwrap();
+
+    assert_eq!(name.to_str().unwrap(), "handle");
+
+    let frozen = mruby.run("'frozen'.freeze").unwrap();
+
+    assert!(!frozen.ruby_dup().is_frozen());
+    assert!(frozen.ruby_clone().is_frozen());
+    assert!(frozen.ruby_clone().call("<<", vec![mruby.string("!")]).is_err());
+
+    assert_eq!(mruby.symbol("sym").ruby_clone(), mruby.symbol("sym"));
+    assert!(mruby.nil().ruby_dup().call("nil?", vec![]).unwrap().to_bool().unwrap());
+}
+
+#[test]
+fn api_time() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let mruby = Mruby::new();
+
+    let launch = UNIX_EPOCH + Duration::new(1_234_567_890, 123_456_789);
+    let time = mruby.time(launch);
+
+    assert!(time.is_a("Time"));
+    assert_eq!(time.to_systemtime().unwrap(), UNIX_EPOCH + Duration::new(1_234_567_890,
+                                                                           123_456_000));
+
+    mruby.top_self().set_var("launch", time);
+
+    let later = mruby.run("@launch + 1.5").unwrap();
+
+    assert_eq!(later.to_systemtime().unwrap(), UNIX_EPOCH + Duration::new(1_234_567_891,
+                                                                            623_456_000));
+
+    let before = UNIX_EPOCH - Duration::new(10, 250_000_000);
+    let time = mruby.time(before);
+
+    assert_eq!(time.call("to_i", vec![]).unwrap().to_i64().unwrap(), -11);
+    assert_eq!(time.to_systemtime().unwrap(), before);
+
+    match mruby.string("2016-01-01").to_systemtime() {
+        Err(MrubyError::Cast(expected)) => assert_eq!(expected, "Time"),
+        _ => panic!("expected Cast")
+    }
+}
+
+#[test]
+fn api_set_argv() {
+    let mruby = Mruby::new();
+
+    let script = "
+      case ARGV.first
+      when 'greet' then \"#{$0}: hello #{ARGV[1]}\"
+      when nil     then \"#{$0}: no command\"
+      else              \"#{$0}: unknown #{ARGV.first}\"
+      end
+    ";
+
+    mruby.set_argv(vec!["cli", "greet", "Ann"]);
+
+    assert_eq!(mruby.run(script).unwrap().to_str().unwrap(), "cli: hello Ann");
+
+    mruby.set_argv(vec!["cli", "dance"]);
+
+    assert_eq!(mruby.run(script).unwrap().to_str().unwrap(), "cli: unknown dance");
+
+    mruby.set_argv(vec!["tool"]);
+
+    assert_eq!(mruby.run(script).unwrap().to_str().unwrap(), "tool: no command");
+
+    mruby.set_argv(vec![]);
+
+    assert_eq!(mruby.run(script).unwrap().to_str().unwrap(), ": no command");
+}
+
+#[test]
+fn api_data_ptr() {
+    use std::mem;
+    use std::os::raw::c_void;
+    use std::rc::Rc;
+
+    struct Cont {
+        value: i32
+    }
+
+    let mruby = Mruby::new();
+
+    mruby.def_class::<Cont>("Container");
+
+    let first = mruby.obj(Cont { value: 1 });
+    let second = mruby.obj(Cont { value: 2 });
+
+    unsafe {
+        assert!(!first.data_ptr().is_null());
+        assert!(first.data_ptr() != second.data_ptr());
+        assert_eq!(first.data_ptr(), first.clone().data_ptr());
+
+        let rc = mem::transmute::<*const c_void, Rc<Cont>>(second.data_ptr());
+
+        assert_eq!(rc.value, 2);
+
+        mem::forget(rc);
+
+        assert!(mruby.string("data").data_ptr().is_null());
+        assert!(mruby.nil().data_ptr().is_null());
+    }
+}
+
+#[test]
+fn api_string_or_nil() {
+    struct Profile {
+        name:     String,
+        nickname: Option<String>
+    }
+
+    let mruby = Mruby::new();
+
+    let profiles = vec![
+        Profile { name: "Ann".to_owned(), nickname: Some("Annie".to_owned()) },
+        Profile { name: "Bob".to_owned(), nickname: None }
+    ];
+
+    let greet = mruby.run("
+      lambda do |name, nickname|
+        nickname ? \"#{name} (#{nickname})\" : nil
+      end
+    ").unwrap();
+
+    let results: Vec<Option<String>> = profiles.iter().map(|profile| {
+        let nickname = mruby.string_or_nil(profile.nickname.as_ref().map(|s| s.as_ref()));
+
+        greet.call("call", vec![mruby.string(&profile.name), nickname]).unwrap()
+             .to_string_option().unwrap()
+    }).collect();
+
+    assert_eq!(results, vec![Some("Ann (Annie)".to_owned()), None]);
+
+    let value = mruby.string_or_nil(Some("same"));
+
+    assert_eq!(value.to_string_option().unwrap(), Option::<String>::from_value(&value).unwrap());
+    assert_eq!(mruby.string_or_nil(None), None::<&str>.to_value(&mruby));
+
+    match mruby.run("[]").unwrap().to_string_option() {
+        Err(MrubyError::Cast(expected)) => assert_eq!(expected, "String"),
+        _ => panic!("expected Cast")
+    }
+}
+
+#[test]
+fn api_run_all() {
+    let mruby = Mruby::new();
+
+    let results = mruby.run_all(&[
+        "class Plugin; REGISTRY = []; def self.register(name); REGISTRY << name; end; end",
+        "Plugin.register 'a'",
+        "Plugin.register 'b'",
+        "Plugin::REGISTRY.join(',')"
+    ]).unwrap();
+
+    assert_eq!(results.len(), 4);
+    assert_eq!(results[3].to_str().unwrap(), "a,b");
+    assert!(mruby.run_all(&[]).unwrap().is_empty());
+
+    match mruby.run_all(&["Plugin.register 'c'", "raise 'bad plugin'", "Plugin.register 'd'"]) {
+        Err((1, MrubyError::Runtime(err))) => assert_eq!(err, "RuntimeError: bad plugin"),
+        _ => panic!("expected Runtime")
+    }
+
+    match mruby.run_all(&["1", "2", "def broken("]) {
+        Err((2, MrubyError::Syntax { line, .. })) => {
+            assert_eq!(line, 1);
+        },
+        _ => panic!("expected Syntax")
+    }
+
+    assert_eq!(mruby.run("Plugin::REGISTRY.join(',')").unwrap().to_str().unwrap(), "a,b,c");
+}
+
+#[test]
+fn api_def_ruby_method() {
+    struct Counter {
+        count: i32
+    }
+
+    struct Unregistered;
+
+    let mruby = Mruby::new();
+
+    mruby.def_module("Stats");
+    mruby.def_class_under::<Counter>("Stats", "Counter");
+    mruby.def_method::<Counter, _>("initialize", mrfn!(|_mruby, slf: Value, count: i32| {
+        slf.init(Counter { count: count })
+    }));
+    mruby.def_method::<Counter, _>("count", mrfn!(|mruby, slf: Counter| {
+        mruby.fixnum(slf.count)
+    }));
+
+    mruby.def_ruby_method::<Counter>("
+      LIMIT = 10
+
+      def over_limit?
+        count > LIMIT
+      end
+
+      def self.pair(a, b)
+        [new(a), new(b)]
+      end
+    ").unwrap();
+
+    let result = mruby.run("Stats::Counter.pair(3, 12).map(&:over_limit?)").unwrap();
+
+    assert_eq!(result.to_vec_of::<bool>().unwrap(), vec![false, true]);
+
+    match mruby.def_ruby_method::<Unregistered>("def nope; end") {
+        Err(MrubyError::Undef) => (),
+        _ => panic!("expected Undef")
+    }
+
+    assert!(mruby.def_ruby_method::<Counter>("def broken(").is_err());
+    assert!(mruby.def_ruby_method::<Counter>("raise 'in body'").is_err());
+}
+
+#[test]
+fn api_weak_value() {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    let cache: Rc<RefCell<HashMap<String, WeakValue>>> = Rc::new(RefCell::new(HashMap::new()));
+
+    let mruby = Mruby::new();
+
+    {
+        let cache = cache.clone();
+
+        mruby.def_method_on("Object", "remember", mrfn!(|mruby, _slf: Value, name: String,
+                                                          value: Value| {
+            value.gc_protect();
+            cache.borrow_mut().insert(name, value.downgrade());
+
+            mruby.nil()
+        })).unwrap();
+    }
+
+    mruby.run("remember 'greeting', 'hello'").unwrap();
+    mruby.full_gc();
+
+    let greeting = cache.borrow()["greeting"].upgrade().unwrap();
+
+    assert_eq!(greeting.to_str().unwrap(), "hello");
+    assert_eq!(Rc::strong_count(&mruby), 2);
+
+    drop(greeting);
+
+    assert_eq!(Rc::strong_count(&mruby), 1);
+
+    drop(mruby);
+
+    assert!(cache.borrow()["greeting"].upgrade().is_none());
+}
+
+#[test]
+fn api_instance_variables() {
+    struct Cont;
+
+    let mruby = Mruby::new();
+
+    mruby.def_class::<Cont>("Container");
+    mruby.def_method::<Cont, _>("initialize", mrfn!(|mruby, slf: Value| {
+        slf.set_var("from_rust", mruby.string("rust"));
+
+        slf.init(Cont)
+    }));
+
+    let result = mruby.run("
+      class Container
+        def remember
+          @from_ruby = :ruby
+          self
+        end
+      end
+
+      Container.new.remember
+    ").unwrap();
+
+    let mut names = result.instance_variables();
+    names.sort();
+
+    assert_eq!(names, vec!["@from_ruby".to_owned(), "@from_rust".to_owned()]);
+
+    let state: Vec<(String, String)> = names.iter().map(|name| {
+        (name.clone(), result.get_var(name).unwrap().inspect())
+    }).collect();
+
+    assert_eq!(state, vec![("@from_ruby".to_owned(), ":ruby".to_owned()),
+                           ("@from_rust".to_owned(), "\"rust\"".to_owned())]);
+
+    assert!(mruby.run("Object.new").unwrap().instance_variables().is_empty());
+    assert!(mruby.nil().instance_variables().is_empty());
+}
+
+#[test]
+fn api_set_instruction_limit() {
+    let mruby = Mruby::new();
+
+    mruby.set_instruction_limit(100_000);
+
+    assert_eq!(mruby.run("(1..10).reduce(:+)").unwrap().to_i32().unwrap(), 55);
+
+    let script = "
+      $attempts = 0
+
+      begin
+        loop { $attempts += 1 }
+      rescue => e
+        :rescued_standard_error
+      end
+    ";
+
+    match mruby.run(script) {
+        Err(MrubyError::Resource(err)) => assert_eq!(err, "instruction limit exceeded"),
+        _                              => panic!("expected ResourceError")
+    }
+
+    assert_eq!(mruby.run("1").unwrap().to_i32().unwrap(), 1);
+    assert!(mruby.run("$attempts").unwrap().to_i32().unwrap() > 0);
+
+    mruby.set_instruction_limit(1_000);
+
+    let result = mruby.run("
+      begin
+        loop {}
+      rescue ResourceError
+        :caught
+      end
+    ");
+
+    match result {
+        Err(MrubyError::Resource(_)) => (),
+        _                            => panic!("ResourceError should not be recoverable")
+    }
+
+    let worker = mruby.run("
+      class Worker
+        def go; loop {}; end
+        def ok; 1; end
+      end
+
+      Worker.new
+    ").unwrap();
+
+    match worker.call("go", vec![]) {
+        Err(MrubyError::Resource(_)) => (),
+        _                            => panic!("expected ResourceError")
+    }
+
+    assert_eq!(worker.call("ok", vec![]).unwrap().to_i32().unwrap(), 1);
+    assert_eq!(worker.instance_eval("ok + 1").unwrap().to_i32().unwrap(), 2);
+
+    let handler = mruby.run("proc { |x| x + 1 }").unwrap();
+
+    for i in 0..1_000 {
+        let result = handler.call_proc(vec![mruby.fixnum(i)]).unwrap();
+
+        assert_eq!(result.to_i32().unwrap(), i + 1);
+    }
+}
+
+#[test]
+fn api_instance_eval() {
+    use std::cell::RefCell;
+
+    struct Router {
+        routes: Vec<(String, String)>
+    }
+
+    let mruby = Mruby::new();
+
+    mruby.def_class_cell::<Router>("Router");
+    mruby.def_method::<RefCell<Router>, _>("get", mrfn!(|mruby, slf: Value, path: String,
+                                                         action: String| {
+        slf.to_obj_mut::<Router>().unwrap().routes.push((path, action));
+
+        mruby.nil()
+    }));
+
+    let router = mruby.obj(RefCell::new(Router { routes: vec![] }));
+
+    router.def_singleton_method("root", mrfn!(|mruby, slf: Value, action: String| {
+        slf.call("get", vec![mruby.string("/"), mruby.string(&action)]).unwrap()
+    })).unwrap();
+
+    let result = router.instance_eval("
+      root 'home#index'
+      get '/users', 'users#index'
+
+      def helper
+        :defined
+      end
+
+      self
+    ").unwrap();
+
+    assert_eq!(result.to_obj::<RefCell<Router>>().unwrap().borrow().routes, vec![
+        ("/".to_owned(), "home#index".to_owned()),
+        ("/users".to_owned(), "users#index".to_owned())
+    ]);
+    assert_eq!(router.call("helper", vec![]).unwrap(), mruby.symbol("defined"));
+    assert!(!mruby.obj(RefCell::new(Router { routes: vec![] })).respond_to("helper"));
+
+    match router.instance_eval("raise ArgumentError, 'bad route'") {
+        Err(MrubyError::Runtime(err)) => assert_eq!(err, "ArgumentError: bad route"),
+        _ => panic!("expected ArgumentError")
+    }
+
+    match router.instance_eval("get '/broken', ") {
+        Err(MrubyError::Runtime(err)) => assert!(err.starts_with("SyntaxError: ")),
+        _ => panic!("expected SyntaxError")
+    }
+    assert_eq!(router.instance_eval("1 + 1").unwrap().to_i32().unwrap(), 2);
+    assert_eq!(mruby.run("[1, 2].map { |i| i * 3 }").unwrap().to_vec_of::<i32>().unwrap(),
+               vec![3, 6]);
+}
+
+#[test]
+fn api_f32() {
+    let mruby = Mruby::new();
+
+    let value = mruby.float32(0.1);
+
+    assert_eq!(value.to_f32().unwrap(), 0.1);
+    assert_eq!(0.25f32.to_value(&mruby).to_f64().unwrap(), 0.25);
+    assert_eq!(f32::from_value(&mruby.run("2.0 ** 3").unwrap()).unwrap(), 8.0);
+    assert_eq!(Vec::<f32>::from_value(&mruby.run("[0.5, 1.5]").unwrap()).unwrap(),
+               vec![0.5, 1.5]);
+
+    assert!(mruby.run("Float::INFINITY").unwrap().to_f32().unwrap().is_infinite());
+    assert!(mruby.run("Float::NAN").unwrap().to_f32().unwrap().is_nan());
+    assert_eq!(mruby.run("1e-300").unwrap().to_f32().unwrap(), 0.0);
+
+    match mruby.run("-1e40").unwrap().to_f32() {
+        Err(MrubyError::Cast(expected)) => assert_eq!(expected, "Float in the range of f32"),
+        _ => panic!("expected Cast")
+    }
+
+    assert!(mruby.fixnum(1).to_f32().is_err());
+}
+
+#[test]
+fn api_identical() {
+    let mruby = Mruby::new();
+
+    let list = mruby.run("list = [1, 2]; list << list; list").unwrap();
+    let items = list.to_vec().unwrap();
+
+    assert!(items[2].identical(&list));
+    assert!(!items[0].identical(&list));
+
+    let copy = list.ruby_dup();
+
+    assert!(!copy.identical(&list));
+    assert!(copy.to_vec().unwrap()[2].identical(&list));
+
+    let symbol = mruby.run(":name").unwrap();
+
+    assert!(symbol.identical(&mruby.symbol("name")));
+    assert!(mruby.nil().identical(&mruby.run("nil").unwrap()));
+    assert!(!mruby.fixnum(1).identical(&mruby.float(1.0)));
+    assert!(!mruby.run("'a'").unwrap().identical(&mruby.run("'a'").unwrap()));
+}
+
+#[test]
+fn api_panic_superclass() {
+    let mruby = Mruby::new();
+
+    mruby.def_method_on("Object", "explode", mrfn!(|_mruby, _slf: Value| {
+        panic!("exploded")
+    })).unwrap();
+
+    match mruby.run("begin; explode; rescue; :rescued; end") {
+        Err(MrubyError::Panic(message)) => assert_eq!(message, "exploded"),
+        _ => panic!("bare rescue should not catch panics by default")
+    }
+
+    let mruby = Mruby::new_with_opts(MrubyOptions {
+        panic_superclass: "StandardError".to_owned(),
+        ..MrubyOptions::default()
+    });
+
+    mruby.def_method_on("Object", "explode", mrfn!(|_mruby, _slf: Value| {
+        panic!("exploded")
+    })).unwrap();
+
+    let rescued = mruby.run("begin; explode; rescue => e; e.message; end").unwrap();
+
+    assert_eq!(rescued.to_str().unwrap(), "exploded");
+    assert!(mruby.run("RustPanic.ancestors.include?(StandardError)").unwrap().to_bool().unwrap());
+
+    let mruby = Mruby::new_with_opts(MrubyOptions {
+        panic_class: "HostPanic".to_owned(),
+        ..MrubyOptions::default()
+    });
+
+    mruby.def_method_on("Object", "explode", mrfn!(|_mruby, _slf: Value| {
+        panic!("exploded")
+    })).unwrap();
+
+    let rescued = mruby.run("begin; explode; rescue HostPanic => e; e.class.to_s; end").unwrap();
+
+    assert_eq!(rescued.to_str().unwrap(), "HostPanic");
+    assert!(mruby.run("RustPanic").is_err());
+
+    match mruby.run("explode") {
+        Err(MrubyError::Panic(message)) => assert_eq!(message, "exploded"),
+        _ => panic!("expected Panic")
+    }
+}
+
+#[test]
+#[should_panic]
+fn api_panic_superclass_module() {
+    Mruby::new_with_opts(MrubyOptions {
+        panic_superclass: "Kernel".to_owned(),
+        ..MrubyOptions::default()
+    });
+}
+
+#[test]
+#[should_panic]
+fn api_panic_class_defined() {
+    Mruby::new_with_opts(MrubyOptions {
+        panic_class: "String".to_owned(),
+        ..MrubyOptions::default()
+    });
+}
+
+#[test]
+fn api_try_call() {
+    let mruby = Mruby::new();
+
+    let obj = mruby.run("
+      class Duck
+        def quack; :quack; end
+        def broken; nope; end
+
+        def method_missing(name, *args)
+          name == :waddle ? :waddle : super
+        end
+
+        def respond_to_missing?(name, priv)
+          name == :waddle
+        end
+
+        private
+
+        def secret; :secret; end
+      end
+
+      Duck.new
+    ").unwrap();
+
+    let quack = obj.try_call("quack", vec![]).unwrap().unwrap();
+
+    assert_eq!(quack.to_str().unwrap(), "quack");
+    assert_eq!(obj.try_call("waddle", vec![]).unwrap().unwrap().to_str().unwrap(), "waddle");
+    assert_eq!(obj.try_call("secret", vec![]).unwrap().unwrap().to_str().unwrap(), "secret");
+    assert!(obj.try_call("fly", vec![]).unwrap().is_none());
+
+    match obj.try_call("broken", vec![]) {
+        Err(MrubyError::Runtime(message)) => assert!(message.starts_with("NoMethodError")),
+        _ => panic!("broken should raise")
+    }
+}
+
+#[test]
+fn api_value_key() {
+    use std::collections::HashSet;
+
+    let mruby = Mruby::new();
+
+    assert_eq!(mruby.fixnum(1), mruby.float(1.0));
+    assert!(ValueKey(mruby.fixnum(1)) != ValueKey(mruby.float(1.0)));
+    assert!(ValueKey(mruby.float(0.0)) == ValueKey(mruby.float(-0.0)));
+
+    let odd = mruby.run("
+      class Odd
+        def hash; raise 'no hash'; end
+      end
+
+      class Big
+        def hash; 2.5; end
+      end
+
+      [Odd.new, Big.new]
+    ").unwrap().to_vec().unwrap();
+
+    let mut keys = HashSet::new();
+
+    for value in odd {
+        keys.insert(ValueKey(value.clone()));
+
+        assert!(keys.contains(&ValueKey(value)));
+    }
+
+    keys.insert(ValueKey(mruby.run("[1, [2]]").unwrap()));
+
+    assert!(keys.contains(&ValueKey(mruby.run("[1, [2]]").unwrap())));
+    assert!(!keys.contains(&ValueKey(mruby.run("[1, [2.0]]").unwrap())));
+
+    let nan = mruby.run("0.0 / 0.0").unwrap();
+
+    keys.insert(ValueKey(nan.clone()));
+
+    assert!(keys.contains(&ValueKey(nan)));
+    assert!(keys.contains(&ValueKey(mruby.float(std::f64::NAN))));
+
+    let recursive = mruby.run("a = [1]; a << a; a").unwrap();
+
+    keys.insert(ValueKey(recursive.clone()));
+
+    assert!(keys.contains(&ValueKey(recursive)));
+    assert!(keys.contains(&ValueKey(mruby.run("b = [1]; b << b; b").unwrap())));
+    assert!(!keys.contains(&ValueKey(mruby.run("c = [2]; c << c; c").unwrap())));
+}
+
+#[test]
+fn api_proc_from_free() {
+    use std::rc::Rc;
+
+    let mruby = Mruby::new();
+    let captured = Rc::new(());
+
+    let arena = mruby.gc_arena_save();
+
+    {
+        let captured = captured.clone();
+
+        mruby.proc_from(move |mruby, _args| mruby.fixnum(Rc::strong_count(&captured) as i32));
+    }
+
+    assert_eq!(Rc::strong_count(&captured), 2);
+
+    mruby.gc_arena_restore(arena);
+    mruby.full_gc();
+
+    assert_eq!(Rc::strong_count(&captured), 1);
+}
+
+#[test]
+fn api_def_module_function() {
+    let mruby = Mruby::new();
+
+    mruby.def_module("Config");
+    mruby.def_module_function("Config", "double", mrfn!(|mruby, _slf: Value, v: i32| {
+        mruby.fixnum(v * 2)
+    }));
+
+    let result = mruby.run("
+      class Worker
+        include Config
+
+        def run
+          double 4
+        end
+      end
+
+      [Config.double(3), Worker.new.run]
+    ").unwrap();
+
+    assert_eq!(result.to_vec_of::<i32>().unwrap(), vec![6, 8]);
+}
+
+#[test]
+fn api_run_with_timeout() {
+    use std::time::Duration;
+
+    let mruby = Mruby::new();
+
+    match mruby.run_with_timeout("loop {}", Duration::from_millis(20)) {
+        Err(MrubyError::Runtime(err)) => assert!(err.contains("execution timed out")),
+        _ => panic!("expected RuntimeError")
+    }
+
+    let result = mruby.run_with_timeout("
+      begin
+        loop {}
+      rescue => e
+        [:caught, e.message]
+      end
+    ", Duration::from_millis(20)).unwrap();
+
+    assert_eq!(result, mruby.run("[:caught, 'execution timed out']").unwrap());
+    assert_eq!(mruby.run("loop { break 2 }").unwrap().to_i32().unwrap(), 2);
+}
+
+#[test]
+fn api_require_relative() {
+    let mruby = Mruby::new();
+
+    let result = mruby.execute(Path::new("tests/a/main.rb")).unwrap();
+
+    assert_eq!(result.to_str().unwrap(), "helper");
+}
+
+#[test]
+fn api_gc_arena() {
+    let mruby = Mruby::new();
+
+    let idx = mruby.gc_arena_save();
+
+    for i in 0..100000 {
+        mruby.with_gc_arena(|| {
+            mruby.array(vec![mruby.fixnum(i)]);
+        });
+
+        assert_eq!(mruby.gc_arena_save(), idx);
+    }
+}
+
+#[test]
+fn api_backtrace() {
+    let mruby = Mruby::new();
+
+    mruby.filename("script.rb");
+
+    let result = mruby.run("
+        def outer
+          inner
+        end
+
+        def inner
+          1.nope
+        end
+
+        outer
+    ");
+
+    match result {
+        Err(MrubyError::RuntimeWithBacktrace { message, backtrace }) => {
+            assert_eq!(message, "script.rb:7: undefined method 'nope' for 1 (NoMethodError)");
+            assert_eq!(backtrace, vec!["script.rb:7:in Object.inner",
+                                       "script.rb:3:in Object.outer",
+                                       "script.rb:10"]);
+        },
+        _ => assert!(false)
+    }
+}
+
+#[test]
+fn api_optional_args() {
+    let mruby = Mruby::new();
+
+    struct Cont;
+
+    mruby.def_class::<Cont>("Container");
+    mruby.def_class_method::<Cont, _>("greet", mrfn!(|mruby, _slf: Value, name: Option<String>,
+                                                     punct: Option<String>| {
+        let name = name.unwrap_or("world".to_owned());
+        let punct = punct.unwrap_or("!".to_owned());
+
+        mruby.string(&format!("hello {}{}", name, punct))
+    }));
+
+    assert_eq!(mruby.run("Container.greet").unwrap().to_str().unwrap(), "hello world!");
+    assert_eq!(mruby.run("Container.greet 'a'").unwrap().to_str().unwrap(), "hello a!");
+    assert_eq!(mruby.run("Container.greet 'a', '?'").unwrap().to_str().unwrap(), "hello a?");
+}
+
+#[test]
+fn api_kwargs() {
+    let mruby = Mruby::new();
+
+    struct Cont;
+
+    mruby.def_class::<Cont>("Container");
+    mruby.def_class_method::<Cont, _>("scale", mrfn!(|mruby, _slf: Value;
+                                                     by: f64, round: Option<bool>| {
+        let value = 1.5 * by;
+
+        match round {
+            Some(true) => mruby.float(value.round()),
+            _          => mruby.float(value)
+        }
+    }));
+
+    assert_eq!(mruby.run("Container.scale by: 3.0").unwrap().to_f64().unwrap(), 4.5);
+    assert_eq!(mruby.run("Container.scale by: 3.0, round: true").unwrap().to_f64().unwrap(), 5.0);
+    assert!(mruby.run("Container.scale").is_err());
+
+    match mruby.run("Container.scale by: 3.0, rnd: true") {
+        Err(MrubyError::Runtime(err)) => assert_eq!(err, "ArgumentError: unknown keyword: :rnd"),
+        _ => panic!("expected ArgumentError")
+    }
+
+    match mruby.run("Container.scale 'by' => 3.0") {
+        Err(MrubyError::Runtime(err)) => assert_eq!(err, "ArgumentError: unknown keyword: \"by\""),
+        _ => panic!("expected ArgumentError")
+    }
+
+    mruby.def_class_method::<Cont, _>("resize", mrfn!(|mruby, _slf: Value, a: i32; w: i32| {
+        mruby.fixnum(a * w)
+    }));
+
+    assert_eq!(mruby.run("Container.resize 3, w: 2").unwrap().to_i32().unwrap(), 6);
+
+    match mruby.run("Container.resize w: 2") {
+        Err(MrubyError::Runtime(err)) => {
+            assert_eq!(err, "ArgumentError: wrong number of arguments (given 0, expected 1)")
+        }
+        _ => panic!("expected ArgumentError")
+    }
+}
+
+#[test]
+fn api_block_capture() {
+    let mruby = Mruby::new();
+
+    struct Cont;
+
+    mruby.def_class::<Cont>("Container");
+    mruby.def_class_method::<Cont, _>("given?", mrfn!(|mruby, _slf: Value, &block| {
+        mruby.bool(block.is_proc())
+    }));
+    mruby.def_class_method::<Cont, _>("map", mrfn!(|mruby, _slf: Value, a: i32, b: Option<i32>,
+                                                    &block| {
+        let a = block.call("call", vec![mruby.fixnum(a)]).unwrap();
+        let b = b.map(|b| block.call("call", vec![mruby.fixnum(b)]).unwrap());
+
+        mruby.array(vec![a, b.unwrap_or(mruby.nil())])
+    }));
+
+    let result = mruby.run("[Container.given? {}, Container.given?]").unwrap();
+
+    assert_eq!(result.to_vec().unwrap(), vec![mruby.bool(true), mruby.bool(false)]);
+
+    let result = mruby.run("Container.map(1) { |x| x + 1 }").unwrap();
+
+    assert_eq!(result.to_vec().unwrap(), vec![mruby.fixnum(2), mruby.nil()]);
+
+    let result = mruby.run("Container.map(1, 2) { |x| x * 3 }").unwrap();
+
+    assert_eq!(result.to_vec().unwrap(), vec![mruby.fixnum(3), mruby.fixnum(6)]);
+}
+
+#[test]
+fn api_compile_proc() {
+    let mruby = Mruby::new();
+
+    mruby.filename("loop.rb");
+
+    let script = mruby.compile_proc("
+      x = (x || 0) + 1
+      $sum = ($sum || 0) + x
+    ").unwrap();
+
+    for _ in 0..10000 {
+        mruby.run_compiled(&script).unwrap();
+    }
+
+    assert_eq!(mruby.run("$sum").unwrap().to_i32().unwrap(), 10000);
+
+    let failing = mruby.compile_proc("\nfail 'nope'").unwrap();
+
+    match mruby.run_compiled(&failing) {
+        Err(MrubyError::Runtime(err)) => assert_eq!(err, "loop.rb:2: nope (RuntimeError)"),
+        _ => panic!("expected a runtime error")
+    }
+
+    drop(script);
+    drop(failing);
+
+    mruby.full_gc();
+
+    assert_eq!(mruby.run("1 + 1").unwrap().to_i32().unwrap(), 2);
+}
+
+#[test]
+fn api_arity() {
+    let mruby = Mruby::new();
+
+    struct Cont;
+
+    mruby.def_class::<Cont>("Container");
+    mruby.def_class_method::<Cont, _>("none", mrfn!(|mruby, _slf: Value| {
+        mruby.nil()
+    }));
+    mruby.def_class_method::<Cont, _>("two", mrfn!(|mruby, _slf: Value, a: i32, b: i32| {
+        mruby.fixnum(a + b)
+    }));
+    mruby.def_class_method::<Cont, _>("opt", mrfn!(|mruby, _slf: Value, a: i32, b: Option<i32>| {
+        mruby.fixnum(a + b.unwrap_or(0))
+    }));
+    mruby.def_class_method::<Cont, _>("rest", mrfn!(|mruby, _slf: Value, a: i32; args| {
+        mruby.fixnum(a + args.len() as i32)
+    }));
+
+    let error = |script| {
+        match mruby.run(script) {
+            Err(MrubyError::Runtime(err)) => err,
+            _ => panic!("expected an ArgumentError")
+        }
+    };
+
+    assert_eq!(error("Container.none 1"),
+               "ArgumentError: wrong number of arguments (given 1, expected 0)");
+    assert_eq!(error("Container.two 1"),
+               "ArgumentError: wrong number of arguments (given 1, expected 2)");
+    assert_eq!(error("Container.two 1, 2, 3"),
+               "ArgumentError: wrong number of arguments (given 3, expected 2)");
+    assert_eq!(error("Container.opt"),
+               "ArgumentError: wrong number of arguments (given 0, expected 1..2)");
+    assert_eq!(error("Container.opt 1, 2, 3"),
+               "ArgumentError: wrong number of arguments (given 3, expected 1..2)");
+    assert_eq!(error("Container.rest"),
+               "ArgumentError: wrong number of arguments (given 0, expected 1+)");
+
+    assert_eq!(mruby.run("Container.two 1, 2").unwrap().to_i32().unwrap(), 3);
+    assert_eq!(mruby.run("Container.opt 1").unwrap().to_i32().unwrap(), 1);
+    assert_eq!(mruby.run("Container.rest 1, 2, 3").unwrap().to_i32().unwrap(), 3);
+    assert_eq!(mruby.run("Container.two(*[1, 2])").unwrap().to_i32().unwrap(), 3);
+
+    struct Other;
+
+    mruby.def_class::<Other>("Other");
+
+    assert_eq!(mruby.run("Other").unwrap().to_string(), "Other");
+}
+
+#[test]
+fn api_block() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let mruby = Mruby::new();
+
+    let seen = Rc::new(RefCell::new(vec![]));
+    let block_seen = seen.clone();
+
+    let block = mruby.proc_from(move |mruby, args| {
+        block_seen.borrow_mut().push(args[0].to_i32().unwrap());
+
+        mruby.nil()
+    });
+
+    let array = mruby.run("[1, 2, 3]").unwrap();
+
+    array.call_with_block("each", vec![], block).unwrap();
+
+    assert_eq!(*seen.borrow(), vec![1, 2, 3]);
+}
+
+#[test]
+fn api_class_under_nested() {
+    let mruby = Mruby::new();
+
+    mruby.def_class_under::<Scalar>("Math::Linear", "Scalar");
+
+    let result = mruby.run("
+        module Math::Linear
+          def self.hi
+            'hi'
+          end
+        end
+
+        Math::Linear.hi + Math::Linear::Scalar.to_s
+    ").unwrap();
+
+    assert_eq!(result.to_str().unwrap(), "hiMath::Linear::Scalar");
+    assert_eq!(mruby.class_name::<Scalar>().unwrap(), "Math::Linear::Scalar");
+}
+
+#[test]
+fn api_include_module() {
+    let mruby = Mruby::new();
+
+    Scalar::require(mruby.clone());
+
+    mruby.def_method::<Scalar, _>("<=>", mrfn!(|mruby, slf: Scalar, other: Scalar| {
+        match slf.value.partial_cmp(&other.value) {
+            Some(ordering) => mruby.fixnum(ordering as i32),
+            None           => mruby.nil()
+        }
+    }));
+
+    mruby.include_module::<Scalar>("Comparable").unwrap();
+
+    let result = mruby.run("Scalar.new(3.0) > Scalar.new(2.0)").unwrap();
+
+    assert_eq!(result.to_bool().unwrap(), true);
+
+    let result = mruby.run("Scalar.new(1.0).between?(Scalar.new(2.0), Scalar.new(3.0))").unwrap();
+
+    assert_eq!(result.to_bool().unwrap(), false);
+
+    assert!(mruby.include_module::<Scalar>("Nope").is_err());
+    assert!(mruby.include_module::<Vector>("Comparable").is_err());
+}
+
+#[test]
+fn api_inspect_recursion() {
+    let mruby = Mruby::new();
+
+    Scalar::require(mruby.clone());
+
+    mruby.def_method::<Scalar, _>("inspect", mrfn!(|mruby, slf: Value| {
+        mruby.string(&format!("#<Scalar {:?}>", slf))
+    }));
+
+    let scalar = mruby.run("Scalar.new 1.0").unwrap();
+
+    assert_eq!(format!("{:?}", scalar), "#<Scalar #<Scalar>>");
+}
+
 describe!(Scalar, "
   context 'when zero' do
     let(:zero) { Scalar.new 0 }
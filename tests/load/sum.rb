@@ -0,0 +1,5 @@
+a = 1
+b = 2
+c = 3
+
+a + b + c